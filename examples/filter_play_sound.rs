@@ -3,15 +3,14 @@
 /// dave horner 10/24
 /// 
 /// Default settings for Nordic Thingy53, nrf5340dk, and other nordic devices (baud/com).
-use bytes::BytesMut;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use futures::stream::StreamExt;
+use futures_util::stream::StreamExt;
 use std::sync::Mutex;
 use std::sync::Arc;
-use std::{env, io, str};
+use std::env;
 use tokio::time::Duration;
+use tokio_serial::frame::{LineCodec, SerialFramed, Utf8Policy};
 use tokio_serial::SerialPortBuilderExt;
-use tokio_util::codec::{Decoder, Encoder};
 extern crate anyhow;
 
 #[cfg(unix)]
@@ -58,12 +57,12 @@ async fn main() -> tokio_serial::Result<()> {
     #[cfg(unix)]
     port.set_exclusive(false)
         .expect("Unable to set serial port exclusive to false");
-    let mut reader = LineCodec.framed(port);
+    let mut reader = SerialFramed::new(port, LineCodec::new().with_utf8_policy(Utf8Policy::Lossy));
 
     let find_text_map = create_find_text_map();
     while let Some(line_result) = reader.next().await {
         let line = line_result.expect("Failed to read line");
-        print!("{}", line);
+        println!("{}", line);
 
         for (phrase, params) in &find_text_map {
             if line.contains(phrase) {
@@ -79,38 +78,6 @@ async fn main() -> tokio_serial::Result<()> {
 }
 
 
-///////////////////////////////////
-///  Codec
-/// ///////////////////////////////
-
-struct LineCodec;
-
-impl Decoder for LineCodec {
-    type Item = String;
-    type Error = io::Error;
-
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let newline = src.as_ref().iter().position(|b| *b == b'\n');
-        if let Some(n) = newline {
-            let line = src.split_to(n + 1);
-            return match str::from_utf8(line.as_ref()) {
-                Ok(s) => Ok(Some(s.to_string())),
-                Err(_) => Err(io::Error::new(io::ErrorKind::Other, "Invalid String")),
-            };
-        }
-        Ok(None)
-    }
-}
-
-impl Encoder<String> for LineCodec {
-    type Error = io::Error;
-
-    fn encode(&mut self, _item: String, _dst: &mut BytesMut) -> Result<(), Self::Error> {
-        Ok(())
-    }
-}
-
-
 ///////////////////////////////////
 ///  All this code to make noise.
 /// ///////////////////////////////
@@ -1,10 +1,9 @@
 #![warn(rust_2018_idioms)]
 
-use futures::stream::StreamExt;
-use std::{env, io, str};
-use tokio_util::codec::{Decoder, Encoder};
+use futures_util::stream::StreamExt;
+use std::env;
 
-use bytes::BytesMut;
+use tokio_serial::frame::{LineCodec, SerialFramed};
 use tokio_serial::SerialPortBuilderExt;
 
 #[cfg(unix)]
@@ -12,33 +11,6 @@ const DEFAULT_TTY: &str = "/dev/ttyUSB0";
 #[cfg(windows)]
 const DEFAULT_TTY: &str = "COM1";
 
-struct LineCodec;
-
-impl Decoder for LineCodec {
-    type Item = String;
-    type Error = io::Error;
-
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let newline = src.as_ref().iter().position(|b| *b == b'\n');
-        if let Some(n) = newline {
-            let line = src.split_to(n + 1);
-            return match str::from_utf8(line.as_ref()) {
-                Ok(s) => Ok(Some(s.to_string())),
-                Err(_) => Err(io::Error::new(io::ErrorKind::Other, "Invalid String")),
-            };
-        }
-        Ok(None)
-    }
-}
-
-impl Encoder<String> for LineCodec {
-    type Error = io::Error;
-
-    fn encode(&mut self, _item: String, _dst: &mut BytesMut) -> Result<(), Self::Error> {
-        Ok(())
-    }
-}
-
 #[tokio::main]
 async fn main() -> tokio_serial::Result<()> {
     let mut args = env::args();
@@ -50,7 +22,7 @@ async fn main() -> tokio_serial::Result<()> {
     port.set_exclusive(false)
         .expect("Unable to set serial port exclusive to false");
 
-    let mut reader = LineCodec.framed(port);
+    let mut reader = SerialFramed::new(port, LineCodec::new());
 
     while let Some(line_result) = reader.next().await {
         let line = line_result.expect("Failed to read line");
@@ -0,0 +1,152 @@
+//! Hayes AT commands, as spoken by cellular and other modems.
+//!
+//! [`AtClient`] sends one command at a time and collects the lines of its response up
+//! to the final result code.  Unsolicited result codes (URCs) such as `RING` or
+//! `+CREG: 1` can arrive at any time, even in the middle of a response; they are
+//! told apart from the response and passed to the [`Urcs`] streams returned by
+//! [`AtClient::subscribe`].
+//!
+//! ```no_run
+//! use tokio_serial::at::AtClient;
+//! use tokio_serial::SerialStream;
+//! use std::time::Duration;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB2", 115200))?;
+//! let mut modem = AtClient::new(port);
+//! let mut urcs = modem.subscribe();
+//!
+//! let quality = modem.command("AT+CSQ").await?;
+//! println!("{:?}", quality);
+//! modem.command("AT+CMGF=1").await?;
+//! modem
+//!     .command_with_prompt("AT+CMGS=\"+15551234567\"", b"Hello", Duration::from_secs(60))
+//!     .await?;
+//!
+//! // URCs are only read while the client is busy, so listen for them in between.
+//! let _ = tokio::time::timeout(Duration::from_secs(10), modem.listen()).await;
+//! while let Ok(urc) = urcs.try_recv() {
+//!     println!("{}", urc);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module is only available with the `at` feature.
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{fmt, io};
+
+mod client;
+
+pub use self::client::AtClient;
+
+/// The error reported with a `+CME ERROR` or `+CMS ERROR` result code.
+///
+/// Depending on the `AT+CMEE` setting, modems report errors by number or by a verbose
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorReport {
+    /// A numeric error code.
+    Code(u16),
+    /// A verbose error message.
+    Text(String),
+}
+
+impl ErrorReport {
+    pub(crate) fn parse(report: &str) -> ErrorReport {
+        let report = report.trim();
+        match report.parse() {
+            Ok(code) => ErrorReport::Code(code),
+            Err(_) => ErrorReport::Text(report.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorReport::Code(code) => write!(f, "{}", code),
+            ErrorReport::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// A stream of unsolicited result codes, created by [`AtClient::subscribe`].
+///
+/// Each URC is a single line, without its line ending.  The stream ends once the
+/// client is dropped.
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct Urcs {
+    rx: mpsc::UnboundedReceiver<String>,
+}
+
+impl Urcs {
+    /// Waits for the next URC, returning `None` once the client is dropped.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.rx.recv().await
+    }
+
+    /// Returns the next URC if one has arrived already.
+    pub fn try_recv(&mut self) -> Result<String, mpsc::error::TryRecvError> {
+        self.rx.try_recv()
+    }
+}
+
+impl Stream for Urcs {
+    type Item = String;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+/// An error from an [`AtClient`].
+#[derive(Debug)]
+pub enum AtError {
+    /// The command failed with `ERROR`.
+    Error,
+    /// The command failed with `+CME ERROR`, an equipment error.
+    Cme(ErrorReport),
+    /// The command failed with `+CMS ERROR`, a messaging error.
+    Cms(ErrorReport),
+    /// No final result code arrived in time.
+    Timeout,
+    /// The command could not be sent, because it contains a line ending, or its data a
+    /// Ctrl-Z or ESC.
+    InvalidCommand,
+    /// An I/O error occurred, or the port reported end of file.
+    Io(io::Error),
+}
+
+impl fmt::Display for AtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtError::Error => write!(f, "command failed"),
+            AtError::Cme(report) => write!(f, "equipment error: {}", report),
+            AtError::Cms(report) => write!(f, "messaging error: {}", report),
+            AtError::Timeout => write!(f, "no response from modem"),
+            AtError::InvalidCommand => write!(f, "command contains a control character"),
+            AtError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AtError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AtError {
+    fn from(err: io::Error) -> Self {
+        AtError::Io(err)
+    }
+}
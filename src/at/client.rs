@@ -0,0 +1,328 @@
+//! Sending AT commands and reading their responses.
+use super::{AtError, ErrorReport, Urcs};
+use crate::SerialStream;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
+
+use std::io;
+use std::time::Duration;
+
+/// Ends the data sent after a `>` prompt.
+const CTRL_Z: u8 = 0x1A;
+/// Cancels the data sent after a `>` prompt.
+const ESC: u8 = 0x1B;
+
+/// The URCs of 27.007 and 27.005 that are recognised by default.
+const DEFAULT_URC_PREFIXES: &[&str] = &[
+    "RING", "+CRING:", "+CLIP:", "+CREG:", "+CGREG:", "+CEREG:", "+CMTI:", "+CDSI:", "+CBMI:",
+    "+CUSD:", "+CGEV:", "+CIEV:",
+];
+
+/// A line read from the modem.
+enum Line {
+    Text(String),
+    Prompt,
+}
+
+/// Parses a final result code, or returns `None` for any other line.
+fn final_result(line: &str) -> Option<Result<(), AtError>> {
+    if line == "OK" {
+        Some(Ok(()))
+    } else if line == "ERROR" {
+        Some(Err(AtError::Error))
+    } else if let Some(report) = line.strip_prefix("+CME ERROR:") {
+        Some(Err(AtError::Cme(ErrorReport::parse(report))))
+    } else {
+        line.strip_prefix("+CMS ERROR:")
+            .map(|report| Err(AtError::Cms(ErrorReport::parse(report))))
+    }
+}
+
+/// An AT command client for a modem on a serial port.
+///
+/// The client sends one command at a time and returns the lines of the response up to
+/// the final result code: `OK`, or `ERROR`, `+CME ERROR` or `+CMS ERROR` as an
+/// [`AtError`].  The echo of the command, if the modem has echo turned on, is skipped,
+/// and so are blank lines.
+///
+/// Lines that start with one of the URC prefixes are unsolicited result codes and are
+/// sent to the [`Urcs`] streams rather than being taken as part of the response,
+/// unless they answer the command itself: `+CREG: 0,1` is the response to `AT+CREG?`,
+/// but a URC in the middle of any other command.  Every line that arrives while no
+/// command is running is taken as a URC.  The client only reads from the port while
+/// it runs a command or [`listen`](AtClient::listen)s; URCs that span more than one
+/// line, such as `+CMT`, are not supported.
+///
+/// ```no_run
+/// use tokio_serial::at::AtClient;
+/// use tokio_serial::SerialStream;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB2", 115200))?;
+/// let mut modem = AtClient::new(port)
+///     .with_timeout(Duration::from_millis(500))
+///     .with_urc_prefix("+QIND:");
+/// let operators = modem
+///     .command_with_timeout("AT+COPS=?", Duration::from_secs(180))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AtClient {
+    port: SerialStream,
+    buf: Vec<u8>,
+    timeout: Duration,
+    urc_prefixes: Vec<String>,
+    subscribers: Vec<mpsc::UnboundedSender<String>>,
+    // Whether the modem may still be waiting for the data after a prompt.
+    awaiting_prompt: bool,
+}
+
+impl AtClient {
+    /// Create a client on `port`, with a response timeout of one second and the URC
+    /// prefixes of the common network registration, call, message and supplementary
+    /// service notifications.
+    pub fn new(port: SerialStream) -> AtClient {
+        Self {
+            port,
+            buf: Vec::new(),
+            timeout: Duration::from_secs(1),
+            urc_prefixes: DEFAULT_URC_PREFIXES
+                .iter()
+                .map(|prefix| prefix.to_string())
+                .collect(),
+            subscribers: Vec::new(),
+            awaiting_prompt: false,
+        }
+    }
+
+    /// Sets how long to wait for the final result code of a command, counted from when
+    /// it was sent.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a prefix that marks a line as a URC, such as a vendor specific `+QIND:`.
+    pub fn with_urc_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.urc_prefixes.push(prefix.into());
+        self
+    }
+
+    /// Returns how long to wait for the final result code of a command.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the prefixes that mark a line as a URC.
+    pub fn urc_prefixes(&self) -> &[String] {
+        &self.urc_prefixes
+    }
+
+    /// Returns a stream of the URCs read from now on.
+    ///
+    /// Every stream gets every URC.  URCs read while there is no stream are dropped.
+    pub fn subscribe(&mut self) -> Urcs {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        Urcs { rx }
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the client, returning the underlying port.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Sends `command`, such as `AT+CSQ`, and returns the lines of its response before
+    /// the final `OK`.
+    pub async fn command(&mut self, command: &str) -> Result<Vec<String>, AtError> {
+        self.command_with_timeout(command, self.timeout).await
+    }
+
+    /// Sends `command` and returns the lines of its response, waiting `timeout` rather
+    /// than the configured timeout for the final result code.
+    pub async fn command_with_timeout(
+        &mut self,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<Vec<String>, AtError> {
+        self.transact(command, None, timeout).await
+    }
+
+    /// Sends `command`, such as `AT+CMGS="+15551234567"`, waits for the `>` prompt and
+    /// sends `data` followed by Ctrl-Z, then returns the lines of the response.
+    ///
+    /// `timeout` covers the whole exchange.  If it runs out before the data was sent,
+    /// the command is cancelled with ESC.
+    pub async fn command_with_prompt(
+        &mut self,
+        command: &str,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<String>, AtError> {
+        if data.contains(&CTRL_Z) || data.contains(&ESC) {
+            return Err(AtError::InvalidCommand);
+        }
+        self.transact(command, Some(data), timeout).await
+    }
+
+    /// Reads from the port and passes every line to the [`Urcs`] streams, until the
+    /// port reports end of file.
+    ///
+    /// This never returns while the port is open, so it is meant to be run with a
+    /// timeout or in a `select!` between commands.  Cancelling it loses nothing.
+    pub async fn listen(&mut self) -> Result<(), AtError> {
+        while let Some(line) = self.read_line(false).await? {
+            if let Line::Text(line) = line {
+                self.unsolicited(line);
+            }
+        }
+        Ok(())
+    }
+
+    async fn transact(
+        &mut self,
+        command: &str,
+        data: Option<&[u8]>,
+        timeout: Duration,
+    ) -> Result<Vec<String>, AtError> {
+        if command.contains(['\r', '\n']) {
+            return Err(AtError::InvalidCommand);
+        }
+        // Whatever was read before the command can't belong to its response.
+        while let Some(Line::Text(line)) = self.take_line(false) {
+            self.unsolicited(line);
+        }
+
+        let deadline = Instant::now() + timeout;
+        self.port
+            .write_all(format!("{}\r", command).as_bytes())
+            .await?;
+        self.awaiting_prompt = data.is_some();
+        let result = time::timeout_at(deadline, self.read_response(command, data)).await;
+        if self.awaiting_prompt {
+            self.awaiting_prompt = false;
+            self.port.write_all(&[ESC]).await?;
+        }
+        result.unwrap_or(Err(AtError::Timeout))
+    }
+
+    async fn read_response(
+        &mut self,
+        command: &str,
+        data: Option<&[u8]>,
+    ) -> Result<Vec<String>, AtError> {
+        let mut lines = Vec::new();
+        loop {
+            let line = match self.read_line(self.awaiting_prompt).await? {
+                Some(Line::Text(line)) => line,
+                Some(Line::Prompt) => {
+                    let data = data.expect("prompts are only awaited with data");
+                    self.port.write_all(data).await?;
+                    self.port.write_all(&[CTRL_Z]).await?;
+                    self.awaiting_prompt = false;
+                    continue;
+                }
+                None => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            };
+
+            if let Some(result) = final_result(&line) {
+                // The modem answered without prompting, so it isn't waiting for data.
+                self.awaiting_prompt = false;
+                return result.map(|_| lines);
+            }
+            if lines.is_empty() && line == command {
+                // The echo of the command.
+                continue;
+            }
+            if self.is_urc(&line, command) {
+                self.unsolicited(line);
+            } else {
+                lines.push(line);
+            }
+        }
+    }
+
+    /// Returns whether `line` is a URC rather than part of the response to `command`.
+    fn is_urc(&self, line: &str, command: &str) -> bool {
+        self.urc_prefixes.iter().any(|prefix| {
+            if !line.starts_with(prefix.as_str()) {
+                return false;
+            }
+            // `+CREG:` answers `AT+CREG?`, `AT+CREG=?` and the like.
+            let name = prefix.trim_end_matches(':');
+            let answers = command
+                .get(..2 + name.len())
+                .map(|start| start.eq_ignore_ascii_case(&format!("AT{}", name)))
+                .unwrap_or(false);
+            !(name.starts_with('+') && answers)
+        })
+    }
+
+    /// Passes a line that arrived outside of a response to the subscribers.
+    fn unsolicited(&mut self, line: String) {
+        if final_result(&line).is_some() {
+            log::debug!("dropping stray result code {:?}", line);
+            return;
+        }
+        if self.subscribers.is_empty() {
+            log::debug!("dropping URC {:?}, nobody subscribed", line);
+        }
+        self.subscribers.retain(|tx| tx.send(line.clone()).is_ok());
+    }
+
+    /// Takes the next line, or the prompt if `prompt` is set, from what was read
+    /// already.
+    fn take_line(&mut self, prompt: bool) -> Option<Line> {
+        loop {
+            let blank = self
+                .buf
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+            self.buf.drain(..blank);
+            if prompt && self.buf.first() == Some(&b'>') {
+                // The space after the prompt is skipped as part of the next line.
+                self.buf.remove(0);
+                return Some(Line::Prompt);
+            }
+
+            let end = self.buf.iter().position(|b| *b == b'\r' || *b == b'\n')?;
+            let line = String::from_utf8_lossy(&self.buf[..end]).trim().to_owned();
+            self.buf.drain(..=end);
+            if !line.is_empty() {
+                return Some(Line::Text(line));
+            }
+        }
+    }
+
+    /// Reads from the port until a whole line, or the prompt if `prompt` is set, has
+    /// arrived.  Returns `None` once the port reports end of file.
+    async fn read_line(&mut self, prompt: bool) -> Result<Option<Line>, AtError> {
+        let mut chunk = [0u8; 256];
+        loop {
+            if let Some(line) = self.take_line(prompt) {
+                return Ok(Some(line));
+            }
+            let n = self.port.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! The basic option of the 3GPP 27.010 (GSM 07.10) multiplexer protocol, CMUX.
+//!
+//! Cellular modules can run several logical channels over one UART, such as PPP data on
+//! one and AT commands on another.  Once the module has been switched into
+//! multiplexer mode, usually with `AT+CMUX=0`, [`Cmux::start`] takes over the port and
+//! returns a [`Multiplexer`] to open channels with and a [`Driver`] that does the I/O.
+//! The driver must be polled for anything to happen, so it is usually spawned onto the
+//! runtime.  Each data link connection (DLC) is a [`Channel`] that implements
+//! `AsyncRead` and `AsyncWrite`.
+//!
+//! ```no_run
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//! use tokio_serial::cmux::Cmux;
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! // ... send AT+CMUX=0 and wait for OK ...
+//! let (mux, driver) = Cmux::new().with_max_frame_size(127).start(port);
+//! let driver = tokio::spawn(driver.run());
+//!
+//! let mut at = mux.open(2).await?;
+//! at.write_all(b"AT+CSQ\r").await?;
+//! let mut response = [0u8; 64];
+//! let n = at.read(&mut response).await?;
+//!
+//! at.shutdown().await?;
+//! mux.close().await?;
+//! let port = driver.await??;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! We always act as the initiator, the side that opens the control channel and the
+//! channels.  Flow control is done per channel with modem status commands (MSC): a
+//! channel stops sending when the other side sets its flow control bit, and asks the
+//! other side to stop while the data it has received isn't being read.  The aggregate
+//! FCon and FCoff commands are honoured as well.
+//!
+//! This module is only available with the `cmux` feature, which enables `codec` as
+//! well.
+use crate::SerialStream;
+
+use bytes::BytesMut;
+use tokio::sync::Notify;
+use tokio::time;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::future::poll_fn;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+mod channel;
+mod driver;
+mod frame;
+
+pub use self::channel::Channel;
+pub use self::driver::Driver;
+
+use self::frame::{Frame, CLD, DISC, MSC, SABM, UIH};
+
+/// How much data a channel holds in each direction.  Beyond this, writes wait, and the
+/// other side is asked to stop sending.
+const BUFFER_SIZE: usize = 4096;
+
+/// The settings of a multiplexer, used to start one on a port.
+///
+/// ```
+/// use tokio_serial::cmux::Cmux;
+/// use std::time::Duration;
+///
+/// let cmux = Cmux::new()
+///     .with_max_frame_size(127)
+///     .with_timeout(Duration::from_millis(300))
+///     .with_retries(5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cmux {
+    max_frame_size: usize,
+    timeout: Duration,
+    retries: usize,
+}
+
+impl Cmux {
+    /// Create settings with the defaults of the basic option: a maximum frame size (N1)
+    /// of 31 bytes, an acknowledgement timer (T1) of 100 ms and 3 retransmissions (N2).
+    pub fn new() -> Cmux {
+        Self {
+            max_frame_size: 31,
+            timeout: Duration::from_millis(100),
+            retries: 3,
+        }
+    }
+
+    /// Sets the maximum length of the information field of a frame, from 1 to 32768.
+    /// It must not be larger than what the other side was configured with, such as the
+    /// `N1` parameter of `AT+CMUX`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_frame_size` is out of range.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        assert!((1..=32768).contains(&max_frame_size));
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Sets how long to wait for the other side to acknowledge opening or closing a
+    /// channel or the multiplexer, before sending the command again.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a command is sent again when it isn't acknowledged.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Returns the maximum length of the information field of a frame.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Returns how long to wait for a command to be acknowledged.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns how many times a command is sent again when it isn't acknowledged.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Takes over `port`, which must be in multiplexer mode already, and returns a
+    /// handle to open channels with and the driver that does the I/O on the port.
+    ///
+    /// The control channel is opened along with the first channel.
+    pub fn start(self, port: SerialStream) -> (Multiplexer, Driver) {
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            notify: Notify::new(),
+            config: self,
+        });
+        let mux = Multiplexer {
+            shared: shared.clone(),
+        };
+        (mux, Driver::new(port, shared))
+    }
+}
+
+impl Default for Cmux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to a running multiplexer, created by [`Cmux::start`].
+///
+/// Handles can be cloned to open channels from several tasks.  Once every handle and
+/// every channel has been dropped, the driver stops and gives the port back, without
+/// closing the multiplexer; use [`close`](Multiplexer::close) for that.
+#[derive(Debug, Clone)]
+pub struct Multiplexer {
+    shared: Arc<Shared>,
+}
+
+impl Multiplexer {
+    /// Opens the channel `dlci`, from 1 to 63, and returns it.  The control channel is
+    /// opened first if it isn't open yet.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `dlci` is out of range.
+    /// * `AddrInUse` if the channel is open already.
+    /// * `ConnectionRefused` if the other side refused to open the channel.
+    /// * `TimedOut` if the other side never answered.
+    /// * `NotConnected` if the multiplexer has been closed.
+    pub async fn open(&self, dlci: u8) -> io::Result<Channel> {
+        if dlci == 0 || dlci > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "DLCI must be from 1 to 63",
+            ));
+        }
+        if self.shared.lock().control != Link::Open {
+            self.establish(0).await?;
+        }
+
+        {
+            let mut state = self.shared.lock();
+            if state.channels.contains_key(&dlci) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "channel is open already",
+                ));
+            }
+            state.channels.insert(dlci, ChannelState::default());
+        }
+        if let Err(err) = self.establish(dlci).await {
+            self.shared.lock().channels.remove(&dlci);
+            return Err(err);
+        }
+
+        // Tell the other side we are ready, which some modules wait for.
+        let mut state = self.shared.lock();
+        state.send_message(MSC, true, &frame::msc_values(dlci, false));
+        self.shared.notify.notify_one();
+        Ok(Channel::new(self.shared.clone(), dlci))
+    }
+
+    /// Closes the multiplexer with a close down command (CLD), after which the other
+    /// side goes back to its normal mode, and the driver stops and gives the port back.
+    ///
+    /// Every channel is closed along with it: reads return end of file and writes
+    /// fail.  Shut channels down first to be sure what was written to them has been
+    /// sent.
+    ///
+    /// ## Errors
+    ///
+    /// * `TimedOut` if the other side never acknowledged the command.  The multiplexer
+    ///   is closed regardless.
+    pub async fn close(&self) -> io::Result<()> {
+        let config = &self.shared.config;
+        let mut acknowledged = self.shared.lock().control != Link::Open;
+        for _ in 0..=config.retries {
+            if acknowledged {
+                break;
+            }
+            {
+                let mut state = self.shared.lock();
+                state.control = Link::Closing;
+                state.send_message(CLD, true, &[]);
+            }
+            self.shared.notify.notify_one();
+            acknowledged = time::timeout(
+                config.timeout,
+                self.shared
+                    .wait(|state| (state.control != Link::Closing).then_some(())),
+            )
+            .await
+            .is_ok();
+        }
+
+        self.shared.lock().shut_down();
+        self.shared.notify.notify_one();
+        if acknowledged {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::TimedOut.into())
+        }
+    }
+
+    /// Sends SABM for `dlci` until it is answered.
+    async fn establish(&self, dlci: u8) -> io::Result<()> {
+        let config = &self.shared.config;
+        for _ in 0..=config.retries {
+            {
+                let mut state = self.shared.lock();
+                if state.closed {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "multiplexer is closed",
+                    ));
+                }
+                match state.link_mut(dlci) {
+                    Some(link) => *link = Link::Opening,
+                    None => return Err(io::ErrorKind::NotConnected.into()),
+                }
+                state.send(Frame::new(dlci, SABM, BytesMut::new()));
+            }
+            self.shared.notify.notify_one();
+
+            let link = time::timeout(
+                config.timeout,
+                self.shared.wait(|state| match state.link_mut(dlci) {
+                    Some(Link::Opening) => None,
+                    Some(link) => Some(*link),
+                    None => Some(Link::Closed),
+                }),
+            )
+            .await;
+            match link {
+                Ok(Link::Open) => return Ok(()),
+                Ok(Link::Refused) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "channel refused by the other side",
+                    ))
+                }
+                Ok(_) => return Err(io::ErrorKind::NotConnected.into()),
+                Err(_) => log::debug!("no answer to SABM for DLCI {}, retrying", dlci),
+            }
+        }
+
+        if let Some(link) = self.shared.lock().link_mut(dlci) {
+            *link = Link::Closed;
+        }
+        Err(io::ErrorKind::TimedOut.into())
+    }
+}
+
+impl Drop for Multiplexer {
+    fn drop(&mut self) {
+        // The driver stops once it is the last one holding on.
+        self.shared.notify.notify_one();
+    }
+}
+
+/// The state of a data link connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Link {
+    #[default]
+    Closed,
+    /// SABM has been sent.
+    Opening,
+    Open,
+    /// DM was received in answer to SABM.
+    Refused,
+    /// DISC or CLD has been sent.
+    Closing,
+}
+
+#[derive(Debug, Default)]
+struct ChannelState {
+    link: Link,
+    /// Data received and not read yet.
+    rx: BytesMut,
+    /// Data written and not sent yet.
+    tx: BytesMut,
+    rx_waker: Option<Waker>,
+    tx_waker: Option<Waker>,
+    /// The other side asked us to stop sending.
+    remote_stopped: bool,
+    /// We asked the other side to stop sending.
+    local_stopped: bool,
+}
+
+impl ChannelState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.rx_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.tx_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    control: Link,
+    channels: BTreeMap<u8, ChannelState>,
+    /// Frames to send before any data.
+    outgoing: VecDeque<Frame>,
+    /// Handles waiting for a link to change.
+    waiters: Vec<Waker>,
+    /// The other side asked us to stop sending on every channel.
+    stopped: bool,
+    closed: bool,
+}
+
+impl State {
+    fn link_mut(&mut self, dlci: u8) -> Option<&mut Link> {
+        match dlci {
+            0 => Some(&mut self.control),
+            _ => self
+                .channels
+                .get_mut(&dlci)
+                .map(|channel| &mut channel.link),
+        }
+    }
+
+    fn send(&mut self, frame: Frame) {
+        self.outgoing.push_back(frame);
+    }
+
+    /// Sends a message on the control channel.
+    fn send_message(&mut self, kind: u8, command: bool, values: &[u8]) {
+        let mut info = BytesMut::with_capacity(2 + values.len());
+        frame::put_message(&mut info, kind, command, values);
+        self.send(Frame::new(0, UIH, info));
+    }
+
+    /// Sends DISC for a channel that is going away.
+    fn disconnect(&mut self, dlci: u8) {
+        self.send(Frame::new(dlci, DISC, BytesMut::new()));
+    }
+
+    fn wake_all(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+        for channel in self.channels.values_mut() {
+            channel.wake();
+        }
+    }
+
+    /// Closes the multiplexer and every channel.
+    fn shut_down(&mut self) {
+        self.closed = true;
+        self.control = Link::Closed;
+        for channel in self.channels.values_mut() {
+            channel.link = Link::Closed;
+        }
+        self.wake_all();
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    /// Wakes the driver when there is something to send.
+    notify: Notify,
+    config: Cmux,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        crate::lock(&self.state)
+    }
+
+    /// Waits until `ready` returns a value.  It is checked again whenever a link
+    /// changes.
+    async fn wait<T>(&self, mut ready: impl FnMut(&mut State) -> Option<T>) -> T {
+        poll_fn(|cx| {
+            let mut state = self.lock();
+            match ready(&mut state) {
+                Some(value) => Poll::Ready(value),
+                None => {
+                    state.waiters.push(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,182 @@
+//! A data link connection as a byte stream.
+use super::{Link, Shared, BUFFER_SIZE, MSC};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{self, Sleep};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// One channel of a [`Multiplexer`](super::Multiplexer), opened with
+/// [`Multiplexer::open`](super::Multiplexer::open).
+///
+/// Writes are buffered and sent by the driver in frames of at most the maximum frame
+/// size, while the other side allows it.  Reads return end of file once the channel
+/// has been closed by either side, or the multiplexer has gone away.
+///
+/// Shutting the channel down sends what was written, then closes the channel with DISC
+/// and waits for the other side to acknowledge it.  Dropping the channel closes it
+/// without waiting.
+#[derive(Debug)]
+pub struct Channel {
+    shared: Arc<Shared>,
+    dlci: u8,
+    // When to send DISC again, and how many times it was sent.
+    shutdown: Option<(Pin<Box<Sleep>>, usize)>,
+}
+
+impl Channel {
+    pub(super) fn new(shared: Arc<Shared>, dlci: u8) -> Channel {
+        Self {
+            shared,
+            dlci,
+            shutdown: None,
+        }
+    }
+
+    /// Returns the data link connection identifier of the channel.
+    pub fn dlci(&self) -> u8 {
+        self.dlci
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "channel is closed")
+}
+
+impl AsyncRead for Channel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut state = self.shared.lock();
+        let channel = match state.channels.get_mut(&self.dlci) {
+            Some(channel) => channel,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        if channel.rx.is_empty() {
+            if channel.link == Link::Open || channel.link == Link::Closing {
+                channel.rx_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        let n = channel.rx.len().min(buf.remaining());
+        buf.put_slice(&channel.rx.split_to(n));
+        if channel.local_stopped && channel.rx.len() < BUFFER_SIZE / 2 {
+            channel.local_stopped = false;
+            state.send_message(MSC, true, &super::frame::msc_values(self.dlci, false));
+            self.shared.notify.notify_one();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Channel {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.shared.lock();
+        let channel = match state.channels.get_mut(&self.dlci) {
+            Some(channel) if channel.link == Link::Open => channel,
+            _ => return Poll::Ready(Err(closed())),
+        };
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let room = BUFFER_SIZE.saturating_sub(channel.tx.len());
+        if room == 0 {
+            channel.tx_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = room.min(buf.len());
+        channel.tx.extend_from_slice(&buf[..n]);
+        self.shared.notify.notify_one();
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut state = self.shared.lock();
+        let channel = match state.channels.get_mut(&self.dlci) {
+            Some(channel) => channel,
+            None => return Poll::Ready(Ok(())),
+        };
+        if channel.tx.is_empty() {
+            Poll::Ready(Ok(()))
+        } else if channel.link != Link::Open {
+            Poll::Ready(Err(closed()))
+        } else {
+            channel.tx_waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let config = &this.shared.config;
+        let mut state = this.shared.lock();
+        let channel = match state.channels.get_mut(&this.dlci) {
+            Some(channel) => channel,
+            None => return Poll::Ready(Ok(())),
+        };
+
+        match channel.link {
+            Link::Open if !channel.tx.is_empty() => {
+                channel.tx_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            Link::Open => {
+                channel.link = Link::Closing;
+                state.disconnect(this.dlci);
+                this.shared.notify.notify_one();
+                this.shutdown = Some((Box::pin(time::sleep(config.timeout)), 0));
+            }
+            Link::Closing => {}
+            _ => return Poll::Ready(Ok(())),
+        }
+
+        let (timer, sent) = match &mut this.shutdown {
+            Some(shutdown) => shutdown,
+            // The channel is being closed by the multiplexer.
+            None => return Poll::Ready(Ok(())),
+        };
+        while timer.as_mut().poll(cx).is_ready() {
+            if *sent == config.retries {
+                if let Some(channel) = state.channels.get_mut(&this.dlci) {
+                    channel.link = Link::Closed;
+                }
+                return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+            }
+            *sent += 1;
+            timer.as_mut().reset(time::Instant::now() + config.timeout);
+            state.disconnect(this.dlci);
+            this.shared.notify.notify_one();
+        }
+        if let Some(channel) = state.channels.get_mut(&this.dlci) {
+            channel.tx_waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        if let Some(channel) = state.channels.remove(&self.dlci) {
+            if channel.link == Link::Open || channel.link == Link::Closing {
+                state.disconnect(self.dlci);
+            }
+        }
+        drop(state);
+        self.shared.notify.notify_one();
+    }
+}
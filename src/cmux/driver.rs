@@ -0,0 +1,213 @@
+//! The task that moves frames between the port and the channels.
+use super::frame::{
+    self, Frame, CLD, DISC, DM, FCOFF, FCON, MSC, NSC, SABM, SIGNAL_FC, TEST, UA, UI, UIH,
+};
+use super::{Link, Shared, State, BUFFER_SIZE};
+use crate::SerialStream;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncRead, AsyncWriteExt, ReadBuf};
+
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+
+/// Does the I/O of a [`Multiplexer`](super::Multiplexer), created by
+/// [`Cmux::start`](super::Cmux::start).
+///
+/// Nothing is sent or received until [`run`](Driver::run) is polled.
+#[must_use = "the multiplexer does nothing unless the driver is run"]
+#[derive(Debug)]
+pub struct Driver {
+    port: SerialStream,
+    shared: Arc<Shared>,
+    buf: BytesMut,
+}
+
+impl Driver {
+    pub(super) fn new(port: SerialStream, shared: Arc<Shared>) -> Driver {
+        Self {
+            port,
+            shared,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// Runs the multiplexer until it is closed, or until every handle and channel has
+    /// been dropped, and gives the port back.
+    ///
+    /// ## Errors
+    ///
+    /// * `UnexpectedEof` if the port hung up.
+    /// * `Io` if reading from or writing to the port failed.
+    ///
+    /// The channels are closed when the driver stops, however it stops.
+    pub async fn run(mut self) -> io::Result<SerialStream> {
+        let result = self.serve().await;
+        self.shared.lock().shut_down();
+        result.map(|()| self.port)
+    }
+
+    async fn serve(&mut self) -> io::Result<()> {
+        let max_frame_size = self.shared.config.max_frame_size;
+        let mut chunk = [0u8; 1024];
+        loop {
+            let mut frames = BytesMut::new();
+            let done = {
+                let mut state = self.shared.lock();
+                state.take_outgoing(max_frame_size, &mut frames);
+                state.closed || Arc::strong_count(&self.shared) == 1
+            };
+            if !frames.is_empty() {
+                self.port.write_all(&frames).await?;
+            }
+            if done {
+                return Ok(());
+            }
+
+            let port = &mut self.port;
+            let notified = self.shared.notify.notified();
+            tokio::pin!(notified);
+            let read = poll_fn(|cx| {
+                if notified.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(None);
+                }
+                let mut buf = ReadBuf::new(&mut chunk);
+                match Pin::new(&mut *port).poll_read(cx, &mut buf) {
+                    Poll::Ready(result) => Poll::Ready(Some(result.map(|()| buf.filled().len()))),
+                    Poll::Pending => Poll::Pending,
+                }
+            })
+            .await;
+
+            match read {
+                None => {}
+                Some(Ok(0)) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Some(Ok(n)) => {
+                    self.buf.extend_from_slice(&chunk[..n]);
+                    let mut state = self.shared.lock();
+                    while let Some(frame) = Frame::decode(&mut self.buf, max_frame_size) {
+                        state.receive(frame);
+                    }
+                }
+                Some(Err(err)) => return Err(err),
+            }
+        }
+    }
+}
+
+impl State {
+    /// Encodes the frames waiting to be sent, and then the data of every channel that
+    /// may send, into `dst`.
+    fn take_outgoing(&mut self, max_frame_size: usize, dst: &mut BytesMut) {
+        for frame in self.outgoing.drain(..) {
+            frame.encode(dst);
+        }
+        if self.control != Link::Open || self.stopped {
+            return;
+        }
+        for (&dlci, channel) in self.channels.iter_mut() {
+            if channel.link != Link::Open || channel.remote_stopped || channel.tx.is_empty() {
+                continue;
+            }
+            while !channel.tx.is_empty() {
+                let n = channel.tx.len().min(max_frame_size);
+                Frame::new(dlci, UIH, channel.tx.split_to(n)).encode(dst);
+            }
+            if let Some(waker) = channel.tx_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Handles a frame from the other side.
+    fn receive(&mut self, frame: Frame) {
+        match frame.kind {
+            UA => {
+                if let Some(link) = self.link_mut(frame.dlci) {
+                    match *link {
+                        Link::Opening => *link = Link::Open,
+                        Link::Closing => *link = Link::Closed,
+                        _ => {}
+                    }
+                }
+                self.wake_all();
+            }
+            DM => {
+                if let Some(link) = self.link_mut(frame.dlci) {
+                    match *link {
+                        Link::Opening => *link = Link::Refused,
+                        Link::Open | Link::Closing => *link = Link::Closed,
+                        _ => {}
+                    }
+                }
+                self.wake_all();
+            }
+            // We open the channels, not the other side.
+            SABM => self.send(Frame::new(frame.dlci, DM, BytesMut::new())),
+            DISC if frame.dlci == 0 => {
+                self.send(Frame::new(0, UA, BytesMut::new()));
+                self.shut_down();
+            }
+            DISC => match self.channels.get_mut(&frame.dlci) {
+                Some(channel) if channel.link == Link::Open || channel.link == Link::Closing => {
+                    channel.link = Link::Closed;
+                    channel.wake();
+                    self.send(Frame::new(frame.dlci, UA, BytesMut::new()));
+                }
+                _ => self.send(Frame::new(frame.dlci, DM, BytesMut::new())),
+            },
+            UIH | UI if frame.dlci == 0 => self.receive_messages(frame.info),
+            UIH | UI => match self.channels.get_mut(&frame.dlci) {
+                Some(channel) if channel.link == Link::Open || channel.link == Link::Closing => {
+                    channel.rx.extend_from_slice(&frame.info);
+                    if let Some(waker) = channel.rx_waker.take() {
+                        waker.wake();
+                    }
+                    if channel.rx.len() >= BUFFER_SIZE && !channel.local_stopped {
+                        channel.local_stopped = true;
+                        self.send_message(MSC, true, &frame::msc_values(frame.dlci, true));
+                    }
+                }
+                _ => log::debug!("dropping data for closed DLCI {}", frame.dlci),
+            },
+            kind => log::debug!("ignoring frame of type {:02X}", kind),
+        }
+    }
+
+    /// Handles the messages on the control channel.
+    fn receive_messages(&mut self, mut info: BytesMut) {
+        while let Some((kind, command, values)) = frame::take_message(&mut info) {
+            if !command {
+                if kind == CLD && self.control == Link::Closing {
+                    self.control = Link::Closed;
+                    self.wake_all();
+                }
+                continue;
+            }
+
+            match kind {
+                MSC => {
+                    if let [address, signals, ..] = values[..] {
+                        if let Some(channel) = self.channels.get_mut(&(address >> 2)) {
+                            channel.remote_stopped = signals & SIGNAL_FC != 0;
+                        }
+                    }
+                    self.send_message(MSC, false, &values);
+                }
+                FCON | FCOFF => {
+                    self.stopped = kind == FCOFF;
+                    self.send_message(kind, false, &[]);
+                }
+                TEST => self.send_message(TEST, false, &values),
+                CLD => {
+                    self.send_message(CLD, false, &[]);
+                    self.shut_down();
+                }
+                _ => self.send_message(NSC, false, &[kind << 2 | 0x03]),
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Basic option frames and control channel messages of 27.010.
+use crate::crc::Crc;
+
+use bytes::{Buf, BufMut, BytesMut};
+
+/// Opens and closes every frame.
+const FLAG: u8 = 0xF9;
+
+/// The frame check sequence: the ones' complement of a reflected CRC-8 with polynomial
+/// `0x07`.
+const FCS: Crc = Crc {
+    width: 8,
+    poly: 0x07,
+    init: 0xFF,
+    reflect: true,
+    xorout: 0xFF,
+};
+
+/// The poll/final bit of the control field.
+const PF: u8 = 0x10;
+
+/// Frame types, given by the control field without the poll/final bit.
+pub(super) const SABM: u8 = 0x2F;
+pub(super) const UA: u8 = 0x63;
+pub(super) const DM: u8 = 0x0F;
+pub(super) const DISC: u8 = 0x43;
+pub(super) const UIH: u8 = 0xEF;
+pub(super) const UI: u8 = 0x03;
+
+/// Control channel message types, without the C/R and EA bits.
+pub(super) const MSC: u8 = 0x38;
+pub(super) const CLD: u8 = 0x30;
+pub(super) const TEST: u8 = 0x08;
+pub(super) const NSC: u8 = 0x04;
+pub(super) const FCON: u8 = 0x28;
+pub(super) const FCOFF: u8 = 0x18;
+
+/// Bits of the V.24 signals octet of an MSC message.
+pub(super) const SIGNAL_FC: u8 = 0x02;
+const SIGNAL_RTC: u8 = 0x04;
+const SIGNAL_RTR: u8 = 0x08;
+const SIGNAL_DV: u8 = 0x80;
+
+/// A frame, with the length and FCS stripped.
+#[derive(Debug)]
+pub(super) struct Frame {
+    pub(super) dlci: u8,
+    /// The C/R bit of the address field.
+    pub(super) command: bool,
+    /// The frame type.
+    pub(super) kind: u8,
+    pub(super) poll: bool,
+    pub(super) info: BytesMut,
+}
+
+impl Frame {
+    /// A frame sent by us, the initiator, which sets the C/R bit on commands.
+    pub(super) fn new(dlci: u8, kind: u8, info: BytesMut) -> Frame {
+        let command = kind != UA && kind != DM;
+        Frame {
+            dlci,
+            command,
+            kind,
+            poll: kind != UIH && kind != UI,
+            info,
+        }
+    }
+
+    /// Appends the frame to `dst`.
+    pub(super) fn encode(&self, dst: &mut BytesMut) {
+        let len = self.info.len();
+        dst.reserve(len + 7);
+        dst.put_u8(FLAG);
+        let start = dst.len();
+        dst.put_u8(self.dlci << 2 | u8::from(self.command) << 1 | 1);
+        dst.put_u8(if self.poll { self.kind | PF } else { self.kind });
+        if len <= 0x7F {
+            dst.put_u8((len as u8) << 1 | 1);
+        } else {
+            dst.put_u8((len as u8) << 1);
+            dst.put_u8((len >> 7) as u8);
+        }
+        // UI and UIH frames leave the information field out of the check.
+        let header_end = dst.len();
+        dst.put_slice(&self.info);
+        let checked = match self.kind {
+            UIH | UI => &dst[start..header_end],
+            _ => &dst[start..],
+        };
+        let fcs = FCS.checksum(checked) as u8;
+        dst.put_u8(fcs);
+        dst.put_u8(FLAG);
+    }
+
+    /// Takes the next frame out of `src`, skipping anything that isn't a valid frame.
+    /// Returns `None` if there isn't a whole frame yet.
+    ///
+    /// The closing flag is left in `src`, since it may be the opening flag of the next
+    /// frame as well.
+    pub(super) fn decode(src: &mut BytesMut, max_frame_size: usize) -> Option<Frame> {
+        loop {
+            // Hunt for the last of a run of flags.
+            match src.iter().position(|b| *b != FLAG) {
+                Some(0) => match src.iter().position(|b| *b == FLAG) {
+                    Some(at) => {
+                        src.advance(at);
+                        continue;
+                    }
+                    None => {
+                        src.clear();
+                        return None;
+                    }
+                },
+                Some(at) => src.advance(at - 1),
+                None => {
+                    let flags = src.len();
+                    src.advance(flags.saturating_sub(1));
+                    return None;
+                }
+            }
+            if src.len() < 5 {
+                return None;
+            }
+            if src[1] & 1 == 0 {
+                // Addresses are always a single octet in basic option.
+                src.advance(1);
+                continue;
+            }
+
+            let (len, header_len) = if src[3] & 1 == 1 {
+                (usize::from(src[3] >> 1), 4)
+            } else if src.len() < 6 {
+                return None;
+            } else {
+                (usize::from(src[3] >> 1) | usize::from(src[4]) << 7, 5)
+            };
+            if len > max_frame_size {
+                log::debug!("dropping overlong frame of {} bytes", len);
+                src.advance(1);
+                continue;
+            }
+            let frame_len = header_len + len + 2;
+            if src.len() < frame_len {
+                src.reserve(frame_len - src.len());
+                return None;
+            }
+            if src[frame_len - 1] != FLAG {
+                src.advance(1);
+                continue;
+            }
+
+            let kind = src[2] & !PF;
+            let checked = match kind {
+                UIH | UI => &src[1..header_len],
+                _ => &src[1..header_len + len],
+            };
+            if FCS.checksum(checked) as u8 != src[header_len + len] {
+                log::debug!("dropping frame with bad FCS");
+                src.advance(1);
+                continue;
+            }
+
+            let mut frame = src.split_to(frame_len - 1);
+            let (address, control) = (frame[1], frame[2]);
+            frame.advance(header_len);
+            frame.truncate(len);
+            return Some(Frame {
+                dlci: address >> 2,
+                command: address & 0x02 != 0,
+                kind,
+                poll: control & PF != 0,
+                info: frame,
+            });
+        }
+    }
+}
+
+/// Appends a control channel message to `dst`.
+pub(super) fn put_message(dst: &mut BytesMut, kind: u8, command: bool, values: &[u8]) {
+    dst.put_u8(kind << 2 | u8::from(command) << 1 | 1);
+    // Control messages are never long enough to need a second length octet.
+    dst.put_u8((values.len() as u8) << 1 | 1);
+    dst.put_slice(values);
+}
+
+/// Takes the next control channel message out of `src`, returning its type, whether it
+/// is a command, and its values.
+pub(super) fn take_message(src: &mut BytesMut) -> Option<(u8, bool, BytesMut)> {
+    if src.len() < 2 {
+        return None;
+    }
+    let (kind, command) = (src[0] >> 2, src[0] & 0x02 != 0);
+    let mut len = 0;
+    let mut at = 1;
+    loop {
+        let octet = *src.get(at)?;
+        len |= usize::from(octet >> 1) << (7 * (at - 1));
+        at += 1;
+        if octet & 1 == 1 || at > 3 {
+            break;
+        }
+    }
+    if src.len() < at + len {
+        src.clear();
+        return None;
+    }
+    src.advance(at);
+    Some((kind, command, src.split_to(len)))
+}
+
+/// The values of an MSC command for `dlci`, with the signals of a ready DTE and the
+/// flow control bit set if `stop`.
+pub(super) fn msc_values(dlci: u8, stop: bool) -> [u8; 2] {
+    let mut signals = SIGNAL_RTC | SIGNAL_RTR | SIGNAL_DV | 1;
+    if stop {
+        signals |= SIGNAL_FC;
+    }
+    [dlci << 2 | 0x03, signals]
+}
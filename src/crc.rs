@@ -0,0 +1,114 @@
+//! Cyclic redundancy checks used by the framing and protocol modules.
+//!
+//! The checks are computed bit by bit, which is plenty fast for the data rates of a
+//! serial line and saves carrying a lookup table for every algorithm.
+
+/// A CRC algorithm of up to 32 bits, described by its parameters in the usual
+/// ("Rocksoft") model.
+///
+/// Only algorithms that reflect both their input and their output, or neither, can be
+/// described, which covers all the common ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc {
+    /// The width of the check in bits, from 8 to 32.
+    pub width: u8,
+    /// The generator polynomial, without its top bit and not reflected.
+    pub poly: u32,
+    /// The initial value of the register, not reflected.
+    pub init: u32,
+    /// Whether bytes are processed least significant bit first.
+    pub reflect: bool,
+    /// The value XORed into the register to give the check.
+    pub xorout: u32,
+}
+
+impl Crc {
+    /// CRC-8/SMBUS, the plain CRC-8 with polynomial `0x07`.
+    pub const CRC_8_SMBUS: Crc = Crc {
+        width: 8,
+        poly: 0x07,
+        init: 0x00,
+        reflect: false,
+        xorout: 0x00,
+    };
+
+    /// CRC-16/MODBUS, as used by Modbus RTU.
+    pub const CRC_16_MODBUS: Crc = Crc {
+        width: 16,
+        poly: 0x8005,
+        init: 0xFFFF,
+        reflect: true,
+        xorout: 0x0000,
+    };
+
+    /// CRC-16/XMODEM, as used by XMODEM, YMODEM and ZMODEM.
+    pub const CRC_16_XMODEM: Crc = Crc {
+        width: 16,
+        poly: 0x1021,
+        init: 0x0000,
+        reflect: false,
+        xorout: 0x0000,
+    };
+
+    /// CRC-16/IBM-SDLC, the FCS-16 of HDLC and PPP, also known as CRC-16/X-25.
+    pub const CRC_16_IBM_SDLC: Crc = Crc {
+        width: 16,
+        poly: 0x1021,
+        init: 0xFFFF,
+        reflect: true,
+        xorout: 0xFFFF,
+    };
+
+    /// CRC-32/ISO-HDLC, the FCS-32 of HDLC and PPP, and the CRC-32 of Ethernet and zlib.
+    pub const CRC_32_ISO_HDLC: Crc = Crc {
+        width: 32,
+        poly: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect: true,
+        xorout: 0xFFFF_FFFF,
+    };
+
+    /// The width of the check in bytes.
+    pub fn byte_width(&self) -> usize {
+        usize::from(self.width + 7) / 8
+    }
+
+    /// Computes the check over `data`.
+    pub fn checksum(&self, data: &[u8]) -> u32 {
+        let mask = u32::MAX >> (32 - u32::from(self.width));
+        if self.reflect {
+            let poly = reflect(self.poly, self.width);
+            let mut crc = reflect(self.init, self.width);
+            for &byte in data {
+                crc ^= u32::from(byte);
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    };
+                }
+            }
+            (crc ^ self.xorout) & mask
+        } else {
+            let top = 1 << (self.width - 1);
+            let mut crc = self.init & mask;
+            for &byte in data {
+                crc ^= u32::from(byte) << (self.width - 8);
+                for _ in 0..8 {
+                    crc = if crc & top != 0 {
+                        (crc << 1) ^ self.poly
+                    } else {
+                        crc << 1
+                    } & mask;
+                }
+            }
+            (crc ^ self.xorout) & mask
+        }
+    }
+}
+
+/// Reverses the order of the low `width` bits of `value`.
+fn reflect(value: u32, width: u8) -> u32 {
+    value.reverse_bits() >> (32 - u32::from(width))
+}
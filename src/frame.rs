@@ -1,6 +1,22 @@
 //! A unified [`Stream`] and [`Sink`] interface to an underlying `SerialStream`, using
 //! the `Encoder` and `Decoder` traits to encode and decode frames.
-use super::SerialStream;
+//!
+//! [`SerialFramedRead`] and [`SerialFramedWrite`] do the same for the owned halves
+//! returned by [`SerialStream::into_split`].  For protocols that separate frames by
+//! silence on the line rather than by their content, see [`GapFramed`].
+//!
+//! Codecs for framing schemes commonly found on serial lines are provided as well:
+//!
+//! - [`LineCodec`] for lines of text
+//! - [`SlipCodec`] for SLIP ([RFC 1055]) framed packets
+//! - [`CobsCodec`] for COBS and COBS/R encoded, zero delimited packets
+//! - [`HdlcCodec`] for HDLC-like framing as used by PPP ([RFC 1662])
+//! - [`ModbusAsciiCodec`] for Modbus ASCII frames
+//! - [`PacketCodec`] for length prefixed packets with a sync pattern and a [`Crc`]
+//!
+//! [RFC 1055]: https://www.rfc-editor.org/rfc/rfc1055
+//! [RFC 1662]: https://www.rfc-editor.org/rfc/rfc1662
+use super::{OwnedReadHalf, OwnedWriteHalf, SerialStream};
 
 use tokio_util::codec::{Decoder, Encoder};
 
@@ -8,10 +24,33 @@ use futures_core::Stream;
 use futures_sink::Sink;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use bytes::{BufMut, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
+use std::time::Duration;
 use std::{io, mem::MaybeUninit};
+use tokio::time::{Instant, Sleep};
+
+mod cobs;
+pub use self::cobs::{CobsCodec, CobsCodecError, CobsVariant};
+
+mod hdlc;
+pub use self::hdlc::{Fcs, HdlcCodec, HdlcCodecError};
+
+mod line;
+pub use self::line::{LineCodec, LineCodecError, LineTerminator, Utf8Policy};
+
+mod modbus_ascii;
+pub use self::modbus_ascii::{ModbusAsciiCodec, ModbusAsciiCodecError};
+
+mod packet;
+pub use self::packet::{Endianness, PacketCodec, PacketCodecError};
+
+pub use crate::crc::Crc;
+
+mod slip;
+pub use self::slip::{SlipCodec, SlipCodecError};
 
 /// A unified [`Stream`] and [`Sink`] interface to an underlying `SerialStream`, using
 /// the `Encoder` and `Decoder` traits to encode and decode frames.
@@ -30,6 +69,12 @@ use std::{io, mem::MaybeUninit};
 /// calling [`split`] on the `SerialFramed` returned by this method, which will break
 /// them into separate objects, allowing them to interact more easily.
 ///
+/// Bytes that don't make up a whole frame yet are kept until more data arrives.  The
+/// codec's `decode_eof` is only called once the port reports end of file, which a
+/// serial port does when it hangs up.  Errors returned by the codec are passed on
+/// without ending the stream, so a codec can report a bad frame and carry on with
+/// the next one.
+///
 /// [`Stream`]: futures_core::Stream
 /// [`Sink`]: futures_sink::Sink
 /// [`split`]: https://docs.rs/futures/0.3/futures/stream/trait.StreamExt.html#method.split
@@ -38,110 +83,225 @@ use std::{io, mem::MaybeUninit};
 pub struct SerialFramed<C> {
     port: SerialStream,
     codec: C,
-    rd: BytesMut,
-    wr: BytesMut,
-    flushed: bool,
-    is_readable: bool,
+    read: ReadFrame,
+    write: WriteFrame,
 }
 
 const INITIAL_RD_CAPACITY: usize = 64 * 1024;
 const INITIAL_WR_CAPACITY: usize = 8 * 1024;
 
-impl<C: Decoder + Unpin> Stream for SerialFramed<C> {
-    type Item = Result<C::Item, C::Error>;
+/// The state of the read side of a framed port.
+#[derive(Debug)]
+struct ReadFrame {
+    buffer: BytesMut,
+    max_buffer_size: Option<usize>,
+    is_readable: bool,
+    eof: bool,
+    has_overflowed: bool,
+}
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let pin = self.get_mut();
+impl ReadFrame {
+    fn new(buffer: BytesMut) -> Self {
+        Self {
+            // Bytes handed over from elsewhere may already hold whole frames.
+            is_readable: !buffer.is_empty(),
+            buffer,
+            max_buffer_size: None,
+            eof: false,
+            has_overflowed: false,
+        }
+    }
 
-        pin.rd.reserve(INITIAL_RD_CAPACITY);
+    fn poll_next<R, C>(
+        &mut self,
+        mut port: Pin<&mut R>,
+        codec: &mut C,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<C::Item, C::Error>>>
+    where
+        R: AsyncRead,
+        C: Decoder,
+    {
+        if self.has_overflowed {
+            return Poll::Ready(None);
+        }
 
         loop {
             // Are there still bytes left in the read buffer to decode?
-            if pin.is_readable {
-                if let Some(frame) = pin.codec.decode_eof(&mut pin.rd)? {
+            if self.is_readable {
+                if self.eof {
+                    // The port hung up: hand the codec whatever is left, once.
+                    let frame = codec.decode_eof(&mut self.buffer)?;
+                    if frame.is_none() {
+                        self.is_readable = false;
+                    }
+                    return Poll::Ready(frame.map(Ok));
+                }
+
+                if let Some(frame) = codec.decode(&mut self.buffer)? {
                     return Poll::Ready(Some(Ok(frame)));
                 }
 
-                // if this line has been reached then decode has returned `None`.
-                pin.is_readable = false;
-                pin.rd.clear();
+                // if this line has been reached then decode has returned `None` and
+                // whatever is left in the buffer is the start of a frame still to come.
+                self.is_readable = false;
+            }
+
+            let space = match self.max_buffer_size {
+                Some(max) if self.buffer.len() >= max => {
+                    // The codec may keep state about what it has already seen of the
+                    // buffer, so the buffer is left alone and the stream ends instead.
+                    self.has_overflowed = true;
+                    return Poll::Ready(Some(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "read buffer exceeded its maximum size without a complete frame",
+                    )
+                    .into())));
+                }
+                Some(max) => max - self.buffer.len(),
+                None => usize::MAX,
+            };
+            if self.buffer.capacity() == self.buffer.len() {
+                self.buffer.reserve(INITIAL_RD_CAPACITY.min(space));
             }
 
             // We're out of data. Try and fetch more data to decode
-            unsafe {
+            let bytes_read = unsafe {
                 // Convert `&mut [MaybeUnit<u8>]` to `&mut [u8]` because we will be
                 // writing to it via `poll_recv_from` and therefore initializing the memory.
-                let buf = &mut *(pin.rd.chunk_mut() as *mut _ as *mut [MaybeUninit<u8>]);
-                let mut read = ReadBuf::uninit(buf);
+                let buf = &mut *(self.buffer.chunk_mut() as *mut _ as *mut [MaybeUninit<u8>]);
+                let len = buf.len().min(space);
+                let mut read = ReadBuf::uninit(&mut buf[..len]);
                 let ptr = read.filled().as_ptr();
-                ready!(Pin::new(&mut pin.port).poll_read(cx, &mut read))?;
+                ready!(port.as_mut().poll_read(cx, &mut read))?;
 
                 assert_eq!(ptr, read.filled().as_ptr());
-                pin.rd.advance_mut(read.filled().len());
+                let bytes_read = read.filled().len();
+                self.buffer.advance_mut(bytes_read);
+                bytes_read
             };
 
-            pin.is_readable = true;
+            if bytes_read == 0 {
+                if self.eof {
+                    // Already reported the end of the stream; the port is still closed.
+                    return Poll::Ready(None);
+                }
+                self.eof = true;
+            } else {
+                self.eof = false;
+            }
+
+            self.is_readable = true;
         }
     }
 }
 
-impl<I, C: Encoder<I> + Unpin> Sink<I> for SerialFramed<C> {
-    type Error = C::Error;
+/// The state of the write side of a framed port.
+#[derive(Debug)]
+struct WriteFrame {
+    buffer: BytesMut,
+    backpressure_boundary: usize,
+}
 
-    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if !self.flushed {
-            match self.poll_flush(cx)? {
-                Poll::Ready(()) => {}
-                Poll::Pending => return Poll::Pending,
+impl WriteFrame {
+    fn new(buffer: BytesMut) -> Self {
+        Self {
+            buffer,
+            backpressure_boundary: INITIAL_WR_CAPACITY,
+        }
+    }
+
+    fn poll_ready<W, E>(&mut self, port: Pin<&mut W>, cx: &mut Context<'_>) -> Poll<Result<(), E>>
+    where
+        W: AsyncWrite,
+        E: From<io::Error>,
+    {
+        if self.buffer.len() >= self.backpressure_boundary {
+            return self.poll_flush(port, cx);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush<W, E>(
+        &mut self,
+        mut port: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), E>>
+    where
+        W: AsyncWrite,
+        E: From<io::Error>,
+    {
+        // A short write is routine on a slow line, so keep going until the whole
+        // buffer is out.  Whatever has not been written yet stays in the buffer
+        // while the port is not ready for more.
+        while !self.buffer.is_empty() {
+            let n = ready!(port.as_mut().poll_write(cx, &self.buffer))?;
+
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write frame to serial port",
+                )
+                .into()));
             }
+
+            self.buffer.advance(n);
         }
 
+        ready!(port.poll_flush(cx))?;
         Poll::Ready(Ok(()))
     }
 
-    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
-        let pin = self.get_mut();
+    fn poll_close<W, E>(
+        &mut self,
+        mut port: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), E>>
+    where
+        W: AsyncWrite,
+        E: From<io::Error>,
+    {
+        ready!(self.poll_flush::<W, E>(port.as_mut(), cx))?;
+        ready!(port.poll_shutdown(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
 
-        pin.codec.encode(item, &mut pin.wr)?;
-        pin.flushed = false;
+impl<C: Decoder + Unpin> Stream for SerialFramed<C> {
+    type Item = Result<C::Item, C::Error>;
 
-        Ok(())
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pin = self.get_mut();
+        pin.read
+            .poll_next(Pin::new(&mut pin.port), &mut pin.codec, cx)
     }
+}
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if self.flushed {
-            return Poll::Ready(Ok(()));
-        }
+impl<I, C: Encoder<I> + Unpin> Sink<I> for SerialFramed<C> {
+    type Error = C::Error;
 
-        let Self {
-            ref mut port,
-            ref mut wr,
-            ..
-        } = *self;
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let pin = self.get_mut();
+        pin.write.poll_ready(Pin::new(&mut pin.port), cx)
+    }
 
-        let pinned = Pin::new(port);
-        let n = ready!(pinned.poll_write(cx, &wr))?;
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let pin = self.get_mut();
 
-        let wrote_all = n == self.wr.len();
-        self.wr.clear();
-        self.flushed = true;
+        pin.codec.encode(item, &mut pin.write.buffer)?;
 
-        let res = if wrote_all {
-            Ok(())
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to write entire datagram to socket",
-            )
-            .into())
-        };
+        Ok(())
+    }
 
-        Poll::Ready(res)
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let pin = self.get_mut();
+        pin.write.poll_flush(Pin::new(&mut pin.port), cx)
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        ready!(self.poll_flush(cx))?;
-        Poll::Ready(Ok(()))
+        let pin = self.get_mut();
+        pin.write.poll_close(Pin::new(&mut pin.port), cx)
     }
 }
 
@@ -154,13 +314,85 @@ impl<C> SerialFramed<C> {
         Self {
             port,
             codec,
-            rd: BytesMut::with_capacity(INITIAL_RD_CAPACITY),
-            wr: BytesMut::with_capacity(INITIAL_WR_CAPACITY),
-            flushed: true,
-            is_readable: false,
+            read: ReadFrame::new(BytesMut::with_capacity(INITIAL_RD_CAPACITY)),
+            write: WriteFrame::new(BytesMut::with_capacity(INITIAL_WR_CAPACITY)),
         }
     }
 
+    /// Create a new `SerialFramed` from the parts of another one, carrying over the
+    /// bytes that were buffered but not yet decoded or written.
+    ///
+    /// Frames already complete in `parts.read_buf` are decoded before the port is read
+    /// again.
+    pub fn from_parts(parts: SerialFramedParts<C>) -> SerialFramed<C> {
+        let mut read = ReadFrame::new(parts.read_buf);
+        read.max_buffer_size = parts.max_buffer_size;
+        let mut write = WriteFrame::new(parts.write_buf);
+        write.backpressure_boundary = parts.backpressure_boundary;
+        Self {
+            port: parts.port,
+            codec: parts.codec,
+            read,
+            write,
+        }
+    }
+
+    /// Consumes the `SerialFramed`, returning its port, codec, buffers and buffer limits.
+    ///
+    /// Together with [`from_parts`](SerialFramed::from_parts) this allows switching to a
+    /// different codec without losing bytes that have already been read from the port.
+    pub fn into_parts(self) -> SerialFramedParts<C> {
+        SerialFramedParts {
+            port: self.port,
+            codec: self.codec,
+            read_buf: self.read.buffer,
+            write_buf: self.write.buffer,
+            max_buffer_size: self.read.max_buffer_size,
+            backpressure_boundary: self.write.backpressure_boundary,
+        }
+    }
+
+    /// Maps the codec `C` to `D`, returning a new `SerialFramed`.
+    ///
+    /// The read and write buffers, as well as the buffer limits, are kept as they are.
+    pub fn map_codec<D, F>(self, map: F) -> SerialFramed<D>
+    where
+        F: FnOnce(C) -> D,
+    {
+        let mut read = self.read;
+        // The new codec may well find a frame in what the old one left behind.
+        read.is_readable = !read.buffer.is_empty();
+        SerialFramed {
+            port: self.port,
+            codec: map(self.codec),
+            read,
+            write: self.write,
+        }
+    }
+
+    /// Splits the `SerialFramed` into a [`SerialFramedRead`] and a [`SerialFramedWrite`]
+    /// on the owned halves of the port, each with a clone of the codec.
+    ///
+    /// The buffers and their limits go to the respective half.
+    pub fn into_split(self) -> (SerialFramedRead<C>, SerialFramedWrite<C>)
+    where
+        C: Clone,
+    {
+        let (reader, writer) = self.port.into_split();
+        (
+            SerialFramedRead {
+                port: reader,
+                codec: self.codec.clone(),
+                read: self.read,
+            },
+            SerialFramedWrite {
+                port: writer,
+                codec: self.codec,
+                write: self.write,
+            },
+        )
+    }
+
     /// Returns a reference to the underlying I/O stream wrapped by `Framed`.
     ///
     /// # Note
@@ -215,12 +447,445 @@ impl<C> SerialFramed<C> {
     /// Returns a reference to the read buffer.
     #[allow(dead_code)]
     pub fn read_buffer(&self) -> &BytesMut {
-        &self.rd
+        &self.read.buffer
     }
 
     /// Returns a mutable reference to the read buffer.
     #[allow(dead_code)]
     pub fn read_buffer_mut(&mut self) -> &mut BytesMut {
-        &mut self.rd
+        &mut self.read.buffer
+    }
+
+    /// Returns a reference to the write buffer.
+    pub fn write_buffer(&self) -> &BytesMut {
+        &self.write.buffer
+    }
+
+    /// Returns a mutable reference to the write buffer.
+    pub fn write_buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.write.buffer
+    }
+
+    /// Returns the current backpressure boundary.
+    pub fn backpressure_boundary(&self) -> usize {
+        self.write.backpressure_boundary
+    }
+
+    /// Updates the backpressure boundary.
+    ///
+    /// Once the write buffer holds at least this many bytes, the sink stops accepting
+    /// new frames until the buffer has been written out to the port.
+    pub fn set_backpressure_boundary(&mut self, boundary: usize) {
+        self.write.backpressure_boundary = boundary;
+    }
+
+    /// Returns the maximum size of the read buffer, if any.
+    pub fn max_buffer_size(&self) -> Option<usize> {
+        self.read.max_buffer_size
+    }
+
+    /// Limits how many bytes may be buffered while waiting for a complete frame.
+    ///
+    /// When the read buffer fills up to `max` bytes and the codec still can't decode a
+    /// frame from it, the stream yields an [`io::ErrorKind::InvalidData`] error and then
+    /// ends.  `None`, the default, lets the buffer grow without limit.
+    pub fn set_max_buffer_size(&mut self, max: Option<usize>) {
+        self.read.max_buffer_size = max;
+    }
+}
+
+/// The parts of a [`SerialFramed`], returned by [`SerialFramed::into_parts`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SerialFramedParts<C> {
+    /// The underlying port.
+    pub port: SerialStream,
+    /// The codec.
+    pub codec: C,
+    /// Bytes read from the port that have not been decoded yet.
+    pub read_buf: BytesMut,
+    /// Encoded frames that have not been written to the port yet.
+    pub write_buf: BytesMut,
+    /// See [`SerialFramed::set_max_buffer_size`].
+    pub max_buffer_size: Option<usize>,
+    /// See [`SerialFramed::set_backpressure_boundary`].
+    pub backpressure_boundary: usize,
+}
+
+impl<C> SerialFramedParts<C> {
+    /// Create new parts with empty buffers and the default buffer limits.
+    pub fn new(port: SerialStream, codec: C) -> SerialFramedParts<C> {
+        Self {
+            port,
+            codec,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            max_buffer_size: None,
+            backpressure_boundary: INITIAL_WR_CAPACITY,
+        }
+    }
+}
+
+/// A [`Stream`] of frames decoded from the [`OwnedReadHalf`] of a port.
+///
+/// This is the read side of [`SerialFramed`] and decodes frames in the same way.
+///
+/// [`Stream`]: futures_core::Stream
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct SerialFramedRead<C> {
+    port: OwnedReadHalf,
+    codec: C,
+    read: ReadFrame,
+}
+
+impl<C> SerialFramedRead<C> {
+    /// Create a new `SerialFramedRead` with the given codec.
+    pub fn new(port: OwnedReadHalf, codec: C) -> SerialFramedRead<C> {
+        Self {
+            port,
+            codec,
+            read: ReadFrame::new(BytesMut::with_capacity(INITIAL_RD_CAPACITY)),
+        }
+    }
+
+    /// Maps the codec `C` to `D`, returning a new `SerialFramedRead`.
+    ///
+    /// Bytes that have been read but not decoded yet are kept for the new codec.
+    pub fn map_codec<D, F>(self, map: F) -> SerialFramedRead<D>
+    where
+        F: FnOnce(C) -> D,
+    {
+        let mut read = self.read;
+        read.is_readable = !read.buffer.is_empty();
+        SerialFramedRead {
+            port: self.port,
+            codec: map(self.codec),
+            read,
+        }
+    }
+
+    /// Returns a reference to the underlying read half.
+    pub fn get_ref(&self) -> &OwnedReadHalf {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying read half.
+    ///
+    /// # Note
+    ///
+    /// Care should be taken to not tamper with the underlying stream of data
+    /// coming in as it may corrupt the stream of frames otherwise being worked
+    /// with.
+    pub fn get_mut(&mut self) -> &mut OwnedReadHalf {
+        &mut self.port
+    }
+
+    /// Consumes the `SerialFramedRead`, returning its underlying read half.
+    pub fn into_inner(self) -> OwnedReadHalf {
+        self.port
+    }
+
+    /// Returns a reference to the underlying codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the underlying codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns a reference to the read buffer.
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.read.buffer
+    }
+
+    /// Returns a mutable reference to the read buffer.
+    pub fn read_buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.read.buffer
+    }
+
+    /// Returns the maximum size of the read buffer, if any.
+    pub fn max_buffer_size(&self) -> Option<usize> {
+        self.read.max_buffer_size
+    }
+
+    /// Limits how many bytes may be buffered while waiting for a complete frame.
+    ///
+    /// See [`SerialFramed::set_max_buffer_size`].
+    pub fn set_max_buffer_size(&mut self, max: Option<usize>) {
+        self.read.max_buffer_size = max;
+    }
+}
+
+impl<C: Decoder + Unpin> Stream for SerialFramedRead<C> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pin = self.get_mut();
+        pin.read
+            .poll_next(Pin::new(&mut pin.port), &mut pin.codec, cx)
+    }
+}
+
+/// A [`Sink`] of frames encoded to the [`OwnedWriteHalf`] of a port.
+///
+/// This is the write side of [`SerialFramed`] and encodes frames in the same way.
+///
+/// [`Sink`]: futures_sink::Sink
+#[must_use = "sinks do nothing unless polled"]
+#[derive(Debug)]
+pub struct SerialFramedWrite<C> {
+    port: OwnedWriteHalf,
+    codec: C,
+    write: WriteFrame,
+}
+
+impl<C> SerialFramedWrite<C> {
+    /// Create a new `SerialFramedWrite` with the given codec.
+    pub fn new(port: OwnedWriteHalf, codec: C) -> SerialFramedWrite<C> {
+        Self {
+            port,
+            codec,
+            write: WriteFrame::new(BytesMut::with_capacity(INITIAL_WR_CAPACITY)),
+        }
+    }
+
+    /// Maps the codec `C` to `D`, returning a new `SerialFramedWrite`.
+    ///
+    /// Frames that have been encoded but not written yet are kept.
+    pub fn map_codec<D, F>(self, map: F) -> SerialFramedWrite<D>
+    where
+        F: FnOnce(C) -> D,
+    {
+        SerialFramedWrite {
+            port: self.port,
+            codec: map(self.codec),
+            write: self.write,
+        }
+    }
+
+    /// Returns a reference to the underlying write half.
+    pub fn get_ref(&self) -> &OwnedWriteHalf {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying write half.
+    pub fn get_mut(&mut self) -> &mut OwnedWriteHalf {
+        &mut self.port
+    }
+
+    /// Consumes the `SerialFramedWrite`, returning its underlying write half.
+    ///
+    /// Frames that have not been flushed yet are lost.
+    pub fn into_inner(self) -> OwnedWriteHalf {
+        self.port
+    }
+
+    /// Returns a reference to the underlying codec.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the underlying codec.
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Returns a reference to the write buffer.
+    pub fn write_buffer(&self) -> &BytesMut {
+        &self.write.buffer
+    }
+
+    /// Returns a mutable reference to the write buffer.
+    pub fn write_buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.write.buffer
+    }
+
+    /// Returns the current backpressure boundary.
+    pub fn backpressure_boundary(&self) -> usize {
+        self.write.backpressure_boundary
+    }
+
+    /// Updates the backpressure boundary.
+    ///
+    /// See [`SerialFramed::set_backpressure_boundary`].
+    pub fn set_backpressure_boundary(&mut self, boundary: usize) {
+        self.write.backpressure_boundary = boundary;
+    }
+}
+
+impl<I, C: Encoder<I> + Unpin> Sink<I> for SerialFramedWrite<C> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let pin = self.get_mut();
+        pin.write.poll_ready(Pin::new(&mut pin.port), cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let pin = self.get_mut();
+
+        pin.codec.encode(item, &mut pin.write.buffer)?;
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let pin = self.get_mut();
+        pin.write.poll_flush(Pin::new(&mut pin.port), cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let pin = self.get_mut();
+        pin.write.poll_close(Pin::new(&mut pin.port), cx)
+    }
+}
+
+/// A [`Stream`] of frames delimited by silence on the line.
+///
+/// Many binary protocols, such as Modbus RTU, do not mark where a frame starts or ends.
+/// Instead the sender keeps the line idle for some time between frames.  `GapFramed`
+/// collects incoming bytes and yields them as one frame once nothing has been received
+/// for the configured gap.
+///
+/// The gap can either be given directly with [`new`](GapFramed::new), or as a multiple
+/// of the character time of the port with [`with_character_gap`](GapFramed::with_character_gap).
+/// Note that tokio timers have a resolution of one millisecond, and that USB serial
+/// adapters often deliver data in bursts a few milliseconds apart, so very short gaps
+/// may need to be lengthened to work reliably.
+///
+/// Writing to the port is done directly through [`get_mut`](GapFramed::get_mut).
+///
+/// [`Stream`]: futures_core::Stream
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct GapFramed {
+    port: SerialStream,
+    gap: Duration,
+    rd: BytesMut,
+    idle: Option<Pin<Box<Sleep>>>,
+}
+
+impl GapFramed {
+    /// Create a new `GapFramed` that ends a frame once the line has been idle for `gap`.
+    pub fn new(port: SerialStream, gap: Duration) -> GapFramed {
+        Self {
+            port,
+            gap,
+            rd: BytesMut::with_capacity(INITIAL_RD_CAPACITY),
+            idle: None,
+        }
+    }
+
+    /// Create a new `GapFramed` that ends a frame once the line has been idle for
+    /// `characters` character times, computed from the current settings of `port`.
+    ///
+    /// See [`SerialStream::character_time`].
+    ///
+    /// ## Errors
+    ///
+    /// * As for [`SerialStream::character_time`].
+    /// * `InvalidInput` if `characters` is negative or not a number, or the gap is too
+    ///   long for a `Duration`.
+    pub fn with_character_gap(port: SerialStream, characters: f64) -> crate::Result<GapFramed> {
+        let secs = port.character_time()?.as_secs_f64() * characters;
+        let gap = Duration::try_from_secs_f64(secs).map_err(|_| {
+            crate::Error::new(crate::ErrorKind::InvalidInput, "invalid character gap")
+        })?;
+        Ok(Self::new(port, gap))
+    }
+
+    /// Returns the idle time that ends a frame.
+    pub fn gap(&self) -> Duration {
+        self.gap
+    }
+
+    /// Sets the idle time that ends a frame.
+    pub fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Returns a reference to the underlying I/O stream wrapped by `GapFramed`.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying I/O stream wrapped by
+    /// `GapFramed`.
+    ///
+    /// # Note
+    ///
+    /// Care should be taken to not read from the underlying stream, as it may
+    /// corrupt the stream of frames otherwise being worked with.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the `GapFramed`, returning its underlying I/O stream.
+    ///
+    /// Bytes of a frame that has not ended yet are lost.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Returns a reference to the bytes of the frame received so far.
+    pub fn read_buffer(&self) -> &BytesMut {
+        &self.rd
+    }
+}
+
+impl Stream for GapFramed {
+    type Item = io::Result<BytesMut>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let pin = self.get_mut();
+
+        // Read everything that is available, restarting the idle timer on every chunk.
+        loop {
+            pin.rd.reserve(INITIAL_RD_CAPACITY);
+            let n = unsafe {
+                // Convert `&mut [MaybeUnit<u8>]` to `&mut [u8]` because we will be
+                // writing to it via `poll_read` and therefore initializing the memory.
+                let buf = &mut *(pin.rd.chunk_mut() as *mut _ as *mut [MaybeUninit<u8>]);
+                let mut read = ReadBuf::uninit(buf);
+                let ptr = read.filled().as_ptr();
+                match Pin::new(&mut pin.port).poll_read(cx, &mut read) {
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                    Poll::Pending => break,
+                }
+
+                assert_eq!(ptr, read.filled().as_ptr());
+                let n = read.filled().len();
+                pin.rd.advance_mut(n);
+                n
+            };
+
+            if n == 0 {
+                // End of file: whatever is left is the last frame.
+                pin.idle = None;
+                if pin.rd.is_empty() {
+                    return Poll::Ready(None);
+                }
+                return Poll::Ready(Some(Ok(pin.rd.split())));
+            }
+
+            let deadline = Instant::now() + pin.gap;
+            match pin.idle.as_mut() {
+                Some(idle) => idle.as_mut().reset(deadline),
+                None => pin.idle = Some(Box::pin(tokio::time::sleep_until(deadline))),
+            }
+        }
+
+        if pin.rd.is_empty() {
+            return Poll::Pending;
+        }
+        if let Some(idle) = pin.idle.as_mut() {
+            ready!(idle.as_mut().poll(cx));
+        }
+        pin.idle = None;
+        Poll::Ready(Some(Ok(pin.rd.split())))
     }
 }
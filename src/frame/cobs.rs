@@ -0,0 +1,257 @@
+//! Consistent Overhead Byte Stuffing, with zero bytes delimiting the frames.
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// Ends every encoded frame.
+const DELIMITER: u8 = 0x00;
+/// The code of a block holding the most data bytes, and no zero after them.
+const MAX_CODE: u8 = 0xFF;
+
+/// The flavour of byte stuffing used by a [`CobsCodec`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CobsVariant {
+    /// Plain COBS.
+    #[default]
+    Cobs,
+    /// COBS/R, which saves a byte on many frames by storing the last byte of a frame in
+    /// the code of its final block when it is larger than that code.
+    Reduced,
+}
+
+/// A codec for COBS encoded frames, each followed by a zero byte.
+///
+/// Since an encoded frame never contains a zero, the decoder always finds the start of
+/// the next frame after the next zero, whatever happened to the bytes before it.  A frame
+/// that doesn't decode, or that is longer than the maximum frame length, is reported
+/// with an error and skipped, after which decoding carries on with the next frame.
+/// Empty frames, such as those between two zeros in a row, are skipped.
+///
+/// ```
+/// use tokio_serial::frame::{CobsCodec, CobsVariant};
+///
+/// let codec = CobsCodec::new()
+///     .with_variant(CobsVariant::Reduced)
+///     .with_max_frame_length(512);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CobsCodec {
+    variant: CobsVariant,
+    max_frame_length: usize,
+    // Where to continue looking for a delimiter on the next call to `decode`.
+    next_index: usize,
+    is_discarding: bool,
+}
+
+impl CobsCodec {
+    /// Create a codec for plain COBS with no limit on the frame length.
+    pub fn new() -> CobsCodec {
+        Self {
+            variant: CobsVariant::default(),
+            max_frame_length: usize::MAX,
+            next_index: 0,
+            is_discarding: false,
+        }
+    }
+
+    /// Sets the flavour of byte stuffing.
+    pub fn with_variant(mut self, variant: CobsVariant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Sets the maximum length of a decoded frame.
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Returns the flavour of byte stuffing.
+    pub fn variant(&self) -> CobsVariant {
+        self.variant
+    }
+
+    /// Returns the maximum length of a decoded frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// The longest encoding of a frame of the maximum length, without its delimiter.
+    fn max_encoded_length(&self) -> usize {
+        let max = self.max_frame_length;
+        max.saturating_add(max / 254 + 1)
+    }
+
+    fn unstuff(&self, encoded: &[u8]) -> Result<BytesMut, CobsCodecError> {
+        let mut frame = BytesMut::with_capacity(encoded.len());
+        let mut rest = encoded;
+
+        while let Some((&code, data)) = rest.split_first() {
+            let len = usize::from(code) - 1;
+            if len > data.len() {
+                // Only the final block of a COBS/R frame may be short, in which case its
+                // code is really the last byte of the frame.
+                if self.variant != CobsVariant::Reduced {
+                    return Err(CobsCodecError::InvalidFrame);
+                }
+                frame.put_slice(data);
+                frame.put_u8(code);
+                break;
+            }
+
+            frame.put_slice(&data[..len]);
+            rest = &data[len..];
+            if code != MAX_CODE && !rest.is_empty() {
+                frame.put_u8(0);
+            }
+        }
+
+        if frame.len() > self.max_frame_length {
+            return Err(CobsCodecError::FrameTooLong);
+        }
+        Ok(frame)
+    }
+}
+
+impl Default for CobsCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for CobsCodec {
+    type Item = BytesMut;
+    type Error = CobsCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, CobsCodecError> {
+        loop {
+            // The buffer may have been tampered with since the last call.
+            let start = self.next_index.min(src.len());
+            let end = match src[start..].iter().position(|b| *b == DELIMITER) {
+                Some(at) => start + at,
+                None if self.is_discarding => {
+                    src.clear();
+                    self.next_index = 0;
+                    return Ok(None);
+                }
+                None if src.len() > self.max_encoded_length() => {
+                    src.clear();
+                    self.next_index = 0;
+                    self.is_discarding = true;
+                    return Err(CobsCodecError::FrameTooLong);
+                }
+                None => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            };
+
+            let encoded = src.split_to(end);
+            src.advance(1);
+            self.next_index = 0;
+
+            if self.is_discarding {
+                self.is_discarding = false;
+                continue;
+            }
+            if encoded.is_empty() {
+                continue;
+            }
+            return self.unstuff(&encoded).map(Some);
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, CobsCodecError> {
+        let frame = self.decode(src)?;
+        if frame.is_none() {
+            // A frame that never got its delimiter is incomplete.
+            src.clear();
+            self.next_index = 0;
+            self.is_discarding = false;
+        }
+        Ok(frame)
+    }
+}
+
+impl<T> Encoder<T> for CobsCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = CobsCodecError;
+
+    fn encode(&mut self, frame: T, dst: &mut BytesMut) -> Result<(), CobsCodecError> {
+        let frame = frame.as_ref();
+        dst.reserve(frame.len() + frame.len() / 254 + 2);
+
+        let mut code_at = dst.len();
+        let mut code = 1u8;
+        dst.put_u8(code);
+        for (i, &byte) in frame.iter().enumerate() {
+            if byte == 0 {
+                dst[code_at] = code;
+                code_at = dst.len();
+                code = 1;
+                dst.put_u8(code);
+                continue;
+            }
+
+            dst.put_u8(byte);
+            code += 1;
+            if code == MAX_CODE && i + 1 < frame.len() {
+                dst[code_at] = code;
+                code_at = dst.len();
+                code = 1;
+                dst.put_u8(code);
+            }
+        }
+
+        match frame.last() {
+            // The last byte moves into the code of the final block, if that is unambiguous.
+            Some(&last) if self.variant == CobsVariant::Reduced && last > code => {
+                dst[code_at] = last;
+                dst.truncate(dst.len() - 1);
+            }
+            _ => dst[code_at] = code,
+        }
+        dst.put_u8(DELIMITER);
+        Ok(())
+    }
+}
+
+/// An error from a [`CobsCodec`].
+#[derive(Debug)]
+pub enum CobsCodecError {
+    /// A frame was longer than the maximum frame length.  It is skipped.
+    FrameTooLong,
+    /// A frame was not validly encoded, probably because bytes of it were lost or
+    /// corrupted on the line.  It is skipped.
+    InvalidFrame,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for CobsCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CobsCodecError::FrameTooLong => write!(f, "max frame length exceeded"),
+            CobsCodecError::InvalidFrame => write!(f, "invalid COBS frame"),
+            CobsCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CobsCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CobsCodecError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CobsCodecError {
+    fn from(err: io::Error) -> Self {
+        CobsCodecError::Io(err)
+    }
+}
@@ -0,0 +1,280 @@
+//! HDLC-like framing in asynchronous mode, as used by PPP and described in [RFC 1662].
+//!
+//! [RFC 1662]: https://www.rfc-editor.org/rfc/rfc1662
+use crate::crc::Crc;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// Starts and ends every frame.
+const FLAG: u8 = 0x7E;
+/// Starts an escape sequence; the byte after it has been XORed with `ESCAPE_XOR`.
+const ESCAPE: u8 = 0x7D;
+const ESCAPE_XOR: u8 = 0x20;
+
+/// The frame check sequence appended to every frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fcs {
+    /// The 16 bit FCS, which every implementation must support.
+    #[default]
+    Fcs16,
+    /// The 32 bit FCS.
+    Fcs32,
+}
+
+impl Fcs {
+    fn crc(self) -> Crc {
+        match self {
+            Fcs::Fcs16 => Crc::CRC_16_IBM_SDLC,
+            Fcs::Fcs32 => Crc::CRC_32_ISO_HDLC,
+        }
+    }
+}
+
+/// A codec for HDLC-like framing with byte stuffing.
+///
+/// Frames are decoded into their contents, from the address field up to but not
+/// including the FCS, with all escapes undone.  Neither the address and control fields
+/// nor the protocol field are interpreted, and the encoder takes frames in the same
+/// form, so it is up to the caller to add them.
+///
+/// Control characters set in the async control character map (ACCM) are escaped by the
+/// encoder, and dropped by the decoder when they arrive unescaped, since they must have
+/// been inserted by equipment along the line.  The default ACCM of `0xFFFFFFFF` escapes
+/// all of them.
+///
+/// A frame with a bad FCS is reported with [`HdlcCodecError::BadFcs`], a frame that is
+/// too short to hold an FCS with [`HdlcCodecError::TooShort`], and an overlong one with
+/// [`HdlcCodecError::FrameTooLong`].  Either way the frame is dropped and decoding
+/// carries on with the next one.  Frames ended with the abort sequence `0x7D 0x7E` are
+/// dropped silently.
+///
+/// ```
+/// use tokio_serial::frame::{Fcs, HdlcCodec};
+///
+/// let codec = HdlcCodec::new()
+///     .with_fcs(Fcs::Fcs32)
+///     .with_accm(0x000A_0000)
+///     .with_max_frame_length(1500 + 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HdlcCodec {
+    fcs: Fcs,
+    accm: u32,
+    max_frame_length: usize,
+    frame: BytesMut,
+    escaped: bool,
+    is_discarding: bool,
+}
+
+impl HdlcCodec {
+    /// Create a codec with a 16 bit FCS, the default ACCM, and no limit on the frame
+    /// length.
+    pub fn new() -> HdlcCodec {
+        Self {
+            fcs: Fcs::default(),
+            accm: 0xFFFF_FFFF,
+            max_frame_length: usize::MAX,
+            frame: BytesMut::new(),
+            escaped: false,
+            is_discarding: false,
+        }
+    }
+
+    /// Sets the frame check sequence.
+    pub fn with_fcs(mut self, fcs: Fcs) -> Self {
+        self.fcs = fcs;
+        self
+    }
+
+    /// Sets the async control character map.
+    ///
+    /// Bit `n` set means that the control character `n` is escaped.
+    pub fn with_accm(mut self, accm: u32) -> Self {
+        self.accm = accm;
+        self
+    }
+
+    /// Sets the maximum length of a decoded frame, not counting its FCS.
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Returns the frame check sequence.
+    pub fn fcs(&self) -> Fcs {
+        self.fcs
+    }
+
+    /// Returns the async control character map.
+    pub fn accm(&self) -> u32 {
+        self.accm
+    }
+
+    /// Sets the async control character map, for when it is renegotiated by LCP.
+    pub fn set_accm(&mut self, accm: u32) {
+        self.accm = accm;
+    }
+
+    /// Returns the maximum length of a decoded frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    fn in_accm(&self, byte: u8) -> bool {
+        byte < 0x20 && self.accm & (1 << byte) != 0
+    }
+
+    /// Checks and strips the FCS of a complete frame.
+    fn check(&self, mut frame: BytesMut) -> Result<BytesMut, HdlcCodecError> {
+        let crc = self.fcs.crc();
+        let fcs_len = crc.byte_width();
+        if frame.len() < fcs_len {
+            return Err(HdlcCodecError::TooShort);
+        }
+
+        let fcs = frame.split_off(frame.len() - fcs_len);
+        let received = fcs
+            .iter()
+            .rev()
+            .fold(0u32, |fcs, byte| (fcs << 8) | u32::from(*byte));
+        if crc.checksum(&frame) != received {
+            return Err(HdlcCodecError::BadFcs);
+        }
+        if frame.len() > self.max_frame_length {
+            return Err(HdlcCodecError::FrameTooLong);
+        }
+        Ok(frame)
+    }
+}
+
+impl Default for HdlcCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for HdlcCodec {
+    type Item = BytesMut;
+    type Error = HdlcCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, HdlcCodecError> {
+        let fcs_len = self.fcs.crc().byte_width();
+
+        while src.has_remaining() {
+            let byte = src.get_u8();
+
+            if byte == FLAG {
+                let aborted = self.escaped;
+                let discarded = self.is_discarding;
+                self.escaped = false;
+                self.is_discarding = false;
+                let frame = self.frame.split();
+                if aborted || discarded || frame.is_empty() {
+                    continue;
+                }
+                return self.check(frame).map(Some);
+            }
+            if self.is_discarding || self.in_accm(byte) {
+                continue;
+            }
+            if byte == ESCAPE {
+                self.escaped = true;
+                continue;
+            }
+
+            let byte = if self.escaped {
+                self.escaped = false;
+                byte ^ ESCAPE_XOR
+            } else {
+                byte
+            };
+
+            if self.frame.len() >= self.max_frame_length.saturating_add(fcs_len) {
+                self.frame.clear();
+                self.is_discarding = true;
+                return Err(HdlcCodecError::FrameTooLong);
+            }
+            self.frame.put_u8(byte);
+        }
+
+        Ok(None)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, HdlcCodecError> {
+        let frame = self.decode(src)?;
+        if frame.is_none() {
+            // A frame that never got its closing flag is incomplete.
+            self.frame.clear();
+            self.escaped = false;
+            self.is_discarding = false;
+        }
+        Ok(frame)
+    }
+}
+
+impl<T> Encoder<T> for HdlcCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = HdlcCodecError;
+
+    fn encode(&mut self, frame: T, dst: &mut BytesMut) -> Result<(), HdlcCodecError> {
+        let frame = frame.as_ref();
+        let crc = self.fcs.crc();
+        let fcs = crc.checksum(frame).to_le_bytes();
+
+        dst.reserve(frame.len() + 2 * crc.byte_width() + 2);
+        dst.put_u8(FLAG);
+        for &byte in frame.iter().chain(&fcs[..crc.byte_width()]) {
+            if byte == FLAG || byte == ESCAPE || self.in_accm(byte) {
+                dst.put_slice(&[ESCAPE, byte ^ ESCAPE_XOR]);
+            } else {
+                dst.put_u8(byte);
+            }
+        }
+        dst.put_u8(FLAG);
+        Ok(())
+    }
+}
+
+/// An error from an [`HdlcCodec`].
+#[derive(Debug)]
+pub enum HdlcCodecError {
+    /// A frame failed its frame check sequence.  It is skipped.
+    BadFcs,
+    /// A frame was too short to hold a frame check sequence.  It is skipped.
+    TooShort,
+    /// A frame was longer than the maximum frame length.  It is skipped.
+    FrameTooLong,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for HdlcCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdlcCodecError::BadFcs => write!(f, "frame check sequence mismatch"),
+            HdlcCodecError::TooShort => write!(f, "frame too short"),
+            HdlcCodecError::FrameTooLong => write!(f, "max frame length exceeded"),
+            HdlcCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for HdlcCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HdlcCodecError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HdlcCodecError {
+    fn from(err: io::Error) -> Self {
+        HdlcCodecError::Io(err)
+    }
+}
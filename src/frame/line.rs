@@ -0,0 +1,320 @@
+//! A codec for text lines, such as the console output of an embedded device.
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::marker::PhantomData;
+use std::{fmt, io, str};
+
+/// The bytes that end a line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LineTerminator {
+    /// A line feed, `\n`.
+    #[default]
+    Lf,
+    /// A carriage return, `\r`.
+    Cr,
+    /// A carriage return followed by a line feed, `\r\n`.
+    CrLf,
+    /// Any single one of the given bytes.
+    ///
+    /// The encoder appends the first byte of the set.  Note that with `\r` and `\n` in
+    /// the set, a `\r\n` sequence ends a line and then an empty one.
+    AnyOf(Vec<u8>),
+}
+
+impl LineTerminator {
+    /// Finds the first terminator in `buf`, returning its position and length.
+    fn find(&self, buf: &[u8]) -> Option<(usize, usize)> {
+        match self {
+            LineTerminator::Lf => buf.iter().position(|b| *b == b'\n').map(|at| (at, 1)),
+            LineTerminator::Cr => buf.iter().position(|b| *b == b'\r').map(|at| (at, 1)),
+            LineTerminator::CrLf => buf.windows(2).position(|w| w == b"\r\n").map(|at| (at, 2)),
+            LineTerminator::AnyOf(set) => {
+                buf.iter().position(|b| set.contains(b)).map(|at| (at, 1))
+            }
+        }
+    }
+
+    /// How many bytes at the end of `buf` could be the start of a terminator.
+    fn partial_len(&self, buf: &[u8]) -> usize {
+        match self {
+            LineTerminator::CrLf if buf.last() == Some(&b'\r') => 1,
+            _ => 0,
+        }
+    }
+
+    fn encode(&self, dst: &mut BytesMut) {
+        match self {
+            LineTerminator::Lf => dst.put_u8(b'\n'),
+            LineTerminator::Cr => dst.put_u8(b'\r'),
+            LineTerminator::CrLf => dst.put_slice(b"\r\n"),
+            LineTerminator::AnyOf(set) => dst.put_slice(&set[..set.len().min(1)]),
+        }
+    }
+}
+
+/// How a [`LineCodec`] producing `String`s treats bytes that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Fail the line with [`LineCodecError::InvalidUtf8`].
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    Lossy,
+}
+
+/// A codec for lines of text ending in a configurable terminator.
+///
+/// `LineCodec<String>`, created with [`new`](LineCodec::new), decodes lines into
+/// `String`s according to its [`Utf8Policy`].  `LineCodec<BytesMut>`, created with
+/// [`raw`](LineCodec::raw), hands out the bytes of each line as they are.  Either way
+/// the terminator is not part of the decoded line, and the encoder appends it to
+/// every line it writes.
+///
+/// With a maximum line length, a line that grows longer than that is reported once
+/// with [`LineCodecError::MaxLineLengthExceeded`], and its bytes are discarded up to and
+/// including the next terminator.  Decoding then carries on with the line after it.
+///
+/// ```
+/// use tokio_serial::frame::{LineCodec, LineTerminator, Utf8Policy};
+///
+/// let codec = LineCodec::new()
+///     .with_terminator(LineTerminator::CrLf)
+///     .with_max_length(256)
+///     .with_utf8_policy(Utf8Policy::Lossy);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LineCodec<T = String> {
+    terminator: LineTerminator,
+    max_length: usize,
+    utf8: Utf8Policy,
+    // Where to continue looking for a terminator on the next call to `decode`.
+    next_index: usize,
+    is_discarding: bool,
+    item: PhantomData<fn() -> T>,
+}
+
+impl LineCodec<String> {
+    /// Create a codec for `\n` terminated lines of strictly valid UTF-8, with no
+    /// limit on their length.
+    pub fn new() -> LineCodec<String> {
+        Self::with_item()
+    }
+
+    /// Sets how bytes that are not valid UTF-8 are treated.
+    pub fn with_utf8_policy(mut self, utf8: Utf8Policy) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    /// Returns how bytes that are not valid UTF-8 are treated.
+    pub fn utf8_policy(&self) -> Utf8Policy {
+        self.utf8
+    }
+}
+
+impl Default for LineCodec<String> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineCodec<BytesMut> {
+    /// Create a codec for `\n` terminated lines of raw bytes, with no limit on their
+    /// length.
+    pub fn raw() -> LineCodec<BytesMut> {
+        Self::with_item()
+    }
+}
+
+impl<T> LineCodec<T> {
+    fn with_item() -> Self {
+        Self {
+            terminator: LineTerminator::default(),
+            max_length: usize::MAX,
+            utf8: Utf8Policy::default(),
+            next_index: 0,
+            is_discarding: false,
+            item: PhantomData,
+        }
+    }
+
+    /// Sets the terminator that ends a line.
+    pub fn with_terminator(mut self, terminator: LineTerminator) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Sets the maximum length of a line, not counting its terminator.
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Returns the terminator that ends a line.
+    pub fn terminator(&self) -> &LineTerminator {
+        &self.terminator
+    }
+
+    /// Returns the maximum length of a line.
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Splits the next complete line, without its terminator, off `buf`.
+    fn decode_line(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, LineCodecError> {
+        loop {
+            // The buffer may have been tampered with since the last call.
+            let start = self.next_index.min(buf.len());
+            let found = self
+                .terminator
+                .find(&buf[start..])
+                .map(|(at, len)| (start + at, len));
+
+            match found {
+                Some((at, len)) if self.is_discarding => {
+                    // The rest of the overlong line is gone; start afresh after it.
+                    buf.advance(at + len);
+                    self.next_index = 0;
+                    self.is_discarding = false;
+                }
+                None if self.is_discarding => {
+                    let keep = self.terminator.partial_len(buf);
+                    buf.advance(buf.len() - keep);
+                    self.next_index = 0;
+                    return Ok(None);
+                }
+                Some((at, len)) => {
+                    self.next_index = 0;
+                    if at > self.max_length {
+                        buf.advance(at + len);
+                        return Err(LineCodecError::MaxLineLengthExceeded);
+                    }
+                    let mut line = buf.split_to(at + len);
+                    line.truncate(at);
+                    return Ok(Some(line));
+                }
+                None => {
+                    let partial = self.terminator.partial_len(buf);
+                    if buf.len() - partial > self.max_length {
+                        self.is_discarding = true;
+                        return Err(LineCodecError::MaxLineLengthExceeded);
+                    }
+                    self.next_index = buf.len() - partial;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Splits whatever is left in `buf` off as the last line.
+    fn decode_last_line(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, LineCodecError> {
+        if let Some(line) = self.decode_line(buf)? {
+            return Ok(Some(line));
+        }
+        self.next_index = 0;
+        if self.is_discarding {
+            self.is_discarding = false;
+            buf.clear();
+            return Ok(None);
+        }
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(buf.split()))
+    }
+
+    fn line_to_string(&self, line: BytesMut) -> Result<String, LineCodecError> {
+        match self.utf8 {
+            Utf8Policy::Strict => match str::from_utf8(&line) {
+                Ok(line) => Ok(line.to_string()),
+                Err(err) => Err(LineCodecError::InvalidUtf8(err)),
+            },
+            Utf8Policy::Lossy => Ok(String::from_utf8_lossy(&line).into_owned()),
+        }
+    }
+}
+
+impl Decoder for LineCodec<String> {
+    type Item = String;
+    type Error = LineCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<String>, LineCodecError> {
+        match self.decode_line(buf)? {
+            Some(line) => self.line_to_string(line).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<String>, LineCodecError> {
+        match self.decode_last_line(buf)? {
+            Some(line) => self.line_to_string(line).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Decoder for LineCodec<BytesMut> {
+    type Item = BytesMut;
+    type Error = LineCodecError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, LineCodecError> {
+        self.decode_line(buf)
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<BytesMut>, LineCodecError> {
+        self.decode_last_line(buf)
+    }
+}
+
+impl<T, L> Encoder<L> for LineCodec<T>
+where
+    L: AsRef<[u8]>,
+{
+    type Error = LineCodecError;
+
+    fn encode(&mut self, line: L, buf: &mut BytesMut) -> Result<(), LineCodecError> {
+        let line = line.as_ref();
+        buf.reserve(line.len() + 2);
+        buf.put_slice(line);
+        self.terminator.encode(buf);
+        Ok(())
+    }
+}
+
+/// An error from a [`LineCodec`].
+#[derive(Debug)]
+pub enum LineCodecError {
+    /// A line was longer than the maximum length.  It is skipped.
+    MaxLineLengthExceeded,
+    /// A line was not valid UTF-8.  It is skipped.
+    InvalidUtf8(str::Utf8Error),
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for LineCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineCodecError::MaxLineLengthExceeded => write!(f, "max line length exceeded"),
+            LineCodecError::InvalidUtf8(err) => write!(f, "invalid line: {}", err),
+            LineCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for LineCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LineCodecError::MaxLineLengthExceeded => None,
+            LineCodecError::InvalidUtf8(err) => Some(err),
+            LineCodecError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for LineCodecError {
+    fn from(err: io::Error) -> Self {
+        LineCodecError::Io(err)
+    }
+}
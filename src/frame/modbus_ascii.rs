@@ -0,0 +1,243 @@
+//! Modbus ASCII framing: a `:`, the frame in hexadecimal, an LRC and a CR LF.
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// Starts every frame.
+const START: u8 = b':';
+/// Ends every frame, after a carriage return.
+const END: u8 = b'\n';
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Computes the longitudinal redundancy check of `data`: the two's complement of the
+/// sum of its bytes.
+fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg()
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        _ => None,
+    }
+}
+
+/// A codec for Modbus ASCII frames.
+///
+/// Frames are decoded into the bytes they carry, the unit id followed by the PDU, with
+/// the LRC checked and removed.  The encoder takes frames in the same form.
+///
+/// Anything before a `:` is skipped, and a `:` in the middle of a frame starts a new one,
+/// dropping what came before it.  A frame with a bad LRC is reported with
+/// [`ModbusAsciiCodecError::BadLrc`], one that isn't made of pairs of hex digits with
+/// [`ModbusAsciiCodecError::InvalidFrame`], and an overlong one with
+/// [`ModbusAsciiCodecError::FrameTooLong`].  Either way the frame is dropped and
+/// decoding carries on with the next one.
+///
+/// ```
+/// use tokio_serial::frame::ModbusAsciiCodec;
+///
+/// let codec = ModbusAsciiCodec::new().with_max_frame_length(1 + 253);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModbusAsciiCodec {
+    max_frame_length: usize,
+    in_frame: bool,
+    // Where to continue looking for the end of the frame on the next call to `decode`.
+    next_index: usize,
+}
+
+impl ModbusAsciiCodec {
+    /// Create a codec with no limit on the frame length.
+    pub fn new() -> ModbusAsciiCodec {
+        Self {
+            max_frame_length: usize::MAX,
+            in_frame: false,
+            next_index: 0,
+        }
+    }
+
+    /// Sets the maximum length of a decoded frame, not counting its LRC.
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Returns the maximum length of a decoded frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// The longest line of hex digits, LRC and CR a frame of the maximum length makes.
+    fn max_encoded_length(&self) -> usize {
+        self.max_frame_length
+            .saturating_add(1)
+            .saturating_mul(2)
+            .saturating_add(1)
+    }
+
+    /// Decodes and checks the hex digits of a complete frame.
+    fn check(&self, line: &[u8]) -> Result<BytesMut, ModbusAsciiCodecError> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        // `usize::is_multiple_of` is newer than the minimum supported Rust version.
+        #[allow(clippy::manual_is_multiple_of)]
+        let odd = line.len() % 2 != 0;
+        if odd || line.len() < 6 {
+            return Err(ModbusAsciiCodecError::InvalidFrame);
+        }
+
+        let mut frame = BytesMut::with_capacity(line.len() / 2);
+        for pair in line.chunks(2) {
+            match (hex_value(pair[0]), hex_value(pair[1])) {
+                (Some(high), Some(low)) => frame.put_u8(high << 4 | low),
+                _ => return Err(ModbusAsciiCodecError::InvalidFrame),
+            }
+        }
+
+        let received = frame.split_off(frame.len() - 1)[0];
+        if lrc(&frame) != received {
+            return Err(ModbusAsciiCodecError::BadLrc);
+        }
+        if frame.len() > self.max_frame_length {
+            return Err(ModbusAsciiCodecError::FrameTooLong);
+        }
+        Ok(frame)
+    }
+}
+
+impl Default for ModbusAsciiCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for ModbusAsciiCodec {
+    type Item = BytesMut;
+    type Error = ModbusAsciiCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, ModbusAsciiCodecError> {
+        loop {
+            if !self.in_frame {
+                match src.iter().position(|b| *b == START) {
+                    Some(at) => src.advance(at + 1),
+                    None => {
+                        src.clear();
+                        return Ok(None);
+                    }
+                }
+                self.in_frame = true;
+                self.next_index = 0;
+            }
+
+            // The buffer may have been tampered with since the last call.
+            let start = self.next_index.min(src.len());
+            let at = match src[start..].iter().position(|b| *b == END || *b == START) {
+                Some(at) => start + at,
+                None if src.len() > self.max_encoded_length() => {
+                    // Whatever follows is skipped up to the next start of a frame.
+                    src.clear();
+                    self.in_frame = false;
+                    return Err(ModbusAsciiCodecError::FrameTooLong);
+                }
+                None => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            };
+
+            if src[at] == START {
+                // The frame was cut short, and a new one starts here.
+                src.advance(at);
+                self.in_frame = false;
+                continue;
+            }
+
+            let line = src.split_to(at);
+            src.advance(1);
+            self.in_frame = false;
+            return self.check(&line).map(Some);
+        }
+    }
+
+    fn decode_eof(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<BytesMut>, ModbusAsciiCodecError> {
+        let frame = self.decode(src)?;
+        if frame.is_none() {
+            // A frame that never got its CR LF is incomplete.
+            src.clear();
+            self.in_frame = false;
+            self.next_index = 0;
+        }
+        Ok(frame)
+    }
+}
+
+impl<T> Encoder<T> for ModbusAsciiCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = ModbusAsciiCodecError;
+
+    fn encode(&mut self, frame: T, dst: &mut BytesMut) -> Result<(), ModbusAsciiCodecError> {
+        let frame = frame.as_ref();
+        if frame.len() > self.max_frame_length {
+            return Err(ModbusAsciiCodecError::FrameTooLong);
+        }
+
+        dst.reserve(2 * frame.len() + 5);
+        dst.put_u8(START);
+        for &byte in frame.iter().chain(&[lrc(frame)]) {
+            dst.put_u8(HEX_DIGITS[usize::from(byte >> 4)]);
+            dst.put_u8(HEX_DIGITS[usize::from(byte & 0x0F)]);
+        }
+        dst.put_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+/// An error from a [`ModbusAsciiCodec`].
+#[derive(Debug)]
+pub enum ModbusAsciiCodecError {
+    /// A frame failed its LRC.  It is skipped.
+    BadLrc,
+    /// A frame was not made of pairs of hex digits, or was too short to hold a unit id,
+    /// a function code and an LRC.  It is skipped.
+    InvalidFrame,
+    /// A frame was longer than the maximum frame length.  It is skipped.
+    FrameTooLong,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for ModbusAsciiCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModbusAsciiCodecError::BadLrc => write!(f, "frame LRC mismatch"),
+            ModbusAsciiCodecError::InvalidFrame => write!(f, "invalid Modbus ASCII frame"),
+            ModbusAsciiCodecError::FrameTooLong => write!(f, "max frame length exceeded"),
+            ModbusAsciiCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ModbusAsciiCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModbusAsciiCodecError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModbusAsciiCodecError {
+    fn from(err: io::Error) -> Self {
+        ModbusAsciiCodecError::Io(err)
+    }
+}
@@ -0,0 +1,310 @@
+//! Binary packets made of a sync pattern, a length field, the payload and a CRC.
+use crate::crc::Crc;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// The default maximum length of a payload, the same as that of `LengthDelimitedCodec`.
+const DEFAULT_MAX_PAYLOAD_LENGTH: usize = 8 * 1024 * 1024;
+
+/// The byte order of multi-byte fields in a packet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Endianness {
+    /// Most significant byte first.
+    #[default]
+    Big,
+    /// Least significant byte first.
+    Little,
+}
+
+/// A codec for packets of the form `SYNC | LEN | PAYLOAD | CRC`.
+///
+/// * `SYNC` is a fixed byte pattern marking the start of a packet.
+/// * `LEN` is an unsigned integer of 1 to 4 bytes giving the length of the payload, or
+///   with [`with_length_includes_header`](PacketCodec::with_length_includes_header) the
+///   length of the payload plus `SYNC` and `LEN`.
+/// * `CRC`, if there is one, is computed over `LEN` and `PAYLOAD`, and is sent in the
+///   same byte order as `LEN`.
+///
+/// The decoder yields the payload of each packet.  Bytes before a sync pattern are
+/// skipped.  When a packet has a bad CRC or an impossible length, the sync pattern it
+/// started with may well have been part of some other data, so the error is reported
+/// and the decoder hunts for the next sync pattern starting right after the start of
+/// the rejected one.
+///
+/// ```
+/// use tokio_serial::frame::{Crc, Endianness, PacketCodec};
+///
+/// let codec = PacketCodec::new(&[0xAA, 0x55][..])
+///     .with_length_field(1, Endianness::Big)
+///     .with_crc(Some(Crc::CRC_8_SMBUS))
+///     .with_max_payload_length(255);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PacketCodec {
+    sync: Vec<u8>,
+    length_width: usize,
+    endianness: Endianness,
+    length_includes_header: bool,
+    crc: Option<Crc>,
+    max_payload_length: usize,
+}
+
+impl PacketCodec {
+    /// Create a codec for packets starting with `sync`, with a big endian 2 byte length
+    /// field counting the payload, a CRC-16/XMODEM and payloads of up to 8 MiB.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `sync` is empty.
+    pub fn new(sync: impl Into<Vec<u8>>) -> PacketCodec {
+        let sync = sync.into();
+        assert!(!sync.is_empty(), "sync pattern must not be empty");
+        Self {
+            sync,
+            length_width: 2,
+            endianness: Endianness::default(),
+            length_includes_header: false,
+            crc: Some(Crc::CRC_16_XMODEM),
+            max_payload_length: DEFAULT_MAX_PAYLOAD_LENGTH,
+        }
+    }
+
+    /// Sets the width in bytes and the byte order of the length field.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `width` is not between 1 and 4.
+    pub fn with_length_field(mut self, width: usize, endianness: Endianness) -> Self {
+        assert!(
+            (1..=4).contains(&width),
+            "length field must be 1 to 4 bytes wide"
+        );
+        self.length_width = width;
+        self.endianness = endianness;
+        self
+    }
+
+    /// Sets whether the length field counts the sync pattern and the length field
+    /// itself as well as the payload.
+    pub fn with_length_includes_header(mut self, includes_header: bool) -> Self {
+        self.length_includes_header = includes_header;
+        self
+    }
+
+    /// Sets the CRC appended to every packet, or `None` for packets without one.
+    pub fn with_crc(mut self, crc: Option<Crc>) -> Self {
+        self.crc = crc;
+        self
+    }
+
+    /// Sets the maximum length of a payload.
+    ///
+    /// A length field that is damaged by noise can announce a huge packet, which the
+    /// decoder would wait for until that much data arrived; lengths above the maximum
+    /// are rejected as impossible instead.
+    pub fn with_max_payload_length(mut self, max_payload_length: usize) -> Self {
+        self.max_payload_length = max_payload_length;
+        self
+    }
+
+    /// Returns the sync pattern.
+    pub fn sync(&self) -> &[u8] {
+        &self.sync
+    }
+
+    /// Returns the CRC appended to every packet.
+    pub fn crc(&self) -> Option<Crc> {
+        self.crc
+    }
+
+    /// Returns the maximum length of a payload.
+    pub fn max_payload_length(&self) -> usize {
+        self.max_payload_length
+    }
+
+    fn header_len(&self) -> usize {
+        self.sync.len() + self.length_width
+    }
+
+    fn crc_len(&self) -> usize {
+        self.crc.map_or(0, |crc| crc.byte_width())
+    }
+
+    fn get_uint(&self, mut field: &[u8]) -> u64 {
+        match self.endianness {
+            Endianness::Big => field.get_uint(field.len()),
+            Endianness::Little => field.get_uint_le(field.len()),
+        }
+    }
+
+    fn put_uint(&self, dst: &mut BytesMut, value: u64, width: usize) {
+        match self.endianness {
+            Endianness::Big => dst.put_uint(value, width),
+            Endianness::Little => dst.put_uint_le(value, width),
+        }
+    }
+
+    /// Skips everything before the next sync pattern, or before the bytes at the end of
+    /// `src` that may be the start of one.  Returns whether a whole pattern was found.
+    fn hunt(&self, src: &mut BytesMut) -> bool {
+        let sync = &self.sync[..];
+        let mut at = 0;
+        while at < src.len() {
+            let candidate = &src[at..];
+            let len = candidate.len().min(sync.len());
+            if candidate[..len] == sync[..len] {
+                break;
+            }
+            at += 1;
+        }
+        src.advance(at);
+        src.len() >= sync.len()
+    }
+
+    /// The length of the payload of the packet at the start of `src`, whose header is
+    /// complete.
+    fn payload_len(&self, src: &[u8]) -> Result<usize, PacketCodecError> {
+        let header_len = self.header_len();
+        let length = self.get_uint(&src[self.sync.len()..header_len]) as usize;
+        let payload_len = if self.length_includes_header {
+            length
+                .checked_sub(header_len)
+                .ok_or(PacketCodecError::InvalidLength)?
+        } else {
+            length
+        };
+        if payload_len > self.max_payload_length {
+            return Err(PacketCodecError::InvalidLength);
+        }
+        Ok(payload_len)
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = BytesMut;
+    type Error = PacketCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, PacketCodecError> {
+        if !self.hunt(src) {
+            return Ok(None);
+        }
+
+        let header_len = self.header_len();
+        if src.len() < header_len {
+            return Ok(None);
+        }
+        let payload_len = match self.payload_len(src) {
+            Ok(len) => len,
+            Err(err) => {
+                // Look for a real sync pattern inside what seemed to be this packet.
+                src.advance(1);
+                return Err(err);
+            }
+        };
+
+        let packet_len = header_len + payload_len + self.crc_len();
+        if src.len() < packet_len {
+            // The length may be damaged, so don't reserve room for all of it.
+            return Ok(None);
+        }
+
+        if let Some(crc) = self.crc {
+            let checked = &src[self.sync.len()..header_len + payload_len];
+            let received = self.get_uint(&src[header_len + payload_len..packet_len]);
+            if u64::from(crc.checksum(checked)) != received {
+                src.advance(1);
+                return Err(PacketCodecError::BadCrc);
+            }
+        }
+
+        let mut packet = src.split_to(packet_len);
+        packet.advance(header_len);
+        packet.truncate(payload_len);
+        Ok(Some(packet))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, PacketCodecError> {
+        let packet = self.decode(src)?;
+        if packet.is_none() {
+            // A packet cut short is of no use.
+            src.clear();
+        }
+        Ok(packet)
+    }
+}
+
+impl<T> Encoder<T> for PacketCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = PacketCodecError;
+
+    fn encode(&mut self, payload: T, dst: &mut BytesMut) -> Result<(), PacketCodecError> {
+        let payload = payload.as_ref();
+        let header_len = self.header_len();
+        let length = if self.length_includes_header {
+            payload.len() + header_len
+        } else {
+            payload.len()
+        };
+        if payload.len() > self.max_payload_length
+            || (length as u64) >> (8 * self.length_width) != 0
+        {
+            return Err(PacketCodecError::PayloadTooLong);
+        }
+
+        dst.reserve(header_len + payload.len() + self.crc_len());
+        dst.put_slice(&self.sync);
+        let checked_from = dst.len();
+        self.put_uint(dst, length as u64, self.length_width);
+        dst.put_slice(payload);
+        if let Some(crc) = self.crc {
+            let check = crc.checksum(&dst[checked_from..]);
+            self.put_uint(dst, u64::from(check), crc.byte_width());
+        }
+        Ok(())
+    }
+}
+
+/// An error from a [`PacketCodec`].
+#[derive(Debug)]
+pub enum PacketCodecError {
+    /// A packet failed its CRC.  The decoder hunts for the next sync pattern.
+    BadCrc,
+    /// The length field of a packet was out of range.  The decoder hunts for the next
+    /// sync pattern.
+    InvalidLength,
+    /// A payload was too long to be encoded.
+    PayloadTooLong,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for PacketCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketCodecError::BadCrc => write!(f, "packet CRC mismatch"),
+            PacketCodecError::InvalidLength => write!(f, "invalid packet length"),
+            PacketCodecError::PayloadTooLong => write!(f, "payload too long"),
+            PacketCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PacketCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PacketCodecError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PacketCodecError {
+    fn from(err: io::Error) -> Self {
+        PacketCodecError::Io(err)
+    }
+}
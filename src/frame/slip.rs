@@ -0,0 +1,216 @@
+//! Serial Line Internet Protocol framing, as described in [RFC 1055].
+//!
+//! [RFC 1055]: https://www.rfc-editor.org/rfc/rfc1055
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// Marks the end of a frame.
+const END: u8 = 0xC0;
+/// Starts an escape sequence.
+const ESC: u8 = 0xDB;
+/// `ESC ESC_END` stands for an `END` byte in the frame.
+const ESC_END: u8 = 0xDC;
+/// `ESC ESC_ESC` stands for an `ESC` byte in the frame.
+const ESC_ESC: u8 = 0xDD;
+
+/// A codec for SLIP framed packets.
+///
+/// Frames are decoded into the bytes they carry, with all escapes undone.  Empty frames,
+/// such as those between two `END` bytes in a row, are skipped.  A frame containing an
+/// invalid escape sequence, or one longer than the maximum frame length, is reported
+/// with an error and skipped up to the next `END`, after which decoding carries on.
+///
+/// The encoder escapes each frame and terminates it with `END`.  By default it also
+/// starts each frame with an `END`, which flushes out any line noise the receiver may
+/// have picked up since the previous frame.
+///
+/// ```
+/// use tokio_serial::frame::SlipCodec;
+///
+/// let codec = SlipCodec::new().with_max_frame_length(16 * 1024);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlipCodec {
+    leading_end: bool,
+    max_frame_length: usize,
+    frame: BytesMut,
+    escaped: bool,
+    is_discarding: bool,
+}
+
+impl SlipCodec {
+    /// Create a codec that sends a leading `END` and has no limit on the frame length.
+    pub fn new() -> SlipCodec {
+        Self {
+            leading_end: true,
+            max_frame_length: usize::MAX,
+            frame: BytesMut::new(),
+            escaped: false,
+            is_discarding: false,
+        }
+    }
+
+    /// Sets whether encoded frames start with an `END` byte.
+    pub fn with_leading_end(mut self, leading_end: bool) -> Self {
+        self.leading_end = leading_end;
+        self
+    }
+
+    /// Sets the maximum length of a decoded frame.
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Returns whether encoded frames start with an `END` byte.
+    pub fn leading_end(&self) -> bool {
+        self.leading_end
+    }
+
+    /// Returns the maximum length of a decoded frame.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Drops the frame being decoded, and everything up to the next `END` with it.
+    fn discard(&mut self) {
+        self.frame.clear();
+        self.escaped = false;
+        self.is_discarding = true;
+    }
+}
+
+impl Default for SlipCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for SlipCodec {
+    type Item = BytesMut;
+    type Error = SlipCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, SlipCodecError> {
+        while src.has_remaining() {
+            let byte = src.get_u8();
+
+            if self.is_discarding {
+                if byte == END {
+                    self.is_discarding = false;
+                }
+                continue;
+            }
+
+            let byte = if self.escaped {
+                self.escaped = false;
+                match byte {
+                    ESC_END => END,
+                    ESC_ESC => ESC,
+                    // An `END` right after the `ESC` already ends the broken frame.
+                    END => {
+                        self.frame.clear();
+                        return Err(SlipCodecError::InvalidEscape(byte));
+                    }
+                    _ => {
+                        self.discard();
+                        return Err(SlipCodecError::InvalidEscape(byte));
+                    }
+                }
+            } else {
+                match byte {
+                    END if self.frame.is_empty() => continue,
+                    END => return Ok(Some(self.frame.split())),
+                    ESC => {
+                        self.escaped = true;
+                        continue;
+                    }
+                    _ => byte,
+                }
+            };
+
+            if self.frame.len() >= self.max_frame_length {
+                self.discard();
+                return Err(SlipCodecError::FrameTooLong);
+            }
+            self.frame.put_u8(byte);
+        }
+
+        Ok(None)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, SlipCodecError> {
+        let frame = self.decode(src)?;
+        if frame.is_none() {
+            // A frame that never got its `END` is incomplete.
+            self.frame.clear();
+            self.escaped = false;
+            self.is_discarding = false;
+        }
+        Ok(frame)
+    }
+}
+
+impl<T> Encoder<T> for SlipCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = SlipCodecError;
+
+    fn encode(&mut self, frame: T, dst: &mut BytesMut) -> Result<(), SlipCodecError> {
+        let frame = frame.as_ref();
+        dst.reserve(frame.len() + 2);
+        if self.leading_end {
+            dst.put_u8(END);
+        }
+        for &byte in frame {
+            match byte {
+                END => dst.put_slice(&[ESC, ESC_END]),
+                ESC => dst.put_slice(&[ESC, ESC_ESC]),
+                _ => dst.put_u8(byte),
+            }
+        }
+        dst.put_u8(END);
+        Ok(())
+    }
+}
+
+/// An error from a [`SlipCodec`].
+#[derive(Debug)]
+pub enum SlipCodecError {
+    /// A frame was longer than the maximum frame length.  It is skipped.
+    FrameTooLong,
+    /// An `ESC` byte was followed by the given byte rather than `ESC_END` or `ESC_ESC`.
+    /// The frame is skipped.
+    InvalidEscape(u8),
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for SlipCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlipCodecError::FrameTooLong => write!(f, "max frame length exceeded"),
+            SlipCodecError::InvalidEscape(byte) => {
+                write!(f, "invalid escape sequence 0xDB 0x{:02X}", byte)
+            }
+            SlipCodecError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SlipCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SlipCodecError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SlipCodecError {
+    fn from(err: io::Error) -> Self {
+        SlipCodecError::Io(err)
+    }
+}
@@ -17,16 +17,60 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 #[cfg(unix)]
 use std::convert::TryFrom;
+use std::future::Future;
 use std::io::{Read, Result as IoResult, Write};
 use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::task::{Context, Poll};
 use std::time::Duration;
+use tokio::time::Sleep;
 
 #[cfg(feature = "codec")]
 pub mod frame;
 
+#[cfg(feature = "modbus")]
+pub mod modbus;
+
+#[cfg(feature = "nmea")]
+pub mod nmea;
+
+#[cfg(feature = "ubx")]
+pub mod ubx;
+
+#[cfg(feature = "at")]
+pub mod at;
+
+#[cfg(feature = "cmux")]
+pub mod cmux;
+
+#[cfg(feature = "xmodem")]
+pub mod xmodem;
+
+#[cfg(feature = "ymodem")]
+pub mod ymodem;
+
+#[cfg(feature = "zmodem")]
+pub mod zmodem;
+
+#[cfg(feature = "stm32boot")]
+pub mod stm32boot;
+
+#[cfg(any(feature = "codec", feature = "xmodem"))]
+#[cfg_attr(not(feature = "codec"), allow(dead_code))]
+mod crc;
+
+mod modem;
+pub use crate::modem::{ModemEvents, ModemLine, ModemStatusChange};
+
+mod timeout;
+use crate::timeout::{ReadTimer, Timeouts};
+
+mod split;
+pub use crate::split::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, ReuniteError, WriteHalf};
+
 #[cfg(unix)]
 mod os_prelude {
+    pub use std::os::unix::io::{AsRawFd, RawFd};
     pub use std::task::ready;
     pub use tokio::io::unix::AsyncFd;
 }
@@ -34,8 +78,8 @@ mod os_prelude {
 #[cfg(windows)]
 mod os_prelude {
     pub use std::mem;
-    pub use std::ops::{Deref, DerefMut};
     pub use std::os::windows::prelude::*;
+    pub use std::task::ready;
     pub use tokio::net::windows::named_pipe;
 }
 
@@ -50,13 +94,17 @@ pub type Result<T> = mio_serial::Result<T>;
 /// convenience methods found on the [`tokio::io::AsyncReadExt`] and [`tokio::io::AsyncWriteExt`]
 /// traits.
 ///
+/// A `SerialStream` can be split into a read half and a write half with
+/// [`split`](SerialStream::split) or [`into_split`](SerialStream::into_split) so that
+/// reading and writing can happen from separate tasks.
+///
 /// [`AsyncReadExt`]: trait@tokio::io::AsyncReadExt
 /// [`AsyncWriteExt`]: trait@tokio::io::AsyncWriteExt
 ///
 #[derive(Debug)]
 pub struct SerialStream {
     #[cfg(unix)]
-    inner: AsyncFd<mio_serial::SerialStream>,
+    inner: AsyncFd<Port>,
     // Named pipes and COM ports are actually two entirely different things that hardly have anything in common.
     // The only thing they share is the opaque `HANDLE` type that can be fed into `CreateFileW`, `ReadFile`, `WriteFile`, etc.
     //
@@ -66,7 +114,89 @@ pub struct SerialStream {
     inner: named_pipe::NamedPipeClient,
     // The com port is kept around for serialport related methods
     #[cfg(windows)]
-    com: mem::ManuallyDrop<mio_serial::SerialStream>,
+    com: mem::ManuallyDrop<Mutex<mio_serial::SerialStream>>,
+    drain_on_shutdown: bool,
+    drain: Drain,
+    timeouts: Mutex<Timeouts>,
+    read_timer: ReadTimer,
+}
+
+/// Shortest and longest delay between two checks of the transmit queue while draining.
+const DRAIN_MIN_BACKOFF: Duration = Duration::from_millis(1);
+const DRAIN_MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+/// State for waiting on the transmit queue to empty.
+///
+/// The queue is polled with `bytes_to_write` using an exponential backoff, so that
+/// waiting never blocks the reactor the way `tcdrain` would.
+#[derive(Debug, Default)]
+pub(crate) struct Drain {
+    delay: Option<Pin<Box<Sleep>>>,
+    backoff: Duration,
+}
+
+impl Drain {
+    pub(crate) fn poll_drain(
+        &mut self,
+        port: &SerialStream,
+        cx: &mut Context<'_>,
+    ) -> Poll<IoResult<()>> {
+        loop {
+            if let Some(delay) = self.delay.as_mut() {
+                ready!(delay.as_mut().poll(cx));
+                self.delay = None;
+            }
+
+            if port.borrow().bytes_to_write()? == 0 {
+                self.backoff = Duration::from_secs(0);
+                return Poll::Ready(Ok(()));
+            }
+
+            self.backoff = (self.backoff * 2).clamp(DRAIN_MIN_BACKOFF, DRAIN_MAX_BACKOFF);
+            self.delay = Some(Box::pin(tokio::time::sleep(self.backoff)));
+        }
+    }
+}
+
+/// The `mio_serial::SerialStream` registered with the reactor.
+///
+/// The port is kept behind a lock so that settings can be changed through a shared
+/// reference, e.g. from the write half of a split `SerialStream`.  The lock is only
+/// ever held for the duration of a single system call.
+#[cfg(unix)]
+#[derive(Debug)]
+struct Port {
+    fd: RawFd,
+    serial: Mutex<mio_serial::SerialStream>,
+}
+
+#[cfg(unix)]
+impl Port {
+    fn new(serial: mio_serial::SerialStream) -> Self {
+        Self {
+            fd: serial.as_raw_fd(),
+            serial: Mutex::new(serial),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, mio_serial::SerialStream> {
+        lock(&self.serial)
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Port {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// Take a lock, ignoring poisoning.
+///
+/// The locks only guard calls into `mio_serial` and plain settings, which leave no
+/// state behind that a panicking thread could have corrupted.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 impl SerialStream {
@@ -76,19 +206,21 @@ impl SerialStream {
 
         #[cfg(unix)]
         {
-            Ok(Self {
-                inner: AsyncFd::new(port)?,
-            })
+            Self::from_mio(port)
         }
 
         #[cfg(windows)]
         {
             let handle = port.as_raw_handle();
             // Keep the com port around to use for serialport related things
-            let com = mem::ManuallyDrop::new(port);
+            let com = mem::ManuallyDrop::new(Mutex::new(port));
             Ok(Self {
                 inner: unsafe { named_pipe::NamedPipeClient::from_raw_handle(handle)? },
                 com,
+                drain_on_shutdown: false,
+                drain: Drain::default(),
+                timeouts: Mutex::default(),
+                read_timer: ReadTimer::default(),
             })
         }
     }
@@ -116,13 +248,18 @@ impl SerialStream {
     pub fn pair() -> crate::Result<(Self, Self)> {
         let (master, slave) = mio_serial::SerialStream::pair()?;
 
-        let master = SerialStream {
-            inner: AsyncFd::new(master)?,
-        };
-        let slave = SerialStream {
-            inner: AsyncFd::new(slave)?,
-        };
-        Ok((master, slave))
+        Ok((Self::from_mio(master)?, Self::from_mio(slave)?))
+    }
+
+    #[cfg(unix)]
+    fn from_mio(port: mio_serial::SerialStream) -> crate::Result<Self> {
+        Ok(Self {
+            inner: AsyncFd::new(Port::new(port))?,
+            drain_on_shutdown: false,
+            drain: Drain::default(),
+            timeouts: Mutex::default(),
+            read_timer: ReadTimer::default(),
+        })
     }
 
     /// Sets the exclusivity of the port
@@ -137,7 +274,7 @@ impl SerialStream {
     /// * `Io` for any error while setting exclusivity for the port.
     #[cfg(unix)]
     pub fn set_exclusive(&mut self, exclusive: bool) -> crate::Result<()> {
-        self.inner.get_mut().set_exclusive(exclusive)
+        self.borrow().set_exclusive(exclusive)
     }
 
     /// Returns the exclusivity of the port
@@ -146,34 +283,93 @@ impl SerialStream {
     /// will fail.
     #[cfg(unix)]
     pub fn exclusive(&self) -> bool {
-        self.inner.get_ref().exclusive()
+        self.borrow().exclusive()
     }
 
-    /// Borrow a reference to the underlying mio-serial::SerialStream object.
-    #[inline(always)]
-    fn borrow(&self) -> &mio_serial::SerialStream {
-        #[cfg(unix)]
-        {
-            self.inner.get_ref()
-        }
-        #[cfg(windows)]
-        {
-            self.com.deref()
-        }
+    /// Attempts to clone the `SerialStream` by duplicating the underlying file descriptor.
+    ///
+    /// The clone is registered with the reactor on its own, so it can read the modem
+    /// lines or change settings while the original keeps doing I/O.  The settings
+    /// themselves belong to the port, so changes made through either handle are seen
+    /// by both.
+    ///
+    /// This is the same as `SerialPort::try_clone()` but returns the concrete type instead.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the file descriptor could not be duplicated or registered.
+    #[cfg(unix)]
+    pub fn try_clone_native(&self) -> crate::Result<Self> {
+        use std::os::unix::io::{BorrowedFd, FromRawFd, IntoRawFd};
+
+        let exclusive = self.exclusive();
+        // Safety: the descriptor stays open for as long as `self` is alive.
+        let fd = unsafe { BorrowedFd::borrow_raw(self.inner.get_ref().fd) }.try_clone_to_owned()?;
+        let mut port = unsafe { mio_serial::SerialStream::from_raw_fd(fd.into_raw_fd()) };
+        // Taking ownership of a raw descriptor locks the port, so put back whatever
+        // exclusivity the original handle had.
+        port.set_exclusive(exclusive)?;
+
+        let mut clone = Self::from_mio(port)?;
+        clone.drain_on_shutdown = self.drain_on_shutdown;
+        clone.timeouts = Mutex::new(self.timeouts());
+        Ok(clone)
     }
 
-    /// Borrow a mutable reference to the underlying mio-serial::SerialStream object.
+    /// Returns a stream of changes on the CTS, DSR, DCD and RI modem status lines.
+    ///
+    /// On Linux the changes are waited for with the `TIOCMIWAIT` ioctl on a helper
+    /// thread.  Where that is not supported, e.g. on the pseudo terminals created by
+    /// [`pair`](SerialStream::pair) or on other platforms, the lines are polled instead.
+    /// See [`ModemEvents`] for details.
+    ///
+    /// The helper thread is stopped with the `SIGRTMAX` signal.  The first call installs
+    /// a process-wide handler for it that does nothing, unless the signal already has a
+    /// handler, in which case that is kept and the lines are polled instead.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the handler for `SIGRTMAX` could not be installed, or the helper thread
+    ///   could not be started.
+    pub fn modem_events(&self) -> crate::Result<ModemEvents<&SerialStream>> {
+        ModemEvents::watch(self)
+    }
+
+    /// Splits a `SerialStream` into a read half and a write half, which can be used
+    /// to read and write the port concurrently.
+    ///
+    /// This method is more efficient than [`into_split`](SerialStream::into_split),
+    /// but the halves cannot be moved into independently spawned tasks.
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        split::split(self)
+    }
+
+    /// Splits a `SerialStream` into a read half and a write half, which can be used
+    /// to read and write the port concurrently.
+    ///
+    /// Unlike [`split`](SerialStream::split), the owned halves can be moved to
+    /// separate tasks.  Both halves share the same registration with the reactor,
+    /// and can be put back together with [`OwnedReadHalf::reunite`].
+    ///
+    /// **Note:** Dropping the write half does not close the port.  The port is
+    /// closed once both halves have been dropped.
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        split::split_owned(self)
+    }
+
+    /// Lock the underlying mio-serial::SerialStream object.
     #[inline(always)]
-    fn borrow_mut(&mut self) -> &mut mio_serial::SerialStream {
+    fn borrow(&self) -> MutexGuard<'_, mio_serial::SerialStream> {
         #[cfg(unix)]
         {
-            self.inner.get_mut()
+            self.inner.get_ref().lock()
         }
         #[cfg(windows)]
         {
-            self.com.deref_mut()
+            lock(&self.com)
         }
     }
+
     /// Try to read bytes on the serial port.  On success returns the number of bytes read.
     ///
     /// The function must be called with valid byte array `buf` of sufficient
@@ -182,10 +378,10 @@ impl SerialStream {
     ///
     /// When there is no pending data, `Err(io::ErrorKind::WouldBlock)` is
     /// returned. This function is usually paired with `readable()`.
-    pub fn try_read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+    pub fn try_read(&self, buf: &mut [u8]) -> IoResult<usize> {
         #[cfg(unix)]
         {
-            self.inner.get_mut().read(buf)
+            (&*self.borrow()).read(buf)
         }
         #[cfg(windows)]
         {
@@ -209,10 +405,10 @@ impl SerialStream {
     ///
     /// When the write would block, `Err(io::ErrorKind::WouldBlock)` is
     /// returned. This function is usually paired with `writable()`.
-    pub fn try_write(&mut self, buf: &[u8]) -> IoResult<usize> {
+    pub fn try_write(&self, buf: &[u8]) -> IoResult<usize> {
         #[cfg(unix)]
         {
-            self.inner.get_mut().write(buf)
+            (&*self.borrow()).write(buf)
         }
         #[cfg(windows)]
         {
@@ -231,36 +427,119 @@ impl SerialStream {
         let _ = self.inner.writable().await?;
         Ok(())
     }
-}
 
-#[cfg(unix)]
-impl AsyncRead for SerialStream {
-    /// Attempts to ready bytes on the serial port.
+    /// Returns the time it takes to transmit one character with the current settings.
     ///
-    /// Note that on multiple calls to a `poll_*` method in the read direction, only the
-    /// `Waker` from the `Context` passed to the most recent call will be scheduled to
-    /// receive a wakeup.
+    /// A character is made up of a start bit, the data bits, an optional parity bit and
+    /// the stop bits, sent at the current baud rate.  Protocols that delimit frames by
+    /// silence on the line, such as Modbus RTU, express their gaps in character times.
     ///
-    /// # Return value
+    /// ## Errors
     ///
-    /// The function returns:
+    /// * `Io` if the settings could not be read from the port.
+    /// * `InvalidInput` if the baud rate is zero.
+    pub fn character_time(&self) -> crate::Result<Duration> {
+        let port = self.borrow();
+        let baud_rate = port.baud_rate()?;
+        if baud_rate == 0 {
+            return Err(crate::Error::new(
+                crate::ErrorKind::InvalidInput,
+                "baud rate is zero",
+            ));
+        }
+        let data_bits = match port.data_bits()? {
+            crate::DataBits::Five => 5,
+            crate::DataBits::Six => 6,
+            crate::DataBits::Seven => 7,
+            crate::DataBits::Eight => 8,
+        };
+        let parity_bits = match port.parity()? {
+            crate::Parity::None => 0,
+            crate::Parity::Odd | crate::Parity::Even => 1,
+        };
+        let stop_bits = match port.stop_bits()? {
+            crate::StopBits::One => 1,
+            crate::StopBits::Two => 2,
+        };
+        let bits: u64 = 1 + data_bits + parity_bits + stop_bits;
+        Ok(Duration::from_nanos(
+            bits * 1_000_000_000 / u64::from(baud_rate),
+        ))
+    }
+
+    /// Sets the inter-byte timeout used when reading.
     ///
-    /// * `Poll::Pending` if the socket is not ready to read
-    /// * `Poll::Ready(Ok(()))` reads data `ReadBuf` if the socket is ready
-    /// * `Poll::Ready(Err(e))` if an error is encountered.
+    /// When set, a read does not complete as soon as the first bytes arrive.  Instead
+    /// it keeps collecting bytes until the buffer is full or the line has been idle
+    /// for `timeout`, similar to `VTIME` on a terminal in non-canonical mode.  `None`,
+    /// the default, returns whatever is available as soon as anything arrives.
     ///
-    /// # Errors
+    /// The time to wait for the first byte is set with [`SerialPort::set_timeout`].
+    /// Like it, this can be changed through a shared reference, including from a split
+    /// half.
+    pub fn set_inter_byte_timeout(&self, timeout: Option<Duration>) {
+        lock(&self.timeouts).inter_byte = timeout;
+    }
+
+    /// Returns the inter-byte timeout used when reading.
+    pub fn inter_byte_timeout(&self) -> Option<Duration> {
+        self.timeouts().inter_byte
+    }
+
+    pub(crate) fn timeouts(&self) -> Timeouts {
+        *lock(&self.timeouts)
+    }
+
+    /// Wait until all data written to the port has been transmitted.
     ///
-    /// This function may encounter any standard I/O error except `WouldBlock`.
-    fn poll_read(
-        self: Pin<&mut Self>,
+    /// This has the semantics of `tcdrain`, but instead of blocking the thread it
+    /// polls the number of bytes still queued for transmission, backing off between
+    /// checks.  Note that the queue reported by the driver does not include bytes that
+    /// already sit in the hardware FIFO of the UART.
+    ///
+    /// Writes to the port go straight to the driver, and
+    /// [`flush`](tokio::io::AsyncWriteExt::flush) waits in the same way.  Use either
+    /// before toggling RTS or closing the port.
+    pub async fn drain(&self) -> IoResult<()> {
+        let mut drain = Drain::default();
+        std::future::poll_fn(|cx| drain.poll_drain(self, cx)).await
+    }
+
+    /// Sets whether shutting down the port waits for the transmit queue to empty.
+    ///
+    /// When enabled, [`shutdown`](tokio::io::AsyncWriteExt::shutdown) waits as
+    /// [`drain`](SerialStream::drain) does.  This is disabled by default.
+    pub fn set_drain_on_shutdown(&mut self, drain: bool) {
+        self.drain_on_shutdown = drain;
+    }
+
+    /// Returns whether shutting down the port waits for the transmit queue to empty.
+    pub fn drain_on_shutdown(&self) -> bool {
+        self.drain_on_shutdown
+    }
+
+    pub(crate) fn poll_shutdown_priv(
+        &self,
+        cx: &mut Context<'_>,
+        drain: &mut Drain,
+    ) -> Poll<IoResult<()>> {
+        if self.drain_on_shutdown {
+            drain.poll_drain(self, cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn poll_read_priv(
+        &self,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<IoResult<()>> {
         loop {
             let mut guard = ready!(self.inner.poll_read_ready(cx))?;
 
-            match guard.try_io(|inner| inner.get_ref().read(buf.initialize_unfilled())) {
+            match guard.try_io(|inner| (&*inner.get_ref().lock()).read(buf.initialize_unfilled())) {
                 Ok(Ok(bytes_read)) => {
                     buf.advance(bytes_read);
                     return Poll::Ready(Ok(()));
@@ -272,231 +551,322 @@ impl AsyncRead for SerialStream {
             }
         }
     }
-}
 
-#[cfg(unix)]
-impl AsyncWrite for SerialStream {
-    /// Attempts to send data on the serial port
-    ///
-    /// Note that on multiple calls to a `poll_*` method in the send direction,
-    /// only the `Waker` from the `Context` passed to the most recent call will
-    /// be scheduled to receive a wakeup.
-    ///
-    /// # Return value
-    ///
-    /// The function returns:
-    ///
-    /// * `Poll::Pending` if the socket is not available to write
-    /// * `Poll::Ready(Ok(n))` `n` is the number of bytes sent
-    /// * `Poll::Ready(Err(e))` if an error is encountered.
-    ///
-    /// # Errors
-    ///
-    /// This function may encounter any standard I/O error except `WouldBlock`.
-    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+    #[cfg(unix)]
+    pub(crate) fn poll_write_priv(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
         loop {
             let mut guard = ready!(self.inner.poll_write_ready(cx))?;
 
-            match guard.try_io(|inner| inner.get_ref().write(buf)) {
+            match guard.try_io(|inner| (&*inner.get_ref().lock()).write(buf)) {
                 Ok(result) => return Poll::Ready(result),
                 Err(_would_block) => continue,
             }
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+    #[cfg(windows)]
+    pub(crate) fn poll_read_priv(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
         loop {
-            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
-            match guard.try_io(|inner| inner.get_ref().flush()) {
-                Ok(_) => return Poll::Ready(Ok(())),
-                Err(_would_block) => continue,
+            ready!(self.inner.poll_read_ready(cx))?;
+
+            match self.inner.try_read(buf.initialize_unfilled()) {
+                Ok(bytes_read) => {
+                    buf.advance(bytes_read);
+                    return Poll::Ready(Ok(()));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Poll::Ready(Err(err)),
             }
         }
     }
 
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        let _ = self.poll_flush(cx)?;
-        Ok(()).into()
+    #[cfg(windows)]
+    pub(crate) fn poll_write_priv(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        loop {
+            ready!(self.inner.poll_write_ready(cx))?;
+
+            match self.inner.try_write(buf) {
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => continue,
+                result => return Poll::Ready(result),
+            }
+        }
     }
 }
 
-#[cfg(windows)]
 impl AsyncRead for SerialStream {
+    /// Attempts to ready bytes on the serial port.
+    ///
+    /// Note that on multiple calls to a `poll_*` method in the read direction, only the
+    /// `Waker` from the `Context` passed to the most recent call will be scheduled to
+    /// receive a wakeup.
+    ///
+    /// # Return value
+    ///
+    /// The function returns:
+    ///
+    /// * `Poll::Pending` if the socket is not ready to read
+    /// * `Poll::Ready(Ok(()))` reads data `ReadBuf` if the socket is ready
+    /// * `Poll::Ready(Err(e))` if an error is encountered.
+    ///
+    /// # Errors
+    ///
+    /// This function may encounter any standard I/O error except `WouldBlock`.
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_read(cx, buf)
+        let this = self.get_mut();
+        let mut read_timer = std::mem::take(&mut this.read_timer);
+        let result = read_timer.poll_read(this, cx, buf);
+        this.read_timer = read_timer;
+        result
     }
 }
 
-#[cfg(windows)]
 impl AsyncWrite for SerialStream {
+    /// Attempts to send data on the serial port
+    ///
+    /// Note that on multiple calls to a `poll_*` method in the send direction,
+    /// only the `Waker` from the `Context` passed to the most recent call will
+    /// be scheduled to receive a wakeup.
+    ///
+    /// # Return value
+    ///
+    /// The function returns:
+    ///
+    /// * `Poll::Pending` if the socket is not available to write
+    /// * `Poll::Ready(Ok(n))` `n` is the number of bytes sent
+    /// * `Poll::Ready(Err(e))` if an error is encountered.
+    ///
+    /// # Errors
+    ///
+    /// This function may encounter any standard I/O error except `WouldBlock`.
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_write(cx, buf)
+        self.poll_write_priv(cx, buf)
     }
 
+    /// Waits for the data written to leave the port, as [`SerialStream::drain`] does.
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_flush(cx)
+        let this = self.get_mut();
+        let mut drain = std::mem::take(&mut this.drain);
+        let result = drain.poll_drain(this, cx);
+        this.drain = drain;
+        result
     }
 
+    /// Shuts down the port, waiting for the transmit queue to empty first if
+    /// [`set_drain_on_shutdown`](SerialStream::set_drain_on_shutdown) is enabled.
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_shutdown(cx)
+        let this = self.get_mut();
+        let mut drain = std::mem::take(&mut this.drain);
+        let result = this.poll_shutdown_priv(cx, &mut drain);
+        this.drain = drain;
+        result
     }
 }
 
-impl crate::SerialPort for SerialStream {
-    #[inline(always)]
-    fn name(&self) -> Option<String> {
-        self.borrow().name()
-    }
+// `SerialPort` is implemented for both `SerialStream` and `&SerialStream` so that the
+// port settings can be reached through shared references, in the same way that
+// `std::io::Read` is implemented for `&std::fs::File`.
+macro_rules! impl_serial_port {
+    ($ty:ty) => {
+        impl crate::SerialPort for $ty {
+            #[inline(always)]
+            fn name(&self) -> Option<String> {
+                self.borrow().name()
+            }
 
-    #[inline(always)]
-    fn baud_rate(&self) -> crate::Result<u32> {
-        self.borrow().baud_rate()
-    }
+            #[inline(always)]
+            fn baud_rate(&self) -> crate::Result<u32> {
+                self.borrow().baud_rate()
+            }
 
-    #[inline(always)]
-    fn data_bits(&self) -> crate::Result<crate::DataBits> {
-        self.borrow().data_bits()
-    }
+            #[inline(always)]
+            fn data_bits(&self) -> crate::Result<crate::DataBits> {
+                self.borrow().data_bits()
+            }
 
-    #[inline(always)]
-    fn flow_control(&self) -> crate::Result<crate::FlowControl> {
-        self.borrow().flow_control()
-    }
+            #[inline(always)]
+            fn flow_control(&self) -> crate::Result<crate::FlowControl> {
+                self.borrow().flow_control()
+            }
 
-    #[inline(always)]
-    fn parity(&self) -> crate::Result<crate::Parity> {
-        self.borrow().parity()
-    }
+            #[inline(always)]
+            fn parity(&self) -> crate::Result<crate::Parity> {
+                self.borrow().parity()
+            }
 
-    #[inline(always)]
-    fn stop_bits(&self) -> crate::Result<crate::StopBits> {
-        self.borrow().stop_bits()
-    }
+            #[inline(always)]
+            fn stop_bits(&self) -> crate::Result<crate::StopBits> {
+                self.borrow().stop_bits()
+            }
 
-    #[inline(always)]
-    fn timeout(&self) -> Duration {
-        Duration::from_secs(0)
-    }
+            /// Returns the time a read waits for the first byte to arrive.
+            ///
+            /// Zero, the default, waits forever.
+            #[inline(always)]
+            fn timeout(&self) -> Duration {
+                self.timeouts().total
+            }
 
-    #[inline(always)]
-    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
-        self.borrow_mut().set_baud_rate(baud_rate)
-    }
+            #[inline(always)]
+            fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+                self.borrow().set_baud_rate(baud_rate)
+            }
 
-    #[inline(always)]
-    fn set_data_bits(&mut self, data_bits: crate::DataBits) -> crate::Result<()> {
-        self.borrow_mut().set_data_bits(data_bits)
-    }
+            #[inline(always)]
+            fn set_data_bits(&mut self, data_bits: crate::DataBits) -> crate::Result<()> {
+                self.borrow().set_data_bits(data_bits)
+            }
 
-    #[inline(always)]
-    fn set_flow_control(&mut self, flow_control: crate::FlowControl) -> crate::Result<()> {
-        self.borrow_mut().set_flow_control(flow_control)
-    }
+            #[inline(always)]
+            fn set_flow_control(&mut self, flow_control: crate::FlowControl) -> crate::Result<()> {
+                self.borrow().set_flow_control(flow_control)
+            }
 
-    #[inline(always)]
-    fn set_parity(&mut self, parity: crate::Parity) -> crate::Result<()> {
-        self.borrow_mut().set_parity(parity)
-    }
+            #[inline(always)]
+            fn set_parity(&mut self, parity: crate::Parity) -> crate::Result<()> {
+                self.borrow().set_parity(parity)
+            }
 
-    #[inline(always)]
-    fn set_stop_bits(&mut self, stop_bits: crate::StopBits) -> crate::Result<()> {
-        self.borrow_mut().set_stop_bits(stop_bits)
-    }
+            #[inline(always)]
+            fn set_stop_bits(&mut self, stop_bits: crate::StopBits) -> crate::Result<()> {
+                self.borrow().set_stop_bits(stop_bits)
+            }
 
-    #[inline(always)]
-    fn set_timeout(&mut self, _: Duration) -> crate::Result<()> {
-        Ok(())
-    }
+            /// Sets the time a read waits for the first byte to arrive.
+            ///
+            /// If no byte arrives in time, the read fails with `io::ErrorKind::TimedOut`.
+            /// A zero timeout, the default, waits forever.
+            #[inline(always)]
+            fn set_timeout(&mut self, timeout: Duration) -> crate::Result<()> {
+                lock(&self.timeouts).total = timeout;
+                Ok(())
+            }
 
-    #[inline(always)]
-    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
-        self.borrow_mut().write_request_to_send(level)
-    }
+            #[inline(always)]
+            fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+                self.borrow().write_request_to_send(level)
+            }
 
-    #[inline(always)]
-    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
-        self.borrow_mut().write_data_terminal_ready(level)
-    }
+            #[inline(always)]
+            fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+                self.borrow().write_data_terminal_ready(level)
+            }
 
-    #[inline(always)]
-    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
-        self.borrow_mut().read_clear_to_send()
-    }
+            #[inline(always)]
+            fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+                self.borrow().read_clear_to_send()
+            }
 
-    #[inline(always)]
-    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
-        self.borrow_mut().read_data_set_ready()
-    }
+            #[inline(always)]
+            fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+                self.borrow().read_data_set_ready()
+            }
 
-    #[inline(always)]
-    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
-        self.borrow_mut().read_ring_indicator()
-    }
+            #[inline(always)]
+            fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+                self.borrow().read_ring_indicator()
+            }
 
-    #[inline(always)]
-    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
-        self.borrow_mut().read_carrier_detect()
-    }
+            #[inline(always)]
+            fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+                self.borrow().read_carrier_detect()
+            }
 
-    #[inline(always)]
-    fn bytes_to_read(&self) -> crate::Result<u32> {
-        self.borrow().bytes_to_read()
-    }
+            #[inline(always)]
+            fn bytes_to_read(&self) -> crate::Result<u32> {
+                self.borrow().bytes_to_read()
+            }
 
-    #[inline(always)]
-    fn bytes_to_write(&self) -> crate::Result<u32> {
-        self.borrow().bytes_to_write()
-    }
+            #[inline(always)]
+            fn bytes_to_write(&self) -> crate::Result<u32> {
+                self.borrow().bytes_to_write()
+            }
 
-    #[inline(always)]
-    fn clear(&self, buffer_to_clear: crate::ClearBuffer) -> crate::Result<()> {
-        self.borrow().clear(buffer_to_clear)
-    }
+            #[inline(always)]
+            fn clear(&self, buffer_to_clear: crate::ClearBuffer) -> crate::Result<()> {
+                self.borrow().clear(buffer_to_clear)
+            }
 
-    /// Cloning SerialStream is not supported.
-    ///
-    /// # Errors
-    /// Always returns `ErrorKind::Other` with a message.
-    #[inline(always)]
-    fn try_clone(&self) -> crate::Result<Box<dyn crate::SerialPort>> {
-        Err(crate::Error::new(
-            crate::ErrorKind::Io(std::io::ErrorKind::Other),
-            "Cannot clone Tokio handles",
-        ))
+            /// Clones the port, see [`SerialStream::try_clone_native`].
+            ///
+            /// # Errors
+            /// Cloning is not supported on Windows and always returns `ErrorKind::Other`
+            /// with a message there.
+            #[inline(always)]
+            fn try_clone(&self) -> crate::Result<Box<dyn crate::SerialPort>> {
+                #[cfg(unix)]
+                {
+                    Ok(Box::new(self.try_clone_native()?))
+                }
+                #[cfg(windows)]
+                {
+                    Err(crate::Error::new(
+                        crate::ErrorKind::Io(std::io::ErrorKind::Other),
+                        "Cannot clone Tokio handles",
+                    ))
+                }
+            }
+
+            #[inline(always)]
+            fn set_break(&self) -> crate::Result<()> {
+                self.borrow().set_break()
+            }
+
+            #[inline(always)]
+            fn clear_break(&self) -> crate::Result<()> {
+                self.borrow().clear_break()
+            }
+        }
+    };
+}
+
+impl_serial_port!(SerialStream);
+impl_serial_port!(&SerialStream);
+
+impl Read for SerialStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.try_read(buf)
     }
+}
 
-    #[inline(always)]
-    fn set_break(&self) -> crate::Result<()> {
-        self.borrow().set_break()
+impl Write for SerialStream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.try_write(buf)
     }
 
-    #[inline(always)]
-    fn clear_break(&self) -> crate::Result<()> {
-        self.borrow().clear_break()
+    fn flush(&mut self) -> IoResult<()> {
+        self.borrow().flush()
     }
 }
 
-impl Read for SerialStream {
+impl Read for &SerialStream {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
         self.try_read(buf)
     }
 }
 
-impl Write for SerialStream {
+impl Write for &SerialStream {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
         self.try_write(buf)
     }
 
     fn flush(&mut self) -> IoResult<()> {
-        self.borrow_mut().flush()
+        self.borrow().flush()
     }
 }
 
@@ -506,9 +876,7 @@ impl TryFrom<serialport::TTYPort> for SerialStream {
 
     fn try_from(value: serialport::TTYPort) -> std::result::Result<Self, Self::Error> {
         let port = mio_serial::SerialStream::try_from(value)?;
-        Ok(Self {
-            inner: AsyncFd::new(port)?,
-        })
+        Self::from_mio(port)
     }
 }
 
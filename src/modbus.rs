@@ -0,0 +1,169 @@
+//! Modbus over a serial line.
+//!
+//! * [`Client`] is a Modbus master, which sends [`Request`]s to the devices on the bus
+//!   and waits for their [`Response`]s, over either [`Transport`].
+//! * [`RtuServer`] is a Modbus RTU slave, which answers requests from the tables of a
+//!   [`DataStore`], such as a [`MemoryStore`].
+//!
+//! Requests and responses are kept apart from how they are framed on the line.  In RTU
+//! mode a frame is the unit id of the server, the PDU and a CRC-16/MODBUS, sent least
+//! significant byte first, and frames are separated by at least 3.5 character times of
+//! silence.  In ASCII mode, as framed by
+//! [`ModbusAsciiCodec`](crate::frame::ModbusAsciiCodec), the unit id and PDU are sent in
+//! hexadecimal between a `:` and a CR LF, checked by an LRC.
+//!
+//! This module is only available with the `modbus` feature, which enables `codec` as
+//! well.
+use std::{fmt, io};
+
+mod client;
+mod pdu;
+mod rtu;
+mod server;
+
+pub use self::client::{Client, Counters, Transport};
+pub use self::pdu::{Request, Response};
+pub use self::server::{DataStore, MemoryStore, RtuServer};
+
+/// The unit id that addresses every server on the bus.  Servers carry out write
+/// requests sent to it, but don't answer them.
+pub const BROADCAST: u8 = 0;
+
+/// The reason given by a server for refusing a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionCode {
+    /// The function code is not supported by the server.
+    IllegalFunction,
+    /// An address in the request is not available on the server.
+    IllegalDataAddress,
+    /// A value in the request is not acceptable to the server.
+    IllegalDataValue,
+    /// An unrecoverable error occurred while the server was carrying out the request.
+    ServerDeviceFailure,
+    /// The request was accepted, but will take a long time to carry out.
+    Acknowledge,
+    /// The server is busy with a long-running request.
+    ServerDeviceBusy,
+    /// The server found a parity error in its extended memory.
+    MemoryParityError,
+    /// A gateway could not set up a path to the target device.
+    GatewayPathUnavailable,
+    /// The target device behind a gateway did not respond.
+    GatewayTargetDeviceFailedToRespond,
+    /// An exception code not defined by the specification.
+    Other(u8),
+}
+
+impl From<u8> for ExceptionCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0x01 => ExceptionCode::IllegalFunction,
+            0x02 => ExceptionCode::IllegalDataAddress,
+            0x03 => ExceptionCode::IllegalDataValue,
+            0x04 => ExceptionCode::ServerDeviceFailure,
+            0x05 => ExceptionCode::Acknowledge,
+            0x06 => ExceptionCode::ServerDeviceBusy,
+            0x08 => ExceptionCode::MemoryParityError,
+            0x0A => ExceptionCode::GatewayPathUnavailable,
+            0x0B => ExceptionCode::GatewayTargetDeviceFailedToRespond,
+            code => ExceptionCode::Other(code),
+        }
+    }
+}
+
+impl From<ExceptionCode> for u8 {
+    fn from(code: ExceptionCode) -> Self {
+        match code {
+            ExceptionCode::IllegalFunction => 0x01,
+            ExceptionCode::IllegalDataAddress => 0x02,
+            ExceptionCode::IllegalDataValue => 0x03,
+            ExceptionCode::ServerDeviceFailure => 0x04,
+            ExceptionCode::Acknowledge => 0x05,
+            ExceptionCode::ServerDeviceBusy => 0x06,
+            ExceptionCode::MemoryParityError => 0x08,
+            ExceptionCode::GatewayPathUnavailable => 0x0A,
+            ExceptionCode::GatewayTargetDeviceFailedToRespond => 0x0B,
+            ExceptionCode::Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExceptionCode::IllegalFunction => write!(f, "illegal function"),
+            ExceptionCode::IllegalDataAddress => write!(f, "illegal data address"),
+            ExceptionCode::IllegalDataValue => write!(f, "illegal data value"),
+            ExceptionCode::ServerDeviceFailure => write!(f, "server device failure"),
+            ExceptionCode::Acknowledge => write!(f, "acknowledge"),
+            ExceptionCode::ServerDeviceBusy => write!(f, "server device busy"),
+            ExceptionCode::MemoryParityError => write!(f, "memory parity error"),
+            ExceptionCode::GatewayPathUnavailable => write!(f, "gateway path unavailable"),
+            ExceptionCode::GatewayTargetDeviceFailedToRespond => {
+                write!(f, "gateway target device failed to respond")
+            }
+            ExceptionCode::Other(code) => write!(f, "exception code {:#04x}", code),
+        }
+    }
+}
+
+/// An error from a Modbus transaction.
+#[derive(Debug)]
+pub enum ModbusError {
+    /// The server answered with an exception response.
+    Exception {
+        /// The function code of the response, which is that of the request with its
+        /// top bit set.
+        function: u8,
+        /// The reason the server gave.
+        code: ExceptionCode,
+    },
+    /// No complete response arrived in time.
+    Timeout,
+    /// An RTU response failed its CRC.
+    InvalidCrc,
+    /// An ASCII response failed its LRC.
+    InvalidLrc,
+    /// A response was well framed but made no sense as an answer to the request.
+    InvalidResponse(&'static str),
+    /// A request could not be sent, because it breaks the limits of the protocol.
+    InvalidRequest(&'static str),
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModbusError::Exception { function, code } => {
+                write!(
+                    f,
+                    "exception response to function {:#04x}: {}",
+                    function & 0x7F,
+                    code
+                )
+            }
+            ModbusError::Timeout => write!(f, "response timed out"),
+            ModbusError::InvalidCrc => write!(f, "response CRC mismatch"),
+            ModbusError::InvalidLrc => write!(f, "response LRC mismatch"),
+            ModbusError::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            ModbusError::InvalidRequest(msg) => write!(f, "invalid request: {}", msg),
+            ModbusError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ModbusError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModbusError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModbusError {
+    fn from(err: io::Error) -> Self {
+        ModbusError::Io(err)
+    }
+}
@@ -0,0 +1,510 @@
+//! A Modbus master, speaking RTU or ASCII.
+use super::pdu::{self, Request, Response};
+use super::rtu::{self, MAX_ADU_LENGTH};
+use super::{ModbusError, BROADCAST};
+use crate::frame::{ModbusAsciiCodec, ModbusAsciiCodecError};
+use crate::SerialStream;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{self, Instant};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::time::Duration;
+
+/// How frames are put on the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// Binary frames checked by a CRC-16 and separated by silence on the line.
+    #[default]
+    Rtu,
+    /// Frames in hexadecimal checked by an LRC, as framed by
+    /// [`ModbusAsciiCodec`](crate::frame::ModbusAsciiCodec).
+    Ascii,
+}
+
+/// Counts of how the transactions of a [`Client`] went.
+///
+/// Every attempt is counted, so a request that is retried twice counts as three
+/// requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Counters {
+    /// Requests sent, including broadcasts.
+    pub requests: u64,
+    /// Requests answered with an exception response.
+    pub exceptions: u64,
+    /// Requests that got no complete response in time.
+    pub timeouts: u64,
+    /// RTU responses that failed their CRC.
+    pub crc_errors: u64,
+    /// ASCII responses that failed their LRC.
+    pub lrc_errors: u64,
+    /// Responses that were well framed but made no sense as an answer to the request.
+    pub invalid_responses: u64,
+}
+
+/// A Modbus client, also known as the master of the bus.
+///
+/// The client owns the port and carries out one transaction at a time: it waits for the
+/// line to have been silent for the inter-frame silence, sends the request, and reads
+/// the response until it is complete or the response timeout runs out.  A response
+/// that times out, fails its CRC or LRC, or doesn't match the request is retried up to
+/// the configured number of times, after waiting for the line to go quiet again.  An
+/// exception response is not retried, and is returned as
+/// [`ModbusError::Exception`].  How each attempt went is tallied in the
+/// [`counters`](Client::counters).
+///
+/// ```no_run
+/// use tokio_serial::modbus::{Client, Transport};
+/// use tokio_serial::SerialStream;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 19200))?;
+/// let mut client = Client::new(port)?
+///     .with_transport(Transport::Rtu)
+///     .with_timeout(Duration::from_millis(500))
+///     .with_retries(2);
+/// let registers = client.read_holding_registers(1, 0x0000, 4).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Client {
+    port: SerialStream,
+    transport: Transport,
+    silence: Duration,
+    timeout: Duration,
+    retries: usize,
+    counters: Counters,
+    // When the line last went quiet, as far as we know.
+    idle_since: Instant,
+}
+
+impl Client {
+    /// Create an RTU client on `port`, with a response timeout of one second and no
+    /// retries.
+    ///
+    /// The inter-frame silence is 3.5 character times at the port's current baud rate,
+    /// data bits, parity and stop bits, or 1.75 ms above 19200 baud as the
+    /// specification recommends.  If the settings of the port are changed later, the
+    /// silence must be updated with [`set_silence`](Client::set_silence).
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the settings could not be read from the port.
+    /// * `InvalidInput` if the baud rate is zero.
+    pub fn new(port: SerialStream) -> crate::Result<Client> {
+        let silence = rtu::silence(&port)?;
+        Ok(Self {
+            port,
+            transport: Transport::default(),
+            silence,
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            counters: Counters::default(),
+            idle_since: Instant::now(),
+        })
+    }
+
+    /// Sets how frames are put on the line.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets how long to wait for a response, counted from the end of the request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a request is sent again after it failed.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Returns how frames are put on the line.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns how long to wait for a response.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns how many times a request is sent again after it failed.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Returns the inter-frame silence.
+    pub fn silence(&self) -> Duration {
+        self.silence
+    }
+
+    /// Sets the inter-frame silence.
+    pub fn set_silence(&mut self, silence: Duration) {
+        self.silence = silence;
+    }
+
+    /// Returns the counts of how the transactions went so far.
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Sets all the counters back to zero.
+    pub fn reset_counters(&mut self) {
+        self.counters = Counters::default();
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the client, returning the underlying port.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Sends `request` to the server `unit` and returns its response.
+    ///
+    /// `unit` must be between 1 and 247; use [`broadcast`](Client::broadcast) to
+    /// address every server.
+    pub async fn call(&mut self, unit: u8, request: &Request) -> Result<Response, ModbusError> {
+        self.call_with_timeout(unit, request, self.timeout).await
+    }
+
+    /// Sends `request` to the server `unit` and returns its response, waiting `timeout`
+    /// rather than the configured timeout for each attempt.
+    pub async fn call_with_timeout(
+        &mut self,
+        unit: u8,
+        request: &Request,
+        timeout: Duration,
+    ) -> Result<Response, ModbusError> {
+        if unit == BROADCAST || unit > 247 {
+            return Err(ModbusError::InvalidRequest("unit id out of range"));
+        }
+        request.validate()?;
+        let adu = self.encode(unit, request);
+
+        let mut attempt = 0;
+        loop {
+            let err = match self.transact(unit, request, &adu, timeout).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            match err {
+                ModbusError::Exception { .. } => self.counters.exceptions += 1,
+                ModbusError::Timeout => self.counters.timeouts += 1,
+                ModbusError::InvalidCrc => self.counters.crc_errors += 1,
+                ModbusError::InvalidLrc => self.counters.lrc_errors += 1,
+                ModbusError::InvalidResponse(_) => self.counters.invalid_responses += 1,
+                ModbusError::InvalidRequest(_) | ModbusError::Io(_) => {}
+            }
+            if let ModbusError::Exception { .. } | ModbusError::Io(_) = err {
+                return Err(err);
+            }
+            // Whatever was left of the bad response must not be taken for the next one.
+            self.discard().await?;
+            if attempt == self.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            log::debug!("modbus request to unit {} failed ({}), retrying", unit, err);
+        }
+    }
+
+    /// Sends a write `request` to every server on the bus.  No response is expected.
+    pub async fn broadcast(&mut self, request: &Request) -> Result<(), ModbusError> {
+        if !request.is_write() {
+            return Err(ModbusError::InvalidRequest("only writes can be broadcast"));
+        }
+        request.validate()?;
+        let adu = self.encode(BROADCAST, request);
+        self.send(&adu).await
+    }
+
+    /// Reads `count` coils starting at `address` (function code 1).
+    pub async fn read_coils(
+        &mut self,
+        unit: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<bool>, ModbusError> {
+        let request = Request::ReadCoils { address, count };
+        match self.call(unit, &request).await? {
+            Response::ReadCoils(values) => Ok(values),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads `count` discrete inputs starting at `address` (function code 2).
+    pub async fn read_discrete_inputs(
+        &mut self,
+        unit: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<bool>, ModbusError> {
+        let request = Request::ReadDiscreteInputs { address, count };
+        match self.call(unit, &request).await? {
+            Response::ReadDiscreteInputs(values) => Ok(values),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads `count` holding registers starting at `address` (function code 3).
+    pub async fn read_holding_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        let request = Request::ReadHoldingRegisters { address, count };
+        match self.call(unit, &request).await? {
+            Response::ReadHoldingRegisters(values) => Ok(values),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads `count` input registers starting at `address` (function code 4).
+    pub async fn read_input_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        let request = Request::ReadInputRegisters { address, count };
+        match self.call(unit, &request).await? {
+            Response::ReadInputRegisters(values) => Ok(values),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Turns the coil at `address` on or off (function code 5).
+    pub async fn write_single_coil(
+        &mut self,
+        unit: u8,
+        address: u16,
+        value: bool,
+    ) -> Result<(), ModbusError> {
+        let request = Request::WriteSingleCoil { address, value };
+        match self.call(unit, &request).await? {
+            Response::WriteSingleCoil {
+                address: echoed_address,
+                value: echoed_value,
+            } if echoed_address == address && echoed_value == value => Ok(()),
+            _ => Err(ModbusError::InvalidResponse("write not echoed")),
+        }
+    }
+
+    /// Writes `value` to the holding register at `address` (function code 6).
+    pub async fn write_single_register(
+        &mut self,
+        unit: u8,
+        address: u16,
+        value: u16,
+    ) -> Result<(), ModbusError> {
+        let request = Request::WriteSingleRegister { address, value };
+        match self.call(unit, &request).await? {
+            Response::WriteSingleRegister {
+                address: echoed_address,
+                value: echoed_value,
+            } if echoed_address == address && echoed_value == value => Ok(()),
+            _ => Err(ModbusError::InvalidResponse("write not echoed")),
+        }
+    }
+
+    /// Sets the coils starting at `address` to `values` (function code 15).
+    pub async fn write_multiple_coils(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &[bool],
+    ) -> Result<(), ModbusError> {
+        let request = Request::WriteMultipleCoils {
+            address,
+            values: values.to_vec(),
+        };
+        match self.call(unit, &request).await? {
+            Response::WriteMultipleCoils {
+                address: echoed_address,
+                count,
+            } if echoed_address == address && usize::from(count) == values.len() => Ok(()),
+            _ => Err(ModbusError::InvalidResponse("write not echoed")),
+        }
+    }
+
+    /// Writes `values` to the holding registers starting at `address` (function code
+    /// 16).
+    pub async fn write_multiple_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError> {
+        let request = Request::WriteMultipleRegisters {
+            address,
+            values: values.to_vec(),
+        };
+        match self.call(unit, &request).await? {
+            Response::WriteMultipleRegisters {
+                address: echoed_address,
+                count,
+            } if echoed_address == address && usize::from(count) == values.len() => Ok(()),
+            _ => Err(ModbusError::InvalidResponse("write not echoed")),
+        }
+    }
+
+    /// Writes `values` to the holding registers starting at `write_address`, then reads
+    /// `read_count` holding registers starting at `read_address`, in one transaction
+    /// (function code 23).
+    pub async fn read_write_multiple_registers(
+        &mut self,
+        unit: u8,
+        read_address: u16,
+        read_count: u16,
+        write_address: u16,
+        values: &[u16],
+    ) -> Result<Vec<u16>, ModbusError> {
+        let request = Request::ReadWriteMultipleRegisters {
+            read_address,
+            read_count,
+            write_address,
+            values: values.to_vec(),
+        };
+        match self.call(unit, &request).await? {
+            Response::ReadWriteMultipleRegisters(values) => Ok(values),
+            _ => unreachable!(),
+        }
+    }
+
+    /// One attempt at a transaction.
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &Request,
+        adu: &[u8],
+        timeout: Duration,
+    ) -> Result<Response, ModbusError> {
+        self.send(adu).await?;
+        let deadline = Instant::now() + timeout;
+        let receive = async {
+            match self.transport {
+                Transport::Rtu => self.receive_rtu().await,
+                Transport::Ascii => self.receive_ascii().await,
+            }
+        };
+        let response = time::timeout_at(deadline, receive)
+            .await
+            .map_err(|_| ModbusError::Timeout)??;
+        self.idle_since = Instant::now();
+
+        let (&received_unit, pdu) = response.split_first().expect("response is never empty");
+        if received_unit != unit {
+            return Err(ModbusError::InvalidResponse("unexpected unit id"));
+        }
+        Response::decode(request, pdu)
+    }
+
+    /// Waits for the inter-frame silence, then sends a frame and waits for it to leave.
+    async fn send(&mut self, adu: &[u8]) -> Result<(), ModbusError> {
+        time::sleep_until(self.idle_since + self.silence).await;
+        self.counters.requests += 1;
+        self.port.write_all(adu).await?;
+        self.port.drain().await?;
+        self.idle_since = Instant::now();
+        Ok(())
+    }
+
+    /// Reads a whole RTU frame, whose length is worked out from its function code, and
+    /// checks and strips its CRC.
+    async fn receive_rtu(&mut self) -> Result<Vec<u8>, ModbusError> {
+        let mut adu = Vec::with_capacity(MAX_ADU_LENGTH);
+        let mut buf = [0u8; MAX_ADU_LENGTH];
+        loop {
+            // Every response is at least five bytes, so the unit id, the function code
+            // and the byte after it can always be read before knowing the length.
+            let len = match pdu::response_len(adu.get(1..).unwrap_or(&[]))? {
+                Some(pdu_len) => 1 + pdu_len + 2,
+                None => 3,
+            };
+            if adu.len() >= len {
+                break;
+            }
+            let n = self.port.read(&mut buf[..len - adu.len()]).await?;
+            if n == 0 {
+                return Err(ModbusError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            adu.extend_from_slice(&buf[..n]);
+        }
+
+        if !rtu::crc_ok(&adu) {
+            return Err(ModbusError::InvalidCrc);
+        }
+        adu.truncate(adu.len() - 2);
+        Ok(adu)
+    }
+
+    /// Reads a whole ASCII frame, and checks and strips its LRC.
+    async fn receive_ascii(&mut self) -> Result<Vec<u8>, ModbusError> {
+        let mut codec = ModbusAsciiCodec::new().with_max_frame_length(MAX_ADU_LENGTH - 2);
+        let mut buf = BytesMut::with_capacity(2 * MAX_ADU_LENGTH + 3);
+        loop {
+            match codec.decode(&mut buf) {
+                Ok(Some(adu)) => return Ok(adu.to_vec()),
+                Ok(None) => {}
+                Err(ModbusAsciiCodecError::BadLrc) => return Err(ModbusError::InvalidLrc),
+                Err(_) => return Err(ModbusError::InvalidResponse("malformed ASCII frame")),
+            }
+            if self.port.read_buf(&mut buf).await? == 0 {
+                return Err(ModbusError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    /// Reads and drops whatever arrives until the line has been silent for the
+    /// inter-frame silence.
+    async fn discard(&mut self) -> Result<(), ModbusError> {
+        let mut buf = [0u8; MAX_ADU_LENGTH];
+        loop {
+            match time::timeout(self.silence, self.port.read(&mut buf)).await {
+                Ok(Ok(0)) | Err(_) => break,
+                Ok(Ok(_)) => continue,
+                Ok(Err(err)) => return Err(err.into()),
+            }
+        }
+        self.idle_since = Instant::now();
+        Ok(())
+    }
+
+    /// Frames a request for the transport in use.
+    fn encode(&self, unit: u8, request: &Request) -> Vec<u8> {
+        let mut adu = Vec::with_capacity(MAX_ADU_LENGTH);
+        adu.push(unit);
+        request.encode(&mut adu);
+        match self.transport {
+            Transport::Rtu => {
+                rtu::append_crc(&mut adu);
+                adu
+            }
+            Transport::Ascii => {
+                let mut line = BytesMut::new();
+                ModbusAsciiCodec::new()
+                    .encode(&adu, &mut line)
+                    .expect("frame length is not limited");
+                line.to_vec()
+            }
+        }
+    }
+}
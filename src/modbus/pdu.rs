@@ -0,0 +1,537 @@
+//! Modbus protocol data units: the function code and data of requests and responses,
+//! independent of how they are framed on the line.
+use super::{ExceptionCode, ModbusError};
+
+/// The largest number of coils or discrete inputs that can be read at once.
+const MAX_READ_BITS: u16 = 2000;
+/// The largest number of registers that can be read at once.
+const MAX_READ_REGISTERS: u16 = 125;
+/// The largest number of coils that can be written at once.
+const MAX_WRITE_BITS: u16 = 1968;
+/// The largest number of registers that can be written at once.
+const MAX_WRITE_REGISTERS: u16 = 123;
+/// The largest number of registers that can be written by Read/Write Multiple registers.
+const MAX_READ_WRITE_REGISTERS: u16 = 121;
+
+/// How a coil that is on is encoded by Write Single Coil.
+const COIL_ON: u16 = 0xFF00;
+const COIL_OFF: u16 = 0x0000;
+
+/// A request from a client to a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Read Coils (function code 1).
+    ReadCoils {
+        /// The address of the first coil.
+        address: u16,
+        /// The number of coils to read.
+        count: u16,
+    },
+    /// Read Discrete Inputs (function code 2).
+    ReadDiscreteInputs {
+        /// The address of the first input.
+        address: u16,
+        /// The number of inputs to read.
+        count: u16,
+    },
+    /// Read Holding Registers (function code 3).
+    ReadHoldingRegisters {
+        /// The address of the first register.
+        address: u16,
+        /// The number of registers to read.
+        count: u16,
+    },
+    /// Read Input Registers (function code 4).
+    ReadInputRegisters {
+        /// The address of the first register.
+        address: u16,
+        /// The number of registers to read.
+        count: u16,
+    },
+    /// Write Single Coil (function code 5).
+    WriteSingleCoil {
+        /// The address of the coil.
+        address: u16,
+        /// The new state of the coil.
+        value: bool,
+    },
+    /// Write Single Register (function code 6).
+    WriteSingleRegister {
+        /// The address of the register.
+        address: u16,
+        /// The new value of the register.
+        value: u16,
+    },
+    /// Write Multiple Coils (function code 15).
+    WriteMultipleCoils {
+        /// The address of the first coil.
+        address: u16,
+        /// The new states of the coils.
+        values: Vec<bool>,
+    },
+    /// Write Multiple Registers (function code 16).
+    WriteMultipleRegisters {
+        /// The address of the first register.
+        address: u16,
+        /// The new values of the registers.
+        values: Vec<u16>,
+    },
+    /// Read/Write Multiple Registers (function code 23).  The write is done before
+    /// the read.
+    ReadWriteMultipleRegisters {
+        /// The address of the first register to read.
+        read_address: u16,
+        /// The number of registers to read.
+        read_count: u16,
+        /// The address of the first register to write.
+        write_address: u16,
+        /// The new values of the registers to write.
+        values: Vec<u16>,
+    },
+}
+
+/// A normal response from a server to a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// The states of the coils that were read.
+    ReadCoils(Vec<bool>),
+    /// The states of the discrete inputs that were read.
+    ReadDiscreteInputs(Vec<bool>),
+    /// The values of the holding registers that were read.
+    ReadHoldingRegisters(Vec<u16>),
+    /// The values of the input registers that were read.
+    ReadInputRegisters(Vec<u16>),
+    /// The coil that was written.
+    WriteSingleCoil {
+        /// The address of the coil.
+        address: u16,
+        /// The new state of the coil.
+        value: bool,
+    },
+    /// The register that was written.
+    WriteSingleRegister {
+        /// The address of the register.
+        address: u16,
+        /// The new value of the register.
+        value: u16,
+    },
+    /// The coils that were written.
+    WriteMultipleCoils {
+        /// The address of the first coil.
+        address: u16,
+        /// The number of coils written.
+        count: u16,
+    },
+    /// The registers that were written.
+    WriteMultipleRegisters {
+        /// The address of the first register.
+        address: u16,
+        /// The number of registers written.
+        count: u16,
+    },
+    /// The values of the registers that were read after the write.
+    ReadWriteMultipleRegisters(Vec<u16>),
+}
+
+impl Request {
+    /// Returns the function code of the request.
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Request::ReadCoils { .. } => 0x01,
+            Request::ReadDiscreteInputs { .. } => 0x02,
+            Request::ReadHoldingRegisters { .. } => 0x03,
+            Request::ReadInputRegisters { .. } => 0x04,
+            Request::WriteSingleCoil { .. } => 0x05,
+            Request::WriteSingleRegister { .. } => 0x06,
+            Request::WriteMultipleCoils { .. } => 0x0F,
+            Request::WriteMultipleRegisters { .. } => 0x10,
+            Request::ReadWriteMultipleRegisters { .. } => 0x17,
+        }
+    }
+
+    /// Checks that the quantities in the request are within the limits of the protocol.
+    pub fn validate(&self) -> Result<(), ModbusError> {
+        fn check(count: usize, max: u16) -> Result<(), ModbusError> {
+            if count == 0 || count > usize::from(max) {
+                return Err(ModbusError::InvalidRequest("quantity out of range"));
+            }
+            Ok(())
+        }
+
+        match self {
+            Request::ReadCoils { count, .. } | Request::ReadDiscreteInputs { count, .. } => {
+                check(usize::from(*count), MAX_READ_BITS)
+            }
+            Request::ReadHoldingRegisters { count, .. }
+            | Request::ReadInputRegisters { count, .. } => {
+                check(usize::from(*count), MAX_READ_REGISTERS)
+            }
+            Request::WriteSingleCoil { .. } | Request::WriteSingleRegister { .. } => Ok(()),
+            Request::WriteMultipleCoils { values, .. } => check(values.len(), MAX_WRITE_BITS),
+            Request::WriteMultipleRegisters { values, .. } => {
+                check(values.len(), MAX_WRITE_REGISTERS)
+            }
+            Request::ReadWriteMultipleRegisters {
+                read_count, values, ..
+            } => {
+                check(usize::from(*read_count), MAX_READ_REGISTERS)?;
+                check(values.len(), MAX_READ_WRITE_REGISTERS)
+            }
+        }
+    }
+
+    /// Returns whether the request only writes, and so may be sent to the broadcast
+    /// address.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Request::WriteSingleCoil { .. }
+                | Request::WriteSingleRegister { .. }
+                | Request::WriteMultipleCoils { .. }
+                | Request::WriteMultipleRegisters { .. }
+        )
+    }
+
+    /// Appends the PDU of the request to `dst`.
+    pub fn encode(&self, dst: &mut Vec<u8>) {
+        dst.push(self.function_code());
+        match self {
+            Request::ReadCoils { address, count }
+            | Request::ReadDiscreteInputs { address, count }
+            | Request::ReadHoldingRegisters { address, count }
+            | Request::ReadInputRegisters { address, count } => {
+                put_u16(dst, *address);
+                put_u16(dst, *count);
+            }
+            Request::WriteSingleCoil { address, value } => {
+                put_u16(dst, *address);
+                put_u16(dst, if *value { COIL_ON } else { COIL_OFF });
+            }
+            Request::WriteSingleRegister { address, value } => {
+                put_u16(dst, *address);
+                put_u16(dst, *value);
+            }
+            Request::WriteMultipleCoils { address, values } => {
+                put_u16(dst, *address);
+                put_u16(dst, values.len() as u16);
+                dst.push(bit_bytes(values.len()) as u8);
+                put_bits(dst, values);
+            }
+            Request::WriteMultipleRegisters { address, values } => {
+                put_u16(dst, *address);
+                put_u16(dst, values.len() as u16);
+                dst.push((values.len() * 2) as u8);
+                put_registers(dst, values);
+            }
+            Request::ReadWriteMultipleRegisters {
+                read_address,
+                read_count,
+                write_address,
+                values,
+            } => {
+                put_u16(dst, *read_address);
+                put_u16(dst, *read_count);
+                put_u16(dst, *write_address);
+                put_u16(dst, values.len() as u16);
+                dst.push((values.len() * 2) as u8);
+                put_registers(dst, values);
+            }
+        }
+    }
+
+    /// Decodes the PDU of a request.
+    ///
+    /// A function code that is not supported is reported as
+    /// [`ExceptionCode::IllegalFunction`], a malformed request as
+    /// [`ExceptionCode::IllegalDataValue`], ready to be sent back to the client.
+    pub fn decode(pdu: &[u8]) -> Result<Request, ExceptionCode> {
+        let (&function, data) = pdu.split_first().ok_or(ExceptionCode::IllegalDataValue)?;
+        let mut data = Reader(data);
+        let request = match function {
+            0x01..=0x04 => {
+                let address = data.u16()?;
+                let count = data.u16()?;
+                match function {
+                    0x01 => Request::ReadCoils { address, count },
+                    0x02 => Request::ReadDiscreteInputs { address, count },
+                    0x03 => Request::ReadHoldingRegisters { address, count },
+                    _ => Request::ReadInputRegisters { address, count },
+                }
+            }
+            0x05 => {
+                let address = data.u16()?;
+                let value = match data.u16()? {
+                    COIL_ON => true,
+                    COIL_OFF => false,
+                    _ => return Err(ExceptionCode::IllegalDataValue),
+                };
+                Request::WriteSingleCoil { address, value }
+            }
+            0x06 => Request::WriteSingleRegister {
+                address: data.u16()?,
+                value: data.u16()?,
+            },
+            0x0F => {
+                let address = data.u16()?;
+                let count = data.u16()?;
+                let bytes = data.counted()?;
+                if bytes.len() != bit_bytes(usize::from(count)) {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                Request::WriteMultipleCoils {
+                    address,
+                    values: get_bits(bytes, count),
+                }
+            }
+            0x10 => {
+                let address = data.u16()?;
+                let count = data.u16()?;
+                Request::WriteMultipleRegisters {
+                    address,
+                    values: get_registers(data.counted()?, count)?,
+                }
+            }
+            0x17 => {
+                let read_address = data.u16()?;
+                let read_count = data.u16()?;
+                let write_address = data.u16()?;
+                let write_count = data.u16()?;
+                Request::ReadWriteMultipleRegisters {
+                    read_address,
+                    read_count,
+                    write_address,
+                    values: get_registers(data.counted()?, write_count)?,
+                }
+            }
+            _ => return Err(ExceptionCode::IllegalFunction),
+        };
+        data.finish()?;
+        request
+            .validate()
+            .map_err(|_| ExceptionCode::IllegalDataValue)?;
+        Ok(request)
+    }
+}
+
+impl Response {
+    /// Returns the function code of the response.
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Response::ReadCoils(_) => 0x01,
+            Response::ReadDiscreteInputs(_) => 0x02,
+            Response::ReadHoldingRegisters(_) => 0x03,
+            Response::ReadInputRegisters(_) => 0x04,
+            Response::WriteSingleCoil { .. } => 0x05,
+            Response::WriteSingleRegister { .. } => 0x06,
+            Response::WriteMultipleCoils { .. } => 0x0F,
+            Response::WriteMultipleRegisters { .. } => 0x10,
+            Response::ReadWriteMultipleRegisters(_) => 0x17,
+        }
+    }
+
+    /// Appends the PDU of the response to `dst`.
+    pub fn encode(&self, dst: &mut Vec<u8>) {
+        dst.push(self.function_code());
+        match self {
+            Response::ReadCoils(values) | Response::ReadDiscreteInputs(values) => {
+                dst.push(bit_bytes(values.len()) as u8);
+                put_bits(dst, values);
+            }
+            Response::ReadHoldingRegisters(values)
+            | Response::ReadInputRegisters(values)
+            | Response::ReadWriteMultipleRegisters(values) => {
+                dst.push((values.len() * 2) as u8);
+                put_registers(dst, values);
+            }
+            Response::WriteSingleCoil { address, value } => {
+                put_u16(dst, *address);
+                put_u16(dst, if *value { COIL_ON } else { COIL_OFF });
+            }
+            Response::WriteSingleRegister { address, value } => {
+                put_u16(dst, *address);
+                put_u16(dst, *value);
+            }
+            Response::WriteMultipleCoils { address, count } => {
+                put_u16(dst, *address);
+                put_u16(dst, *count);
+            }
+            Response::WriteMultipleRegisters { address, count } => {
+                put_u16(dst, *address);
+                put_u16(dst, *count);
+            }
+        }
+    }
+
+    /// Decodes the PDU of a response to `request`.
+    ///
+    /// Exception responses are returned as [`ModbusError::Exception`].
+    pub fn decode(request: &Request, pdu: &[u8]) -> Result<Response, ModbusError> {
+        let invalid = ModbusError::InvalidResponse;
+        let (&function, data) = pdu.split_first().ok_or(invalid("empty response"))?;
+        if function == request.function_code() | 0x80 {
+            let code = match data {
+                [code] => ExceptionCode::from(*code),
+                _ => return Err(invalid("malformed exception response")),
+            };
+            return Err(ModbusError::Exception { function, code });
+        }
+        if function != request.function_code() {
+            return Err(invalid("unexpected function code"));
+        }
+
+        let malformed = |_| invalid("malformed response");
+        let mut data = Reader(data);
+        let response = match request {
+            Request::ReadCoils { count, .. } | Request::ReadDiscreteInputs { count, .. } => {
+                let bytes = data.counted().map_err(malformed)?;
+                if bytes.len() != bit_bytes(usize::from(*count)) {
+                    return Err(invalid("unexpected byte count"));
+                }
+                let values = get_bits(bytes, *count);
+                match request {
+                    Request::ReadCoils { .. } => Response::ReadCoils(values),
+                    _ => Response::ReadDiscreteInputs(values),
+                }
+            }
+            Request::ReadHoldingRegisters { count, .. }
+            | Request::ReadInputRegisters { count, .. }
+            | Request::ReadWriteMultipleRegisters {
+                read_count: count, ..
+            } => {
+                let values = get_registers(data.counted().map_err(malformed)?, *count)
+                    .map_err(|_| invalid("unexpected byte count"))?;
+                match request {
+                    Request::ReadHoldingRegisters { .. } => Response::ReadHoldingRegisters(values),
+                    Request::ReadInputRegisters { .. } => Response::ReadInputRegisters(values),
+                    _ => Response::ReadWriteMultipleRegisters(values),
+                }
+            }
+            Request::WriteSingleCoil { .. } => Response::WriteSingleCoil {
+                address: data.u16().map_err(malformed)?,
+                value: data.u16().map_err(malformed)? == COIL_ON,
+            },
+            Request::WriteSingleRegister { .. } => Response::WriteSingleRegister {
+                address: data.u16().map_err(malformed)?,
+                value: data.u16().map_err(malformed)?,
+            },
+            Request::WriteMultipleCoils { .. } => Response::WriteMultipleCoils {
+                address: data.u16().map_err(malformed)?,
+                count: data.u16().map_err(malformed)?,
+            },
+            Request::WriteMultipleRegisters { .. } => Response::WriteMultipleRegisters {
+                address: data.u16().map_err(malformed)?,
+                count: data.u16().map_err(malformed)?,
+            },
+        };
+        data.finish().map_err(malformed)?;
+        Ok(response)
+    }
+}
+
+/// The length of the PDU of a response, as far as it can be told from its first bytes.
+///
+/// Returns `None` when more bytes are needed to tell, and an error when the function
+/// code is not one a response can have.
+pub(crate) fn response_len(pdu: &[u8]) -> Result<Option<usize>, ModbusError> {
+    match pdu {
+        [] => Ok(None),
+        [function, ..] if function & 0x80 != 0 => Ok(Some(2)),
+        [0x01..=0x04, ..] | [0x17, ..] => Ok(pdu.get(1).map(|count| 2 + usize::from(*count))),
+        [0x05, ..] | [0x06, ..] | [0x0F, ..] | [0x10, ..] => Ok(Some(5)),
+        _ => Err(ModbusError::InvalidResponse("unexpected function code")),
+    }
+}
+
+/// The length of the PDU of a request, as far as it can be told from its first bytes.
+///
+/// Returns `None` when more bytes are needed to tell, and an error when the function
+/// code is not supported.
+pub(crate) fn request_len(pdu: &[u8]) -> Result<Option<usize>, ExceptionCode> {
+    match pdu {
+        [] => Ok(None),
+        [0x01..=0x06, ..] => Ok(Some(5)),
+        [0x0F, ..] | [0x10, ..] => Ok(pdu.get(5).map(|count| 6 + usize::from(*count))),
+        [0x17, ..] => Ok(pdu.get(9).map(|count| 10 + usize::from(*count))),
+        _ => Err(ExceptionCode::IllegalFunction),
+    }
+}
+
+fn put_u16(dst: &mut Vec<u8>, value: u16) {
+    dst.extend_from_slice(&value.to_be_bytes());
+}
+
+/// The number of bytes that `bits` bits are packed into.
+// `usize::div_ceil` is newer than the minimum supported Rust version.
+#[allow(clippy::manual_div_ceil)]
+fn bit_bytes(bits: usize) -> usize {
+    (bits + 7) / 8
+}
+
+fn put_bits(dst: &mut Vec<u8>, values: &[bool]) {
+    for chunk in values.chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, bit)| byte | (u8::from(*bit) << i));
+        dst.push(byte);
+    }
+}
+
+fn put_registers(dst: &mut Vec<u8>, values: &[u16]) {
+    for value in values {
+        put_u16(dst, *value);
+    }
+}
+
+fn get_bits(bytes: &[u8], count: u16) -> Vec<bool> {
+    (0..usize::from(count))
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect()
+}
+
+fn get_registers(bytes: &[u8], count: u16) -> Result<Vec<u16>, ExceptionCode> {
+    if bytes.len() != usize::from(count) * 2 {
+        return Err(ExceptionCode::IllegalDataValue);
+    }
+    Ok(bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+/// Takes fields off the front of a PDU.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn u16(&mut self) -> Result<u16, ExceptionCode> {
+        match self.0 {
+            [high, low, rest @ ..] => {
+                self.0 = rest;
+                Ok(u16::from_be_bytes([*high, *low]))
+            }
+            _ => Err(ExceptionCode::IllegalDataValue),
+        }
+    }
+
+    /// A byte count followed by that many bytes.
+    fn counted(&mut self) -> Result<&'a [u8], ExceptionCode> {
+        let (&count, rest) = self
+            .0
+            .split_first()
+            .ok_or(ExceptionCode::IllegalDataValue)?;
+        let count = usize::from(count);
+        if rest.len() < count {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+        self.0 = &rest[count..];
+        Ok(&rest[..count])
+    }
+
+    fn finish(&self) -> Result<(), ExceptionCode> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ExceptionCode::IllegalDataValue)
+        }
+    }
+}
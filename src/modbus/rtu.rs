@@ -0,0 +1,35 @@
+//! Framing shared by the RTU client and server: a frame is the unit id, the PDU and a
+//! CRC-16/MODBUS sent least significant byte first.
+use crate::crc::Crc;
+use crate::{SerialPort, SerialStream};
+
+use std::time::Duration;
+
+/// The longest RTU frame: unit id, a PDU of up to 253 bytes and the CRC.
+pub(crate) const MAX_ADU_LENGTH: usize = 256;
+/// The fixed silence used above 19200 baud, where 3.5 character times would be too
+/// short for most devices to time reliably.
+const FAST_SILENCE: Duration = Duration::from_micros(1750);
+
+/// The inter-frame silence for the current settings of `port`.
+pub(crate) fn silence(port: &SerialStream) -> crate::Result<Duration> {
+    if port.baud_rate()? > 19200 {
+        return Ok(FAST_SILENCE);
+    }
+    Ok(port.character_time()? * 7 / 2)
+}
+
+/// Appends the CRC of the unit id and PDU in `adu`.
+pub(crate) fn append_crc(adu: &mut Vec<u8>) {
+    let crc = Crc::CRC_16_MODBUS.checksum(adu) as u16;
+    adu.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Checks the CRC at the end of a received frame.
+pub(crate) fn crc_ok(adu: &[u8]) -> bool {
+    if adu.len() < 2 {
+        return false;
+    }
+    let (frame, crc) = adu.split_at(adu.len() - 2);
+    Crc::CRC_16_MODBUS.checksum(frame) == u32::from(u16::from_le_bytes([crc[0], crc[1]]))
+}
@@ -0,0 +1,437 @@
+//! A Modbus RTU slave.
+use super::pdu::{self, Request, Response};
+use super::rtu::{self, MAX_ADU_LENGTH};
+use super::{ExceptionCode, ModbusError, BROADCAST};
+use crate::SerialStream;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{self, Instant};
+
+use std::io;
+use std::time::Duration;
+
+/// The four tables of data a Modbus server exposes.
+///
+/// Each method either carries out the whole access or refuses it with the exception
+/// code sent back to the client.  Reads must return exactly `count` values.  The
+/// server has already checked that the addresses don't run past `0xFFFF` and that the
+/// quantities are within the limits of the protocol.
+///
+/// By default every table is missing, and accesses to it are refused with
+/// [`ExceptionCode::IllegalFunction`], so a store only needs to implement the tables
+/// it has.
+pub trait DataStore {
+    /// Reads `count` coils starting at `address`.
+    fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+        let _ = (address, count);
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Reads `count` discrete inputs starting at `address`.
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<bool>, ExceptionCode> {
+        let _ = (address, count);
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Reads `count` holding registers starting at `address`.
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ExceptionCode> {
+        let _ = (address, count);
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Reads `count` input registers starting at `address`.
+    fn read_input_registers(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ExceptionCode> {
+        let _ = (address, count);
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Sets the coils starting at `address` to `values`.
+    fn write_coils(&mut self, address: u16, values: &[bool]) -> Result<(), ExceptionCode> {
+        let _ = (address, values);
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Writes `values` to the holding registers starting at `address`.
+    fn write_holding_registers(
+        &mut self,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, values);
+        Err(ExceptionCode::IllegalFunction)
+    }
+}
+
+/// A [`DataStore`] keeping each table in a vector, starting at address 0.
+///
+/// Accesses beyond the end of a table are refused with
+/// [`ExceptionCode::IllegalDataAddress`].  Since discrete inputs and input registers
+/// can't be written by clients, a simulation sets them through the fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStore {
+    /// The coils.
+    pub coils: Vec<bool>,
+    /// The discrete inputs.
+    pub discrete_inputs: Vec<bool>,
+    /// The holding registers.
+    pub holding_registers: Vec<u16>,
+    /// The input registers.
+    pub input_registers: Vec<u16>,
+}
+
+impl MemoryStore {
+    /// Create a store with `size` entries in every table, all zero.
+    pub fn new(size: usize) -> MemoryStore {
+        Self {
+            coils: vec![false; size],
+            discrete_inputs: vec![false; size],
+            holding_registers: vec![0; size],
+            input_registers: vec![0; size],
+        }
+    }
+}
+
+fn range<T>(table: &[T], address: u16, count: usize) -> Result<&[T], ExceptionCode> {
+    let start = usize::from(address);
+    table
+        .get(start..start + count)
+        .ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+fn range_mut<T>(table: &mut [T], address: u16, count: usize) -> Result<&mut [T], ExceptionCode> {
+    let start = usize::from(address);
+    table
+        .get_mut(start..start + count)
+        .ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+impl DataStore for MemoryStore {
+    fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+        range(&self.coils, address, usize::from(count)).map(<[bool]>::to_vec)
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<bool>, ExceptionCode> {
+        range(&self.discrete_inputs, address, usize::from(count)).map(<[bool]>::to_vec)
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ExceptionCode> {
+        range(&self.holding_registers, address, usize::from(count)).map(<[u16]>::to_vec)
+    }
+
+    fn read_input_registers(
+        &mut self,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ExceptionCode> {
+        range(&self.input_registers, address, usize::from(count)).map(<[u16]>::to_vec)
+    }
+
+    fn write_coils(&mut self, address: u16, values: &[bool]) -> Result<(), ExceptionCode> {
+        range_mut(&mut self.coils, address, values.len())?.copy_from_slice(values);
+        Ok(())
+    }
+
+    fn write_holding_registers(
+        &mut self,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), ExceptionCode> {
+        range_mut(&mut self.holding_registers, address, values.len())?.copy_from_slice(values);
+        Ok(())
+    }
+}
+
+/// A Modbus RTU server, also known as a slave, serving the tables of a [`DataStore`].
+///
+/// The server answers requests addressed to its own unit id, and carries out write
+/// requests sent to the broadcast address without answering them.  Frames for other
+/// units, and frames that fail their CRC, are ignored.  A frame ends when its length,
+/// as given by its function code, has arrived, or when the line falls silent for the
+/// inter-frame silence, so that requests with unsupported function codes can still be
+/// answered with [`ExceptionCode::IllegalFunction`].
+///
+/// It is mostly meant for testing clients without hardware, on one side of
+/// [`SerialStream::pair`]:
+///
+/// ```no_run
+/// use tokio_serial::modbus::{Client, MemoryStore, RtuServer};
+/// use tokio_serial::SerialStream;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let (master, slave) = SerialStream::pair()?;
+/// let mut store = MemoryStore::new(100);
+/// store.input_registers[0] = 1234;
+/// tokio::spawn(RtuServer::new(slave, 1, store)?.run());
+///
+/// let mut client = Client::new(master)?;
+/// assert_eq!(client.read_input_registers(1, 0, 1).await?, vec![1234]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RtuServer<D> {
+    port: SerialStream,
+    unit: u8,
+    store: D,
+    silence: Duration,
+    // When the last byte of the last frame arrived.
+    idle_since: Instant,
+}
+
+impl<D: DataStore> RtuServer<D> {
+    /// Create a server on `port` answering to `unit`, with the inter-frame silence
+    /// worked out from the settings of the port as for
+    /// [`Client::new`](super::Client::new).
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the settings could not be read from the port.
+    /// * `InvalidInput` if the baud rate is zero.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `unit` is not between 1 and 247.
+    pub fn new(port: SerialStream, unit: u8, store: D) -> crate::Result<RtuServer<D>> {
+        assert!(
+            (1..=247).contains(&unit),
+            "unit id must be between 1 and 247"
+        );
+        let silence = rtu::silence(&port)?;
+        Ok(Self {
+            port,
+            unit,
+            store,
+            silence,
+            idle_since: Instant::now(),
+        })
+    }
+
+    /// Returns the unit id the server answers to.
+    pub fn unit(&self) -> u8 {
+        self.unit
+    }
+
+    /// Returns the inter-frame silence.
+    pub fn silence(&self) -> Duration {
+        self.silence
+    }
+
+    /// Sets the inter-frame silence.
+    pub fn set_silence(&mut self, silence: Duration) {
+        self.silence = silence;
+    }
+
+    /// Returns a reference to the data store.
+    pub fn store(&self) -> &D {
+        &self.store
+    }
+
+    /// Returns a mutable reference to the data store.
+    pub fn store_mut(&mut self) -> &mut D {
+        &mut self.store
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the server, returning the underlying port and the data store.
+    pub fn into_parts(self) -> (SerialStream, D) {
+        (self.port, self.store)
+    }
+
+    /// Serves requests until the other end of the line hangs up or an I/O error occurs.
+    pub async fn run(mut self) -> Result<(), ModbusError> {
+        loop {
+            match self.process().await {
+                Err(ModbusError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(())
+                }
+                Err(err) => return Err(err),
+                Ok(()) => {}
+            }
+        }
+    }
+
+    /// Waits for the next frame and handles it.  Returns once the response, if any,
+    /// has been sent.
+    ///
+    /// A hangup is reported as an `Io` error of kind `UnexpectedEof`.
+    pub async fn process(&mut self) -> Result<(), ModbusError> {
+        let adu = self.receive().await?;
+        if adu.len() < 4 || !rtu::crc_ok(&adu) {
+            log::debug!("dropping invalid modbus frame {:02x?}", adu);
+            return Ok(());
+        }
+        let unit = adu[0];
+        if unit != self.unit && unit != BROADCAST {
+            return Ok(());
+        }
+
+        let pdu = &adu[1..adu.len() - 2];
+        let result = match Request::decode(pdu) {
+            Ok(request) if unit == BROADCAST && !request.is_write() => return Ok(()),
+            Ok(request) => execute(&mut self.store, &request),
+            Err(code) => Err(code),
+        };
+        if unit == BROADCAST {
+            return Ok(());
+        }
+
+        let mut response = Vec::with_capacity(MAX_ADU_LENGTH);
+        response.push(self.unit);
+        match result {
+            Ok(response_pdu) => response_pdu.encode(&mut response),
+            Err(code) => response.extend_from_slice(&[pdu[0] | 0x80, code.into()]),
+        }
+        rtu::append_crc(&mut response);
+
+        time::sleep_until(self.idle_since + self.silence).await;
+        self.port.write_all(&response).await?;
+        self.port.drain().await?;
+        self.idle_since = Instant::now();
+        Ok(())
+    }
+
+    /// Reads a frame, until its length as given by its function code has arrived or
+    /// the line falls silent.
+    async fn receive(&mut self) -> Result<Vec<u8>, ModbusError> {
+        let mut adu = Vec::with_capacity(MAX_ADU_LENGTH);
+        let mut buf = [0u8; MAX_ADU_LENGTH];
+        loop {
+            // Every request is at least eight bytes long.
+            let len = match pdu::request_len(adu.get(1..).unwrap_or(&[])) {
+                Ok(Some(pdu_len)) => 1 + pdu_len + 2,
+                Ok(None) => (adu.len() + 1).max(8),
+                Err(_) => usize::MAX,
+            };
+            if adu.len() >= len {
+                break;
+            }
+            let want = (len - adu.len()).min(buf.len());
+            let n = if adu.is_empty() {
+                self.port.read(&mut buf[..want]).await?
+            } else {
+                match time::timeout(self.silence, self.port.read(&mut buf[..want])).await {
+                    Ok(n) => n?,
+                    Err(_) => break,
+                }
+            };
+            if n == 0 {
+                return Err(ModbusError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+            adu.extend_from_slice(&buf[..n]);
+            self.idle_since = Instant::now();
+        }
+        Ok(adu)
+    }
+}
+
+/// Carries out a request against the store.
+fn execute<D: DataStore>(store: &mut D, request: &Request) -> Result<Response, ExceptionCode> {
+    fn check_range(address: u16, count: usize) -> Result<(), ExceptionCode> {
+        if usize::from(address) + count > 0x10000 {
+            return Err(ExceptionCode::IllegalDataAddress);
+        }
+        Ok(())
+    }
+    fn check_len<T>(values: Vec<T>, count: u16) -> Result<Vec<T>, ExceptionCode> {
+        if values.len() != usize::from(count) {
+            return Err(ExceptionCode::ServerDeviceFailure);
+        }
+        Ok(values)
+    }
+
+    match *request {
+        Request::ReadCoils { address, count } => {
+            check_range(address, usize::from(count))?;
+            let values = check_len(store.read_coils(address, count)?, count)?;
+            Ok(Response::ReadCoils(values))
+        }
+        Request::ReadDiscreteInputs { address, count } => {
+            check_range(address, usize::from(count))?;
+            let values = check_len(store.read_discrete_inputs(address, count)?, count)?;
+            Ok(Response::ReadDiscreteInputs(values))
+        }
+        Request::ReadHoldingRegisters { address, count } => {
+            check_range(address, usize::from(count))?;
+            let values = check_len(store.read_holding_registers(address, count)?, count)?;
+            Ok(Response::ReadHoldingRegisters(values))
+        }
+        Request::ReadInputRegisters { address, count } => {
+            check_range(address, usize::from(count))?;
+            let values = check_len(store.read_input_registers(address, count)?, count)?;
+            Ok(Response::ReadInputRegisters(values))
+        }
+        Request::WriteSingleCoil { address, value } => {
+            store.write_coils(address, &[value])?;
+            Ok(Response::WriteSingleCoil { address, value })
+        }
+        Request::WriteSingleRegister { address, value } => {
+            store.write_holding_registers(address, &[value])?;
+            Ok(Response::WriteSingleRegister { address, value })
+        }
+        Request::WriteMultipleCoils {
+            address,
+            ref values,
+        } => {
+            check_range(address, values.len())?;
+            store.write_coils(address, values)?;
+            Ok(Response::WriteMultipleCoils {
+                address,
+                count: values.len() as u16,
+            })
+        }
+        Request::WriteMultipleRegisters {
+            address,
+            ref values,
+        } => {
+            check_range(address, values.len())?;
+            store.write_holding_registers(address, values)?;
+            Ok(Response::WriteMultipleRegisters {
+                address,
+                count: values.len() as u16,
+            })
+        }
+        Request::ReadWriteMultipleRegisters {
+            read_address,
+            read_count,
+            write_address,
+            ref values,
+        } => {
+            check_range(read_address, usize::from(read_count))?;
+            check_range(write_address, values.len())?;
+            store.write_holding_registers(write_address, values)?;
+            let values = store.read_holding_registers(read_address, read_count)?;
+            Ok(Response::ReadWriteMultipleRegisters(check_len(
+                values, read_count,
+            )?))
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Notifications for changes of the modem status lines.
+//!
+//! [`SerialStream::modem_events`] returns a [`Stream`] of [`ModemStatusChange`]s for the
+//! CTS, DSR, DCD and RI inputs.  On Linux the changes are reported by the driver through
+//! the `TIOCMIWAIT` ioctl, which is waited on from a helper thread.  Drivers that do not
+//! support it (pseudo terminals, many USB adapters) and other platforms fall back to
+//! polling the lines at a fixed interval.
+//!
+//! When the stream is dropped, the helper thread is woken from `TIOCMIWAIT` with the
+//! last real-time signal, `SIGRTMAX`, for which a handler that does nothing is
+//! installed the first time a stream is created.  If the application already handles
+//! `SIGRTMAX` itself, its handler is left alone and the lines are polled instead.
+//!
+//! [`Stream`]: futures_core::Stream
+use crate::{SerialPort, SerialStream};
+
+use futures_core::Stream;
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::{Interval, MissedTickBehavior};
+
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::BorrowedFd;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(target_os = "linux")]
+use tokio::sync::mpsc;
+
+/// How often the lines are polled when the driver can't notify about changes.
+const DEFAULT_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// A modem status input line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModemLine {
+    /// Clear To Send (CTS)
+    ClearToSend,
+    /// Data Set Ready (DSR)
+    DataSetReady,
+    /// Data Carrier Detect (DCD)
+    CarrierDetect,
+    /// Ring Indicator (RI)
+    RingIndicator,
+}
+
+/// A change of level on one of the modem status lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModemStatusChange {
+    /// The line that changed.
+    pub line: ModemLine,
+    /// The new level of the line, `true` if asserted.
+    pub level: bool,
+    /// When the change was noticed.
+    pub timestamp: Instant,
+}
+
+/// Levels of all modem status lines at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Levels {
+    cts: bool,
+    dsr: bool,
+    cd: bool,
+    ri: bool,
+}
+
+impl Levels {
+    fn read<P: SerialPort>(port: &mut P) -> crate::Result<Self> {
+        Ok(Self {
+            cts: port.read_clear_to_send()?,
+            dsr: port.read_data_set_ready()?,
+            cd: port.read_carrier_detect()?,
+            ri: port.read_ring_indicator()?,
+        })
+    }
+
+    /// The changes needed to go from `self` to `current`.
+    fn changes(
+        self,
+        current: Levels,
+        timestamp: Instant,
+    ) -> impl Iterator<Item = ModemStatusChange> {
+        IntoIterator::into_iter([
+            (ModemLine::ClearToSend, self.cts, current.cts),
+            (ModemLine::DataSetReady, self.dsr, current.dsr),
+            (ModemLine::CarrierDetect, self.cd, current.cd),
+            (ModemLine::RingIndicator, self.ri, current.ri),
+        ])
+        .filter(|(_, old, new)| old != new)
+        .map(move |(line, _, level)| ModemStatusChange {
+            line,
+            level,
+            timestamp,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Source {
+    /// Changes are reported by a helper thread blocked in `TIOCMIWAIT`.
+    #[cfg(target_os = "linux")]
+    Watch {
+        rx: mpsc::UnboundedReceiver<io::Result<ModemStatusChange>>,
+        delivered: bool,
+        // Stops the thread when the stream is dropped or falls back to polling.
+        _thread: sys::Watcher,
+    },
+    /// The lines are read on every tick.
+    Poll(Interval),
+}
+
+/// A [`Stream`] of modem status line changes, created by [`SerialStream::modem_events`]
+/// or [`ModemEvents::polling`].
+///
+/// The stream ends after yielding the first error.
+///
+/// [`Stream`]: futures_core::Stream
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct ModemEvents<P> {
+    port: P,
+    source: Source,
+    period: Duration,
+    levels: Option<Levels>,
+    pending: VecDeque<ModemStatusChange>,
+    done: bool,
+}
+
+impl<'a> ModemEvents<&'a SerialStream> {
+    pub(crate) fn watch(port: &'a SerialStream) -> crate::Result<Self> {
+        #[cfg(unix)]
+        {
+            let fd = port.as_raw_fd();
+            Self::watch_fd(port, fd, DEFAULT_POLL_PERIOD)
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self::polling(port, DEFAULT_POLL_PERIOD))
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<P: SerialPort + AsRawFd> ModemEvents<P> {
+    /// Watch the modem status lines of any port with a file descriptor, the way
+    /// [`SerialStream::modem_events`] does.
+    ///
+    /// Where the driver can't report changes, the lines are read from `port` every
+    /// `period` instead.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the handler for the signal that stops the helper thread could not be
+    ///   installed, or the thread could not be started.
+    pub fn watching(port: P, period: Duration) -> crate::Result<Self> {
+        let fd = port.as_raw_fd();
+        Self::watch_fd(port, fd, period)
+    }
+}
+
+#[cfg(unix)]
+impl<P: SerialPort> ModemEvents<P> {
+    fn watch_fd(port: P, fd: RawFd, period: Duration) -> crate::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            if !sys::install_wake_handler()? {
+                log::debug!("SIGRTMAX is handled elsewhere, polling the modem lines instead");
+                return Ok(Self::polling(port, period));
+            }
+            // The helper thread gets its own descriptor so it never touches the port lock.
+            // Safety: `port` keeps the descriptor open while it is duplicated.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+            let (rx, thread) = sys::spawn_watch(fd)?;
+            Ok(Self::new(
+                port,
+                Source::Watch {
+                    rx,
+                    delivered: false,
+                    _thread: thread,
+                },
+                period,
+            ))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = fd;
+            Ok(Self::polling(port, period))
+        }
+    }
+}
+
+impl<P: SerialPort> ModemEvents<P> {
+    /// Watch the modem status lines of any port by reading them every `period`.
+    ///
+    /// The first reading only establishes the initial levels and does not produce
+    /// any changes.  Pulses shorter than `period` may be missed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if it is not called from within a Tokio runtime.
+    pub fn polling(port: P, period: Duration) -> Self {
+        Self::new(port, Source::Poll(interval(period)), period)
+    }
+}
+
+impl<P> ModemEvents<P> {
+    fn new(port: P, source: Source, period: Duration) -> Self {
+        Self {
+            port,
+            source,
+            period,
+            levels: None,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Returns a reference to the port being watched.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Consumes the `ModemEvents`, returning the port being watched.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+}
+
+fn interval(period: Duration) -> Interval {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    interval
+}
+
+impl<P: SerialPort + Unpin> Stream for ModemEvents<P> {
+    type Item = crate::Result<ModemStatusChange>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(change) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(change)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            match &mut this.source {
+                #[cfg(target_os = "linux")]
+                Source::Watch { rx, delivered, .. } => match ready!(rx.poll_recv(cx)) {
+                    Some(Ok(change)) => {
+                        *delivered = true;
+                        return Poll::Ready(Some(Ok(change)));
+                    }
+                    Some(Err(err)) if !*delivered && sys::is_unsupported(&err) => {
+                        log::debug!("TIOCMIWAIT is not supported ({}), polling instead", err);
+                        this.source = Source::Poll(interval(this.period));
+                    }
+                    Some(Err(err)) => {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(err.into())));
+                    }
+                    None => this.done = true,
+                },
+                Source::Poll(interval) => {
+                    ready!(interval.poll_tick(cx));
+                    let current = match Levels::read(&mut this.port) {
+                        Ok(levels) => levels,
+                        Err(err) => {
+                            this.done = true;
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                    };
+                    if let Some(previous) = this.levels.replace(current) {
+                        this.pending
+                            .extend(previous.changes(current, Instant::now()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::{Levels, ModemStatusChange};
+    use std::io;
+    use std::os::unix::io::{AsRawFd, OwnedFd};
+    use std::os::unix::thread::JoinHandleExt;
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+    use std::sync::{Arc, Once};
+    use std::thread::JoinHandle;
+    use std::time::{Duration, Instant};
+    use tokio::sync::mpsc;
+
+    /// Sent to the helper thread to interrupt `TIOCMIWAIT` when it has to stop.
+    fn wake_signal() -> libc::c_int {
+        libc::SIGRTMAX()
+    }
+
+    /// How long dropping a [`Watcher`] waits for the helper thread to stop.
+    const STOP_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Installs a handler for the wake signal that does nothing, without `SA_RESTART`,
+    /// so that the signal makes a blocked `TIOCMIWAIT` fail with `EINTR`.
+    ///
+    /// Returns `false`, installing nothing, if the signal already has a handler.  The
+    /// outcome of the first call is kept for all later ones.
+    pub(super) fn install_wake_handler() -> io::Result<bool> {
+        static INSTALL: Once = Once::new();
+        static INSTALLED: AtomicBool = AtomicBool::new(false);
+        static ERROR: AtomicI32 = AtomicI32::new(0);
+
+        INSTALL.call_once(|| match unsafe { install() } {
+            Ok(installed) => INSTALLED.store(installed, Ordering::SeqCst),
+            Err(err) => ERROR.store(err.raw_os_error().unwrap_or(libc::EINVAL), Ordering::SeqCst),
+        });
+        match ERROR.load(Ordering::SeqCst) {
+            0 => Ok(INSTALLED.load(Ordering::SeqCst)),
+            errno => Err(io::Error::from_raw_os_error(errno)),
+        }
+    }
+
+    unsafe fn install() -> io::Result<bool> {
+        extern "C" fn wake(_: libc::c_int) {}
+
+        let mut old: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(wake_signal(), std::ptr::null(), &mut old) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if old.sa_sigaction != libc::SIG_DFL && old.sa_sigaction != libc::SIG_IGN {
+            return Ok(false);
+        }
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = wake as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(wake_signal(), &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+
+    /// The helper thread, which is stopped and joined when this is dropped.
+    ///
+    /// `TIOCMIWAIT` has no timeout and closing the descriptor does not wake it, so the
+    /// thread is interrupted with a signal.  Its descriptor is normally closed by the
+    /// time the drop returns, so the port can be opened again right away.  A thread that
+    /// doesn't stop within [`STOP_TIMEOUT`] is left behind instead of blocking the
+    /// caller, and stops after the next change of the lines.
+    #[derive(Debug)]
+    pub(super) struct Watcher {
+        thread: Option<JoinHandle<()>>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl Drop for Watcher {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            if let Some(thread) = self.thread.take() {
+                // The signal may arrive just before the thread blocks, so keep sending it
+                // until the thread is gone.
+                let deadline = Instant::now() + STOP_TIMEOUT;
+                while !thread.is_finished() {
+                    if Instant::now() >= deadline {
+                        log::debug!("modem status thread did not stop, leaving it behind");
+                        return;
+                    }
+                    unsafe { libc::pthread_kill(thread.as_pthread_t(), wake_signal()) };
+                    std::thread::sleep(Duration::from_micros(100));
+                }
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Start a thread that reports every change of the modem status lines on `fd`.
+    ///
+    /// The thread stops after the first error, once the receiver has been dropped, or
+    /// when the returned [`Watcher`] is dropped.
+    pub(super) fn spawn_watch(
+        fd: OwnedFd,
+    ) -> io::Result<(
+        mpsc::UnboundedReceiver<io::Result<ModemStatusChange>>,
+        Watcher,
+    )> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("tokio-serial-modem".into())
+            .spawn(move || watch(fd, tx, &stopped))?;
+        let watcher = Watcher {
+            thread: Some(thread),
+            stop,
+        };
+        Ok((rx, watcher))
+    }
+
+    fn watch(
+        fd: OwnedFd,
+        tx: mpsc::UnboundedSender<io::Result<ModemStatusChange>>,
+        stop: &AtomicBool,
+    ) {
+        let mut levels = match tiocmget(&fd, stop) {
+            Ok(levels) => levels,
+            Err(err) => {
+                let _ = tx.send(Err(err));
+                return;
+            }
+        };
+
+        while !tx.is_closed() && !stop.load(Ordering::SeqCst) {
+            let current = match tiocmiwait(&fd, stop).and_then(|_| tiocmget(&fd, stop)) {
+                Ok(levels) => levels,
+                Err(_) if stop.load(Ordering::SeqCst) => return,
+                Err(err) => {
+                    let _ = tx.send(Err(err));
+                    return;
+                }
+            };
+            for change in levels.changes(current, Instant::now()) {
+                if tx.send(Ok(change)).is_err() {
+                    return;
+                }
+            }
+            levels = current;
+        }
+    }
+
+    /// Whether `err` means the driver has no support for the modem ioctls.
+    pub(super) fn is_unsupported(err: &io::Error) -> bool {
+        matches!(err.raw_os_error(), Some(libc::ENOTTY) | Some(libc::EINVAL))
+    }
+
+    fn tiocmget(fd: &OwnedFd, stop: &AtomicBool) -> io::Result<Levels> {
+        let mut bits: libc::c_int = 0;
+        ioctl(stop, || unsafe {
+            libc::ioctl(fd.as_raw_fd(), libc::TIOCMGET, &mut bits)
+        })?;
+        Ok(Levels {
+            cts: bits & libc::TIOCM_CTS != 0,
+            dsr: bits & libc::TIOCM_DSR != 0,
+            cd: bits & libc::TIOCM_CAR != 0,
+            ri: bits & libc::TIOCM_RNG != 0,
+        })
+    }
+
+    fn tiocmiwait(fd: &OwnedFd, stop: &AtomicBool) -> io::Result<()> {
+        let mask = libc::TIOCM_CTS | libc::TIOCM_DSR | libc::TIOCM_CAR | libc::TIOCM_RNG;
+        ioctl(stop, || unsafe {
+            libc::ioctl(fd.as_raw_fd(), libc::TIOCMIWAIT, mask as libc::c_ulong)
+        })
+    }
+
+    /// Run an ioctl, retrying when interrupted by a signal unless the thread has to
+    /// stop.
+    fn ioctl(stop: &AtomicBool, mut f: impl FnMut() -> libc::c_int) -> io::Result<()> {
+        loop {
+            if f() >= 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted || stop.load(Ordering::SeqCst) {
+                return Err(err);
+            }
+        }
+    }
+}
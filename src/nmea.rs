@@ -0,0 +1,157 @@
+//! NMEA 0183 sentences, as sent by GNSS receivers.
+//!
+//! [`NmeaCodec`] frames the sentences arriving on a serial port, checks their checksums
+//! and parses them into [`Sentence`]s.  The common fix and satellite sentences are
+//! parsed into typed structures, whatever constellation their talker id names; every
+//! other sentence, including proprietary ones, is passed through as a
+//! [`RawSentence`].
+//!
+//! ```no_run
+//! use futures_util::stream::StreamExt;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::nmea::{NmeaCodec, Sentence};
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let port = SerialStream::open(&tokio_serial::new("/dev/ttyACM0", 9600))?;
+//! let mut sentences = SerialFramed::new(port, NmeaCodec::new());
+//! while let Some(sentence) = sentences.next().await {
+//!     match sentence {
+//!         Ok(Sentence::Gga(gga)) => println!("{:?} {:?}", gga.latitude, gga.longitude),
+//!         Ok(_) => {}
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module is only available with the `nmea` feature, which enables `codec` as
+//! well.
+use std::{fmt, io};
+
+mod codec;
+mod sentence;
+
+pub use self::codec::NmeaCodec;
+pub use self::sentence::{
+    FixQuality, FixType, Gga, Gll, Gsa, Gsv, RawSentence, Rmc, SatelliteInfo, Sentence, Vtg, Zda,
+};
+
+#[cfg(feature = "ubx")]
+pub(crate) use self::sentence::parse as parse_sentence;
+
+/// The kind of equipment a sentence came from, given by the first two letters of its
+/// address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Talker {
+    /// GPS (`GP`).
+    Gps,
+    /// GLONASS (`GL`).
+    Glonass,
+    /// Galileo (`GA`).
+    Galileo,
+    /// BeiDou (`GB` or `BD`).
+    Beidou,
+    /// QZSS (`GQ`).
+    Qzss,
+    /// NavIC (`GI`).
+    Navic,
+    /// A combination of constellations (`GN`).
+    Gnss,
+    /// Any other talker.
+    Other([u8; 2]),
+}
+
+impl Talker {
+    fn from_id(id: [u8; 2]) -> Talker {
+        match &id {
+            b"GP" => Talker::Gps,
+            b"GL" => Talker::Glonass,
+            b"GA" => Talker::Galileo,
+            b"GB" | b"BD" => Talker::Beidou,
+            b"GQ" => Talker::Qzss,
+            b"GI" => Talker::Navic,
+            b"GN" => Talker::Gnss,
+            _ => Talker::Other(id),
+        }
+    }
+}
+
+/// A time of day in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 60 to allow for leap seconds.
+    pub second: u8,
+    /// The millisecond, from 0 to 999.
+    pub millisecond: u16,
+}
+
+/// A calendar date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    /// The year.  Sentences that give it in two digits are taken to mean 1980 to 2079.
+    pub year: u16,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+}
+
+/// An error from an [`NmeaCodec`] or from parsing a [`Sentence`].
+///
+/// The codec reports bad sentences and carries on with the next one, so none of these
+/// end a [`SerialFramed`](crate::frame::SerialFramed) stream, except for `Io`.
+#[derive(Debug)]
+pub enum NmeaError {
+    /// The checksum of a sentence didn't match its contents.
+    BadChecksum {
+        /// The checksum computed over the sentence.
+        computed: u8,
+        /// The checksum the sentence carried.
+        received: u8,
+    },
+    /// A sentence had no checksum, and one is required.
+    MissingChecksum,
+    /// A sentence was not valid, or one of its fields could not be parsed.
+    InvalidSentence(&'static str),
+    /// A sentence was longer than the maximum sentence length.
+    SentenceTooLong,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for NmeaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NmeaError::BadChecksum { computed, received } => write!(
+                f,
+                "sentence checksum mismatch: computed {:02X}, received {:02X}",
+                computed, received
+            ),
+            NmeaError::MissingChecksum => write!(f, "sentence has no checksum"),
+            NmeaError::InvalidSentence(msg) => write!(f, "invalid sentence: {}", msg),
+            NmeaError::SentenceTooLong => write!(f, "max sentence length exceeded"),
+            NmeaError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for NmeaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NmeaError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NmeaError {
+    fn from(err: io::Error) -> Self {
+        NmeaError::Io(err)
+    }
+}
@@ -0,0 +1,168 @@
+//! Framing of NMEA 0183 sentences.
+use super::sentence::{self, checksum, Sentence};
+use super::NmeaError;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A codec for NMEA 0183 sentences, one per line.
+///
+/// The decoder checks the checksum of each sentence and parses it into a
+/// [`Sentence`].  Anything on a line before the `$` or `!` that starts a sentence is
+/// skipped, and lines without a sentence are skipped entirely, which takes care of the
+/// partial line usually caught when opening a port.  A sentence that fails its
+/// checksum, can't be parsed or is overlong is reported with an error, and decoding
+/// carries on with the next line.
+///
+/// The encoder takes a sentence from its start delimiter up to but not including its
+/// checksum, such as `$PMTK220,1000`, and adds the checksum and CR LF.
+///
+/// ```
+/// use tokio_serial::nmea::NmeaCodec;
+///
+/// let codec = NmeaCodec::new()
+///     .with_checksum_required(false)
+///     .with_max_sentence_length(82);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NmeaCodec {
+    checksum_required: bool,
+    max_sentence_length: usize,
+    // Where to continue looking for a newline on the next call to `decode`.
+    next_index: usize,
+    is_discarding: bool,
+}
+
+impl NmeaCodec {
+    /// Create a codec that requires checksums and has no limit on the sentence length.
+    pub fn new() -> NmeaCodec {
+        Self {
+            checksum_required: true,
+            max_sentence_length: usize::MAX,
+            next_index: 0,
+            is_discarding: false,
+        }
+    }
+
+    /// Sets whether sentences without a checksum are rejected.  Checksums that are
+    /// present are always verified.
+    pub fn with_checksum_required(mut self, checksum_required: bool) -> Self {
+        self.checksum_required = checksum_required;
+        self
+    }
+
+    /// Sets the maximum length of a sentence, from its start delimiter to its checksum.
+    /// The standard allows 82 characters including the CR LF, but receivers often send
+    /// longer proprietary sentences.
+    pub fn with_max_sentence_length(mut self, max_sentence_length: usize) -> Self {
+        self.max_sentence_length = max_sentence_length;
+        self
+    }
+
+    /// Returns whether sentences without a checksum are rejected.
+    pub fn checksum_required(&self) -> bool {
+        self.checksum_required
+    }
+
+    /// Returns the maximum length of a sentence.
+    pub fn max_sentence_length(&self) -> usize {
+        self.max_sentence_length
+    }
+}
+
+impl Default for NmeaCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for NmeaCodec {
+    type Item = Sentence;
+    type Error = NmeaError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Sentence>, NmeaError> {
+        loop {
+            // The buffer may have been tampered with since the last call.
+            let start = self.next_index.min(src.len());
+            let end = match src[start..].iter().position(|b| *b == b'\n') {
+                Some(at) => start + at,
+                None if self.is_discarding => {
+                    src.clear();
+                    self.next_index = 0;
+                    return Ok(None);
+                }
+                // Leave room for junk before the sentence and the CR.
+                None if src.len() > self.max_sentence_length.saturating_add(1) => {
+                    src.clear();
+                    self.next_index = 0;
+                    self.is_discarding = true;
+                    return Err(NmeaError::SentenceTooLong);
+                }
+                None => {
+                    self.next_index = src.len();
+                    return Ok(None);
+                }
+            };
+
+            let line = src.split_to(end);
+            src.advance(1);
+            self.next_index = 0;
+            if self.is_discarding {
+                self.is_discarding = false;
+                continue;
+            }
+
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            let sentence = match line.iter().position(|b| *b == b'$' || *b == b'!') {
+                Some(at) => &line[at..],
+                None => continue,
+            };
+            if sentence.len() > self.max_sentence_length {
+                return Err(NmeaError::SentenceTooLong);
+            }
+            let sentence = std::str::from_utf8(sentence)
+                .map_err(|_| NmeaError::InvalidSentence("not printable ASCII"))?;
+            return sentence::parse(sentence, self.checksum_required).map(Some);
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Sentence>, NmeaError> {
+        let sentence = self.decode(src)?;
+        if sentence.is_none() {
+            // A sentence that never got its line ending may have been cut short.
+            src.clear();
+            self.next_index = 0;
+            self.is_discarding = false;
+        }
+        Ok(sentence)
+    }
+}
+
+impl<T> Encoder<T> for NmeaCodec
+where
+    T: AsRef<str>,
+{
+    type Error = NmeaError;
+
+    fn encode(&mut self, sentence: T, dst: &mut BytesMut) -> Result<(), NmeaError> {
+        let sentence = sentence.as_ref().as_bytes();
+        match sentence.split_first() {
+            Some((b'$', _)) | Some((b'!', _)) => {}
+            _ => return Err(NmeaError::InvalidSentence("missing start delimiter")),
+        }
+        if !sentence[1..]
+            .iter()
+            .all(|b| (0x20..0x7F).contains(b) && *b != b'*')
+        {
+            return Err(NmeaError::InvalidSentence("not printable ASCII"));
+        }
+        if sentence.len() + 3 > self.max_sentence_length {
+            return Err(NmeaError::SentenceTooLong);
+        }
+
+        dst.reserve(sentence.len() + 5);
+        dst.put_slice(sentence);
+        dst.put_slice(format!("*{:02X}\r\n", checksum(&sentence[1..])).as_bytes());
+        Ok(())
+    }
+}
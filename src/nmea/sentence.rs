@@ -0,0 +1,706 @@
+//! Parsing of NMEA 0183 sentences.
+use super::{Date, NmeaError, Talker, Time};
+
+use std::str::FromStr;
+
+/// A parsed sentence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sentence {
+    /// Global positioning system fix data.
+    Gga(Gga),
+    /// Recommended minimum specific GNSS data.
+    Rmc(Rmc),
+    /// GNSS DOP and active satellites.
+    Gsa(Gsa),
+    /// GNSS satellites in view.
+    Gsv(Gsv),
+    /// Course over ground and ground speed.
+    Vtg(Vtg),
+    /// Geographic position, latitude and longitude.
+    Gll(Gll),
+    /// Time and date.
+    Zda(Zda),
+    /// Any other sentence, passed through as it was received.
+    Unknown(RawSentence),
+}
+
+impl Sentence {
+    /// Parses a sentence, from its `$` or `!` up to but not including the CR LF that
+    /// ends it.
+    ///
+    /// The checksum is verified if there is one, but a sentence without one is
+    /// accepted.
+    pub fn parse(sentence: &str) -> Result<Sentence, NmeaError> {
+        parse(sentence, false)
+    }
+}
+
+impl FromStr for Sentence {
+    type Err = NmeaError;
+
+    fn from_str(sentence: &str) -> Result<Sentence, NmeaError> {
+        Sentence::parse(sentence)
+    }
+}
+
+/// Checks a sentence and parses it, typed if possible.
+pub(crate) fn parse(sentence: &str, require_checksum: bool) -> Result<Sentence, NmeaError> {
+    let raw = RawSentence::new(sentence, require_checksum)?;
+    let talker = match raw.talker() {
+        Some(talker) => talker,
+        None => return Ok(Sentence::Unknown(raw)),
+    };
+    let mut fields = Fields::new(raw.fields());
+    let sentence = match raw.sentence_type() {
+        "GGA" => Sentence::Gga(Gga::parse(talker, &mut fields)?),
+        "RMC" => Sentence::Rmc(Rmc::parse(talker, &mut fields)?),
+        "GSA" => Sentence::Gsa(Gsa::parse(talker, &mut fields)?),
+        "GSV" => Sentence::Gsv(Gsv::parse(talker, &mut fields)?),
+        "VTG" => Sentence::Vtg(Vtg::parse(talker, &mut fields)?),
+        "GLL" => Sentence::Gll(Gll::parse(talker, &mut fields)?),
+        "ZDA" => Sentence::Zda(Zda::parse(talker, &mut fields)?),
+        _ => Sentence::Unknown(raw),
+    };
+    Ok(sentence)
+}
+
+/// A sentence whose checksum, if any, has been verified, but whose fields have not
+/// been interpreted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawSentence {
+    sentence: String,
+    // Where the data fields end: at the `*` of the checksum, or at the end.
+    data_end: usize,
+}
+
+impl RawSentence {
+    fn new(sentence: &str, require_checksum: bool) -> Result<RawSentence, NmeaError> {
+        if !sentence.bytes().all(|b| (0x20..0x7F).contains(&b)) {
+            return Err(NmeaError::InvalidSentence("not printable ASCII"));
+        }
+        if !(sentence.starts_with('$') || sentence.starts_with('!')) {
+            return Err(NmeaError::InvalidSentence("missing start delimiter"));
+        }
+
+        let data_end = match sentence.rfind('*') {
+            Some(star) => {
+                let received = sentence
+                    .get(star + 1..)
+                    .filter(|hex| hex.len() == 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or(NmeaError::InvalidSentence("malformed checksum"))?;
+                let computed = checksum(&sentence.as_bytes()[1..star]);
+                if computed != received {
+                    return Err(NmeaError::BadChecksum { computed, received });
+                }
+                star
+            }
+            None if require_checksum => return Err(NmeaError::MissingChecksum),
+            None => sentence.len(),
+        };
+
+        let raw = RawSentence {
+            sentence: sentence.to_owned(),
+            data_end,
+        };
+        if raw.address().is_empty() {
+            return Err(NmeaError::InvalidSentence("missing address"));
+        }
+        Ok(raw)
+    }
+
+    /// Returns the whole sentence, including its start delimiter and checksum.
+    pub fn as_str(&self) -> &str {
+        &self.sentence
+    }
+
+    /// Returns the address field, such as `GPGGA` or `PUBX`.
+    pub fn address(&self) -> &str {
+        let data = &self.sentence[1..self.data_end];
+        data.split(',').next().unwrap_or("")
+    }
+
+    /// Returns whether this is a proprietary sentence, whose address starts with `P`
+    /// and a manufacturer code rather than a talker id.
+    pub fn is_proprietary(&self) -> bool {
+        self.address().starts_with('P')
+    }
+
+    /// Returns the talker, unless this is a proprietary sentence.
+    pub fn talker(&self) -> Option<Talker> {
+        match self.address().as_bytes() {
+            [b'P', ..] => None,
+            [first, second, _, _, _] => Some(Talker::from_id([*first, *second])),
+            _ => None,
+        }
+    }
+
+    /// Returns the sentence formatter, such as `GGA`, or the whole address for a
+    /// proprietary sentence.
+    pub fn sentence_type(&self) -> &str {
+        let address = self.address();
+        if self.talker().is_some() {
+            &address[2..]
+        } else {
+            address
+        }
+    }
+
+    /// Returns the data fields following the address.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        let data = &self.sentence[1..self.data_end];
+        data.split(',').skip(1)
+    }
+}
+
+/// Computes the checksum of the characters between the start delimiter and the `*`.
+pub(crate) fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum ^ byte)
+}
+
+/// The quality of a position fix, from a GGA sentence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixQuality {
+    /// No fix.
+    Invalid,
+    /// A standalone GNSS fix.
+    Gps,
+    /// A differential GNSS fix.
+    Dgps,
+    /// A precise positioning service fix.
+    Pps,
+    /// A real time kinematic fix with fixed integers.
+    Rtk,
+    /// A real time kinematic fix with float integers.
+    FloatRtk,
+    /// A dead reckoning estimate.
+    Estimated,
+    /// A position entered by hand.
+    Manual,
+    /// A simulated position.
+    Simulation,
+    /// A fix quality not defined by the standard.
+    Other(u8),
+}
+
+impl From<u8> for FixQuality {
+    fn from(quality: u8) -> Self {
+        match quality {
+            0 => FixQuality::Invalid,
+            1 => FixQuality::Gps,
+            2 => FixQuality::Dgps,
+            3 => FixQuality::Pps,
+            4 => FixQuality::Rtk,
+            5 => FixQuality::FloatRtk,
+            6 => FixQuality::Estimated,
+            7 => FixQuality::Manual,
+            8 => FixQuality::Simulation,
+            quality => FixQuality::Other(quality),
+        }
+    }
+}
+
+/// The kind of fix, from a GSA sentence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixType {
+    /// No fix.
+    NoFix,
+    /// A two dimensional fix, without altitude.
+    Fix2D,
+    /// A three dimensional fix.
+    Fix3D,
+}
+
+/// Global positioning system fix data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gga {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The time of the fix.
+    pub time: Option<Time>,
+    /// The latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// The longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    /// The quality of the fix.
+    pub fix_quality: FixQuality,
+    /// The number of satellites in use.
+    pub satellites: Option<u8>,
+    /// The horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// The altitude above mean sea level in metres.
+    pub altitude: Option<f32>,
+    /// The height of the geoid above the WGS84 ellipsoid in metres.
+    pub geoid_separation: Option<f32>,
+    /// The age of the differential corrections in seconds.
+    pub dgps_age: Option<f32>,
+    /// The id of the differential reference station.
+    pub dgps_station: Option<u16>,
+}
+
+impl Gga {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Gga, NmeaError> {
+        Ok(Gga {
+            talker,
+            time: fields.time()?,
+            latitude: fields.latitude()?,
+            longitude: fields.longitude()?,
+            fix_quality: fields
+                .number::<u8>("fix quality")?
+                .map_or(FixQuality::Invalid, FixQuality::from),
+            satellites: fields.number("satellites")?,
+            hdop: fields.number("HDOP")?,
+            altitude: fields.metres("altitude")?,
+            geoid_separation: fields.metres("geoid separation")?,
+            dgps_age: fields.number("DGPS age")?,
+            dgps_station: fields.number("DGPS station")?,
+        })
+    }
+}
+
+/// Recommended minimum specific GNSS data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rmc {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The time of the fix.
+    pub time: Option<Time>,
+    /// Whether the data is valid.
+    pub valid: bool,
+    /// The latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// The longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    /// The speed over ground in knots.
+    pub speed_knots: Option<f32>,
+    /// The course over ground in degrees from true north.
+    pub course: Option<f32>,
+    /// The date of the fix.
+    pub date: Option<Date>,
+    /// The magnetic variation in degrees, negative to the west.
+    pub magnetic_variation: Option<f32>,
+    /// The mode indicator of NMEA 2.3 and later, such as `A` for autonomous or `D` for
+    /// differential.
+    pub mode: Option<char>,
+}
+
+impl Rmc {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Rmc, NmeaError> {
+        Ok(Rmc {
+            talker,
+            time: fields.time()?,
+            valid: fields.status()?,
+            latitude: fields.latitude()?,
+            longitude: fields.longitude()?,
+            speed_knots: fields.number("speed")?,
+            course: fields.number("course")?,
+            date: fields.date()?,
+            magnetic_variation: fields.signed("magnetic variation", 'E', 'W')?,
+            mode: fields.char(),
+        })
+    }
+}
+
+/// GNSS DOP and active satellites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gsa {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// Whether the receiver chooses between 2D and 3D fixes by itself.
+    pub automatic: bool,
+    /// The kind of fix.
+    pub fix_type: FixType,
+    /// The ids of the satellites used in the fix.
+    pub satellites: Vec<u16>,
+    /// The position dilution of precision.
+    pub pdop: Option<f32>,
+    /// The horizontal dilution of precision.
+    pub hdop: Option<f32>,
+    /// The vertical dilution of precision.
+    pub vdop: Option<f32>,
+    /// The GNSS system id of NMEA 4.1 and later.
+    pub system_id: Option<u8>,
+}
+
+impl Gsa {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Gsa, NmeaError> {
+        let automatic = match fields.char() {
+            Some('A') | None => true,
+            Some('M') => false,
+            Some(_) => return Err(NmeaError::InvalidSentence("invalid selection mode")),
+        };
+        let fix_type = match fields.number::<u8>("fix type")? {
+            Some(1) | None => FixType::NoFix,
+            Some(2) => FixType::Fix2D,
+            Some(3) => FixType::Fix3D,
+            Some(_) => return Err(NmeaError::InvalidSentence("invalid fix type")),
+        };
+        let mut satellites = Vec::with_capacity(12);
+        for _ in 0..12 {
+            if let Some(id) = fields.number("satellite id")? {
+                satellites.push(id);
+            }
+        }
+        Ok(Gsa {
+            talker,
+            automatic,
+            fix_type,
+            satellites,
+            pdop: fields.number("PDOP")?,
+            hdop: fields.number("HDOP")?,
+            vdop: fields.number("VDOP")?,
+            system_id: fields.number("system id")?,
+        })
+    }
+}
+
+/// A satellite in view, from a GSV sentence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SatelliteInfo {
+    /// The id of the satellite.
+    pub id: u16,
+    /// The elevation in degrees.
+    pub elevation: Option<u8>,
+    /// The azimuth in degrees from true north.
+    pub azimuth: Option<u16>,
+    /// The signal to noise ratio in dB-Hz, or `None` when the satellite is not tracked.
+    pub snr: Option<u8>,
+}
+
+/// GNSS satellites in view.  The satellites are spread over several sentences of up to
+/// four satellites each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gsv {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The number of sentences in this group.
+    pub sentence_count: u8,
+    /// The number of this sentence in its group, starting from 1.
+    pub sentence_number: u8,
+    /// The total number of satellites in view.
+    pub satellites_in_view: u16,
+    /// The satellites described by this sentence.
+    pub satellites: Vec<SatelliteInfo>,
+    /// The signal id of NMEA 4.1 and later.
+    pub signal_id: Option<u8>,
+}
+
+impl Gsv {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Gsv, NmeaError> {
+        let sentence_count = fields.required("sentence count")?;
+        let sentence_number = fields.required("sentence number")?;
+        let satellites_in_view = fields.required("satellites in view")?;
+
+        let mut satellites = Vec::with_capacity(4);
+        let mut blocks = fields.rest().chunks_exact(4);
+        for block in &mut blocks {
+            let mut block = Fields::new(block.iter().copied());
+            let id = match block.number("satellite id")? {
+                Some(id) => id,
+                // Padding for a sentence with fewer than four satellites.
+                None => continue,
+            };
+            satellites.push(SatelliteInfo {
+                id,
+                elevation: block.number("elevation")?,
+                azimuth: block.number("azimuth")?,
+                snr: block.number("SNR")?,
+            });
+        }
+        let signal_id = match blocks.remainder() {
+            [] => None,
+            [signal_id] => Fields::new(Some(*signal_id)).number("signal id")?,
+            _ => return Err(NmeaError::InvalidSentence("truncated satellite")),
+        };
+
+        Ok(Gsv {
+            talker,
+            sentence_count,
+            sentence_number,
+            satellites_in_view,
+            satellites,
+            signal_id,
+        })
+    }
+}
+
+/// Course over ground and ground speed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vtg {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The course over ground in degrees from true north.
+    pub course_true: Option<f32>,
+    /// The course over ground in degrees from magnetic north.
+    pub course_magnetic: Option<f32>,
+    /// The speed over ground in knots.
+    pub speed_knots: Option<f32>,
+    /// The speed over ground in kilometres per hour.
+    pub speed_kmh: Option<f32>,
+    /// The mode indicator of NMEA 2.3 and later.
+    pub mode: Option<char>,
+}
+
+impl Vtg {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Vtg, NmeaError> {
+        Ok(Vtg {
+            talker,
+            course_true: fields.with_unit("true course", 'T')?,
+            course_magnetic: fields.with_unit("magnetic course", 'M')?,
+            speed_knots: fields.with_unit("speed in knots", 'N')?,
+            speed_kmh: fields.with_unit("speed in km/h", 'K')?,
+            mode: fields.char(),
+        })
+    }
+}
+
+/// Geographic position, latitude and longitude.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gll {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The latitude in degrees, negative to the south.
+    pub latitude: Option<f64>,
+    /// The longitude in degrees, negative to the west.
+    pub longitude: Option<f64>,
+    /// The time of the position.
+    pub time: Option<Time>,
+    /// Whether the data is valid.
+    pub valid: bool,
+    /// The mode indicator of NMEA 2.3 and later.
+    pub mode: Option<char>,
+}
+
+impl Gll {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Gll, NmeaError> {
+        Ok(Gll {
+            talker,
+            latitude: fields.latitude()?,
+            longitude: fields.longitude()?,
+            time: fields.time()?,
+            valid: fields.status()?,
+            mode: fields.char(),
+        })
+    }
+}
+
+/// Time and date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zda {
+    /// Where the sentence came from.
+    pub talker: Talker,
+    /// The time of day.
+    pub time: Option<Time>,
+    /// The date.
+    pub date: Option<Date>,
+    /// The hours of the local time zone's offset from UTC.
+    pub local_zone_hours: Option<i8>,
+    /// The minutes of the local time zone's offset from UTC, with the same sign as the
+    /// hours.
+    pub local_zone_minutes: Option<u8>,
+}
+
+impl Zda {
+    fn parse(talker: Talker, fields: &mut Fields<'_>) -> Result<Zda, NmeaError> {
+        let time = fields.time()?;
+        let day = fields.number("day")?;
+        let month = fields.number("month")?;
+        let year = fields.number("year")?;
+        let date = match (year, month, day) {
+            (Some(year), Some(month @ 1..=12), Some(day @ 1..=31)) => {
+                Some(Date { year, month, day })
+            }
+            (None, None, None) => None,
+            _ => return Err(NmeaError::InvalidSentence("invalid date")),
+        };
+        Ok(Zda {
+            talker,
+            time,
+            date,
+            local_zone_hours: fields.number("local zone hours")?,
+            local_zone_minutes: fields.number("local zone minutes")?,
+        })
+    }
+}
+
+/// The data fields of a sentence, taken in order.  Missing trailing fields read as
+/// empty, since many receivers leave out the ones added by later versions.
+struct Fields<'a> {
+    fields: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(fields: impl IntoIterator<Item = &'a str>) -> Fields<'a> {
+        Fields {
+            fields: fields.into_iter().collect(),
+            next: 0,
+        }
+    }
+
+    fn next(&mut self) -> &'a str {
+        let field = self.fields.get(self.next).copied().unwrap_or("");
+        self.next += 1;
+        field
+    }
+
+    /// Takes all the fields that are left.
+    fn rest(&mut self) -> &[&'a str] {
+        let rest = self.fields.get(self.next..).unwrap_or(&[]);
+        self.next = self.fields.len();
+        rest
+    }
+
+    fn number<T: FromStr>(&mut self, what: &'static str) -> Result<Option<T>, NmeaError> {
+        match self.next() {
+            "" => Ok(None),
+            field => field
+                .parse()
+                .map(Some)
+                .map_err(|_| NmeaError::InvalidSentence(what)),
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, what: &'static str) -> Result<T, NmeaError> {
+        self.number(what)?.ok_or(NmeaError::InvalidSentence(what))
+    }
+
+    fn char(&mut self) -> Option<char> {
+        self.next().chars().next()
+    }
+
+    /// A status field, `A` for valid and `V` for invalid.
+    fn status(&mut self) -> Result<bool, NmeaError> {
+        match self.char() {
+            Some('A') => Ok(true),
+            Some('V') | None => Ok(false),
+            Some(_) => Err(NmeaError::InvalidSentence("invalid status")),
+        }
+    }
+
+    /// A number followed by a unit field, which must be `unit` or empty.
+    fn with_unit(&mut self, what: &'static str, unit: char) -> Result<Option<f32>, NmeaError> {
+        let value = self.number(what)?;
+        match self.char() {
+            Some(c) if c != unit => Err(NmeaError::InvalidSentence(what)),
+            _ => Ok(value),
+        }
+    }
+
+    fn metres(&mut self, what: &'static str) -> Result<Option<f32>, NmeaError> {
+        self.with_unit(what, 'M')
+    }
+
+    /// A number followed by a hemisphere field, giving its sign.
+    fn signed(
+        &mut self,
+        what: &'static str,
+        positive: char,
+        negative: char,
+    ) -> Result<Option<f32>, NmeaError> {
+        let value: Option<f32> = self.number(what)?;
+        match (value, self.char()) {
+            (Some(value), Some(c)) if c == positive => Ok(Some(value)),
+            (Some(value), Some(c)) if c == negative => Ok(Some(-value)),
+            (None, None) => Ok(None),
+            _ => Err(NmeaError::InvalidSentence(what)),
+        }
+    }
+
+    /// Degrees and minutes as `dddmm.mmmm`, followed by a hemisphere field.
+    fn coordinate(
+        &mut self,
+        what: &'static str,
+        positive: char,
+        negative: char,
+    ) -> Result<Option<f64>, NmeaError> {
+        let field = self.next();
+        let hemisphere = self.char();
+        if field.is_empty() && hemisphere.is_none() {
+            return Ok(None);
+        }
+
+        let invalid = NmeaError::InvalidSentence(what);
+        let dot = field.find('.').unwrap_or(field.len());
+        if dot < 3 {
+            return Err(invalid);
+        }
+        let degrees: f64 = field[..dot - 2].parse().map_err(|_| invalid)?;
+        let minutes: f64 = field[dot - 2..]
+            .parse()
+            .map_err(|_| NmeaError::InvalidSentence(what))?;
+        if minutes >= 60.0 {
+            return Err(NmeaError::InvalidSentence(what));
+        }
+        let value = degrees + minutes / 60.0;
+        match hemisphere {
+            Some(c) if c == positive => Ok(Some(value)),
+            Some(c) if c == negative => Ok(Some(-value)),
+            _ => Err(NmeaError::InvalidSentence(what)),
+        }
+    }
+
+    fn latitude(&mut self) -> Result<Option<f64>, NmeaError> {
+        self.coordinate("latitude", 'N', 'S')
+    }
+
+    fn longitude(&mut self) -> Result<Option<f64>, NmeaError> {
+        self.coordinate("longitude", 'E', 'W')
+    }
+
+    /// A time as `hhmmss` with an optional fraction of a second.
+    fn time(&mut self) -> Result<Option<Time>, NmeaError> {
+        let field = self.next();
+        if field.is_empty() {
+            return Ok(None);
+        }
+
+        let invalid = || NmeaError::InvalidSentence("invalid time");
+        let (whole, fraction) = field.split_at(field.find('.').unwrap_or(field.len()));
+        if whole.len() != 6 || !whole.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let two_digits = |at: usize| whole[at..at + 2].parse::<u8>().map_err(|_| invalid());
+        let (hour, minute, second) = (two_digits(0)?, two_digits(2)?, two_digits(4)?);
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(invalid());
+        }
+
+        let digits = fraction.get(1..).unwrap_or("");
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let millisecond = digits
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(3)
+            .fold(0u16, |ms, digit| ms * 10 + u16::from(digit - b'0'));
+
+        Ok(Some(Time {
+            hour,
+            minute,
+            second,
+            millisecond,
+        }))
+    }
+
+    /// A date as `ddmmyy`.
+    fn date(&mut self) -> Result<Option<Date>, NmeaError> {
+        let field = self.next();
+        if field.is_empty() {
+            return Ok(None);
+        }
+
+        let invalid = || NmeaError::InvalidSentence("invalid date");
+        if field.len() != 6 || !field.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let two_digits = |at: usize| field[at..at + 2].parse::<u8>().map_err(|_| invalid());
+        let (day, month, year) = (two_digits(0)?, two_digits(2)?, two_digits(4)?);
+        if !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+            return Err(invalid());
+        }
+        let year = if year < 80 { 2000 } else { 1900 } + u16::from(year);
+        Ok(Some(Date { year, month, day }))
+    }
+}
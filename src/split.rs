@@ -0,0 +1,286 @@
+//! `SerialStream` split support.
+//!
+//! A `SerialStream` can be split into a read half and a write half with
+//! [`SerialStream::split`] and [`SerialStream::into_split`].  The read half implements
+//! `AsyncRead` and the write half implements `AsyncWrite`.  Both halves poll the same
+//! registration with the reactor, which keeps separate wakers for the read and write
+//! directions, so they can be driven independently from different tasks.
+//!
+//! The port settings and modem control lines are reachable from either half through
+//! [`AsRef<SerialStream>`], since [`SerialPort`](crate::SerialPort) is also implemented
+//! for `&SerialStream`.
+use super::{Drain, ReadTimer, SerialStream};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::error::Error;
+use std::fmt;
+use std::io::Result as IoResult;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Borrowed read half of a [`SerialStream`], created by [`SerialStream::split`].
+///
+/// Reading from a `ReadHalf` is usually done using the convenience methods found on the
+/// [`tokio::io::AsyncReadExt`] trait.
+#[derive(Debug)]
+pub struct ReadHalf<'a> {
+    inner: &'a SerialStream,
+    read_timer: ReadTimer,
+}
+
+/// Borrowed write half of a [`SerialStream`], created by [`SerialStream::split`].
+///
+/// Writing to a `WriteHalf` is usually done using the convenience methods found on the
+/// [`tokio::io::AsyncWriteExt`] trait.
+#[derive(Debug)]
+pub struct WriteHalf<'a> {
+    inner: &'a SerialStream,
+    drain: Drain,
+}
+
+pub(crate) fn split(stream: &mut SerialStream) -> (ReadHalf<'_>, WriteHalf<'_>) {
+    let write = WriteHalf {
+        inner: &*stream,
+        drain: Drain::default(),
+    };
+    let read = ReadHalf {
+        inner: &*stream,
+        read_timer: ReadTimer::default(),
+    };
+    (read, write)
+}
+
+/// Owned read half of a [`SerialStream`], created by [`SerialStream::into_split`].
+///
+/// Reading from an `OwnedReadHalf` is usually done using the convenience methods found
+/// on the [`tokio::io::AsyncReadExt`] trait.
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    inner: Arc<SerialStream>,
+    read_timer: ReadTimer,
+}
+
+/// Owned write half of a [`SerialStream`], created by [`SerialStream::into_split`].
+///
+/// Writing to an `OwnedWriteHalf` is usually done using the convenience methods found
+/// on the [`tokio::io::AsyncWriteExt`] trait.
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    inner: Arc<SerialStream>,
+    drain: Drain,
+}
+
+pub(crate) fn split_owned(stream: SerialStream) -> (OwnedReadHalf, OwnedWriteHalf) {
+    let arc = Arc::new(stream);
+    let read = OwnedReadHalf {
+        inner: Arc::clone(&arc),
+        read_timer: ReadTimer::default(),
+    };
+    let write = OwnedWriteHalf {
+        inner: arc,
+        drain: Drain::default(),
+    };
+    (read, write)
+}
+
+pub(crate) fn reunite(
+    read: OwnedReadHalf,
+    write: OwnedWriteHalf,
+) -> Result<SerialStream, ReuniteError> {
+    if Arc::ptr_eq(&read.inner, &write.inner) {
+        drop(write);
+        // This unwrap cannot fail as the api does not allow creating more than two Arcs,
+        // and we just dropped the other half.
+        Ok(Arc::try_unwrap(read.inner).expect("SerialStream: try_unwrap failed in reunite"))
+    } else {
+        Err(ReuniteError(read, write))
+    }
+}
+
+/// Error indicating that two halves were not from the same port, and thus could
+/// not be reunited.
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Display for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tried to reunite halves that are not from the same serial port"
+        )
+    }
+}
+
+impl Error for ReuniteError {}
+
+impl ReadHalf<'_> {
+    /// Wait for the port to become readable.
+    ///
+    /// See [`SerialStream::readable`].
+    pub async fn readable(&self) -> IoResult<()> {
+        self.inner.readable().await
+    }
+
+    /// Try to read bytes on the serial port.  On success returns the number of bytes read.
+    ///
+    /// See [`SerialStream::try_read`].
+    pub fn try_read(&self, buf: &mut [u8]) -> IoResult<usize> {
+        self.inner.try_read(buf)
+    }
+}
+
+impl WriteHalf<'_> {
+    /// Wait for the port to become writable.
+    ///
+    /// See [`SerialStream::writable`].
+    pub async fn writable(&self) -> IoResult<()> {
+        self.inner.writable().await
+    }
+
+    /// Try to write bytes on the serial port.  On success returns the number of bytes written.
+    ///
+    /// See [`SerialStream::try_write`].
+    pub fn try_write(&self, buf: &[u8]) -> IoResult<usize> {
+        self.inner.try_write(buf)
+    }
+
+    /// Wait until all data written to the port has been transmitted.
+    ///
+    /// See [`SerialStream::drain`].
+    pub async fn drain(&self) -> IoResult<()> {
+        self.inner.drain().await
+    }
+}
+
+impl OwnedReadHalf {
+    /// Attempts to put the two halves of a `SerialStream` back together and
+    /// recover the original port. Succeeds only if the two halves
+    /// originated from the same call to [`SerialStream::into_split`].
+    pub fn reunite(self, other: OwnedWriteHalf) -> Result<SerialStream, ReuniteError> {
+        reunite(self, other)
+    }
+
+    /// Wait for the port to become readable.
+    ///
+    /// See [`SerialStream::readable`].
+    pub async fn readable(&self) -> IoResult<()> {
+        self.inner.readable().await
+    }
+
+    /// Try to read bytes on the serial port.  On success returns the number of bytes read.
+    ///
+    /// See [`SerialStream::try_read`].
+    pub fn try_read(&self, buf: &mut [u8]) -> IoResult<usize> {
+        self.inner.try_read(buf)
+    }
+}
+
+impl OwnedWriteHalf {
+    /// Attempts to put the two halves of a `SerialStream` back together and
+    /// recover the original port. Succeeds only if the two halves
+    /// originated from the same call to [`SerialStream::into_split`].
+    pub fn reunite(self, other: OwnedReadHalf) -> Result<SerialStream, ReuniteError> {
+        reunite(other, self)
+    }
+
+    /// Wait for the port to become writable.
+    ///
+    /// See [`SerialStream::writable`].
+    pub async fn writable(&self) -> IoResult<()> {
+        self.inner.writable().await
+    }
+
+    /// Try to write bytes on the serial port.  On success returns the number of bytes written.
+    ///
+    /// See [`SerialStream::try_write`].
+    pub fn try_write(&self, buf: &[u8]) -> IoResult<usize> {
+        self.inner.try_write(buf)
+    }
+
+    /// Wait until all data written to the port has been transmitted.
+    ///
+    /// See [`SerialStream::drain`].
+    pub async fn drain(&self) -> IoResult<()> {
+        self.inner.drain().await
+    }
+}
+
+impl AsyncRead for ReadHalf<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.read_timer.poll_read(this.inner, cx, buf)
+    }
+}
+
+impl AsyncWrite for WriteHalf<'_> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        self.inner.poll_write_priv(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.drain.poll_drain(this.inner, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.inner.poll_shutdown_priv(cx, &mut this.drain)
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.read_timer.poll_read(&this.inner, cx, buf)
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        self.inner.poll_write_priv(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.drain.poll_drain(&this.inner, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        this.inner.poll_shutdown_priv(cx, &mut this.drain)
+    }
+}
+
+impl AsRef<SerialStream> for ReadHalf<'_> {
+    fn as_ref(&self) -> &SerialStream {
+        self.inner
+    }
+}
+
+impl AsRef<SerialStream> for WriteHalf<'_> {
+    fn as_ref(&self) -> &SerialStream {
+        self.inner
+    }
+}
+
+impl AsRef<SerialStream> for OwnedReadHalf {
+    fn as_ref(&self) -> &SerialStream {
+        &self.inner
+    }
+}
+
+impl AsRef<SerialStream> for OwnedWriteHalf {
+    fn as_ref(&self) -> &SerialStream {
+        &self.inner
+    }
+}
@@ -0,0 +1,623 @@
+//! The UART bootloader in the system memory of STM32 microcontrollers, as described in
+//! ST's AN3155.
+//!
+//! [`Bootloader`] configures the port for the 8 data bits, even parity and one stop
+//! bit the bootloader expects, sends the `0x7F` byte it measures the baud rate from,
+//! and then runs its commands: it reads what the bootloader supports and the product
+//! ID of the chip, reads and writes memory, erases flash and jumps to the
+//! application.  Every command, address and block of data is acknowledged by the
+//! bootloader with ACK or turned down with NACK.
+//!
+//! Boards that wire BOOT0 and NRST to the DTR and RTS outputs of the adapter can be
+//! reset into the bootloader and back into the application by the [`Wiring`] given to
+//! the bootloader.
+//!
+//! ```no_run
+//! use tokio_serial::stm32boot::{Bootloader, Control, Erase, Wiring};
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run(firmware: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+//! let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! let wiring = Wiring::new()
+//!     .with_boot0(Control::RequestToSend, false)
+//!     .with_reset(Control::DataTerminalReady, true);
+//! let mut boot = Bootloader::new(port).with_wiring(wiring);
+//!
+//! boot.enter().await?;
+//! println!("product ID {:#05x}", boot.get_id().await?);
+//! boot.erase(Erase::All).await?;
+//! boot.write_memory(0x0800_0000, firmware).await?;
+//! boot.go(0x0800_0000).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module is only available with the `stm32boot` feature.
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, SerialStream, StopBits};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{self, Instant};
+
+use std::time::Duration;
+use std::{fmt, io};
+
+const ACK: u8 = 0x79;
+const NACK: u8 = 0x1F;
+/// Sent first, for the bootloader to measure the baud rate from.
+const SYNC: u8 = 0x7F;
+
+const GET: u8 = 0x00;
+const GET_ID: u8 = 0x02;
+const READ_MEMORY: u8 = 0x11;
+const GO: u8 = 0x21;
+const WRITE_MEMORY: u8 = 0x31;
+const ERASE: u8 = 0x43;
+const EXTENDED_ERASE: u8 = 0x44;
+
+/// The most that can be read or written with one command.
+const MAX_TRANSFER: usize = 256;
+/// How many times the sync byte is sent before giving up.
+const SYNC_ATTEMPTS: usize = 3;
+/// How long NRST is held low.
+const RESET_PULSE: Duration = Duration::from_millis(10);
+/// How long the chip takes to start after NRST is let go.
+const STARTUP: Duration = Duration::from_millis(50);
+
+/// An error from a [`Bootloader`].
+#[derive(Debug)]
+pub enum BootloaderError {
+    /// The bootloader turned down a command, address or block of data with NACK, as
+    /// it does for commands that read protection forbids or memory that doesn't exist.
+    Nack,
+    /// The bootloader answered with something other than ACK or NACK.
+    UnexpectedReply(u8),
+    /// The bootloader doesn't support the command.
+    Unsupported,
+    /// The bootloader didn't answer in time.
+    Timeout,
+    /// An I/O error occurred, or the port reported end of file.
+    Io(io::Error),
+}
+
+impl fmt::Display for BootloaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootloaderError::Nack => write!(f, "refused by the bootloader"),
+            BootloaderError::UnexpectedReply(byte) => {
+                write!(f, "unexpected reply {:#04x} from the bootloader", byte)
+            }
+            BootloaderError::Unsupported => write!(f, "command not supported by the bootloader"),
+            BootloaderError::Timeout => write!(f, "no answer from the bootloader"),
+            BootloaderError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for BootloaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BootloaderError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BootloaderError {
+    fn from(err: io::Error) -> Self {
+        BootloaderError::Io(err)
+    }
+}
+
+impl From<crate::Error> for BootloaderError {
+    fn from(err: crate::Error) -> Self {
+        BootloaderError::Io(err.into())
+    }
+}
+
+/// What the bootloader told about itself in answer to Get.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Info {
+    /// The version of the bootloader, `0x31` for 3.1.
+    pub version: u8,
+    /// The codes of the commands it supports.
+    pub commands: Vec<u8>,
+}
+
+/// What to erase with [`Bootloader::erase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Erase<'a> {
+    /// All of the flash memory.
+    All,
+    /// The pages with these numbers.  Bootloaders with only the original Erase
+    /// command can erase up to 255 pages at once, numbered below 256.
+    Pages(&'a [u16]),
+    /// The first bank of flash memory.  Needs Extended Erase.
+    Bank1,
+    /// The second bank of flash memory.  Needs Extended Erase.
+    Bank2,
+}
+
+/// A modem control output of the port, wired to a pin of the chip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    /// Data Terminal Ready (DTR)
+    DataTerminalReady,
+    /// Request To Send (RTS)
+    RequestToSend,
+}
+
+/// An output and whether asserting it drives the pin low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pin {
+    control: Control,
+    inverted: bool,
+}
+
+/// How the BOOT0 and NRST pins of the chip are wired to the port, if at all.
+///
+/// An output is inverted if asserting it drives the pin low, as the DTR# and RTS# pins
+/// of most USB adapters do when they are wired straight to the chip.
+///
+/// ```
+/// use tokio_serial::stm32boot::{Control, Wiring};
+///
+/// let wiring = Wiring::new()
+///     .with_boot0(Control::RequestToSend, false)
+///     .with_reset(Control::DataTerminalReady, true);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Wiring {
+    boot0: Option<Pin>,
+    reset: Option<Pin>,
+}
+
+impl Wiring {
+    /// Create a wiring with neither pin connected, where the chip is put into the
+    /// bootloader by hand.
+    pub fn new() -> Wiring {
+        Self::default()
+    }
+
+    /// Sets the output that drives BOOT0.
+    pub fn with_boot0(mut self, control: Control, inverted: bool) -> Self {
+        self.boot0 = Some(Pin { control, inverted });
+        self
+    }
+
+    /// Sets the output that drives NRST.
+    pub fn with_reset(mut self, control: Control, inverted: bool) -> Self {
+        self.reset = Some(Pin { control, inverted });
+        self
+    }
+
+    /// Returns the output that drives BOOT0, and whether it is inverted.
+    pub fn boot0(&self) -> Option<(Control, bool)> {
+        self.boot0.map(|pin| (pin.control, pin.inverted))
+    }
+
+    /// Returns the output that drives NRST, and whether it is inverted.
+    pub fn reset(&self) -> Option<(Control, bool)> {
+        self.reset.map(|pin| (pin.control, pin.inverted))
+    }
+}
+
+/// A client for the bootloader of an STM32 on a serial port.
+///
+/// Call [`enter`](Bootloader::enter) first, to configure the port and get the
+/// bootloader's attention, then run commands one at a time.  Addresses and lengths
+/// are not checked against the memory of the chip; the bootloader turns down those it
+/// doesn't like with NACK.
+///
+/// ```no_run
+/// use tokio_serial::stm32boot::Bootloader;
+/// use tokio_serial::SerialStream;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 57600))?;
+/// let mut boot = Bootloader::new(port).with_erase_timeout(Duration::from_secs(120));
+/// boot.enter().await?;
+/// let mut option_bytes = [0u8; 16];
+/// boot.read_memory(0x1FFF_F800, &mut option_bytes).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Bootloader {
+    port: SerialStream,
+    timeout: Duration,
+    erase_timeout: Duration,
+    wiring: Wiring,
+    // The commands listed by Get, once asked for.
+    commands: Option<Vec<u8>>,
+}
+
+impl Bootloader {
+    /// Create a client on `port`, with a timeout of one second for replies and one
+    /// minute for erasing, and nothing wired to BOOT0 and NRST.
+    pub fn new(port: SerialStream) -> Bootloader {
+        Self {
+            port,
+            timeout: Duration::from_secs(1),
+            erase_timeout: Duration::from_secs(60),
+            wiring: Wiring::new(),
+            commands: None,
+        }
+    }
+
+    /// Sets how long to wait for each reply of the bootloader.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait for an erase to finish, which can take tens of seconds
+    /// on parts with a lot of flash.
+    pub fn with_erase_timeout(mut self, erase_timeout: Duration) -> Self {
+        self.erase_timeout = erase_timeout;
+        self
+    }
+
+    /// Sets how BOOT0 and NRST are wired to the port.
+    pub fn with_wiring(mut self, wiring: Wiring) -> Self {
+        self.wiring = wiring;
+        self
+    }
+
+    /// Returns how long to wait for each reply.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns how long to wait for an erase to finish.
+    pub fn erase_timeout(&self) -> Duration {
+        self.erase_timeout
+    }
+
+    /// Returns how BOOT0 and NRST are wired to the port.
+    pub fn wiring(&self) -> Wiring {
+        self.wiring
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the client, returning the underlying port.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Configures the port for the bootloader, resets the chip into it if NRST is
+    /// wired, and gets its attention with [`sync`](Bootloader::sync).
+    ///
+    /// The port is set to 8 data bits, even parity, one stop bit and no flow control,
+    /// keeping its baud rate.  BOOT0 is then driven high if it is wired, so a chip reset
+    /// by hand starts the bootloader too.  To reset the chip, NRST is pulsed low and
+    /// whatever the port received meanwhile is thrown away.
+    ///
+    /// ## Errors
+    ///
+    /// * `Timeout` if the bootloader never answered.
+    /// * `Io` if configuring the port, setting its outputs, or reading from or writing
+    ///   to it, failed.
+    pub async fn enter(&mut self) -> Result<(), BootloaderError> {
+        self.port.set_data_bits(DataBits::Eight)?;
+        self.port.set_parity(Parity::Even)?;
+        self.port.set_stop_bits(StopBits::One)?;
+        self.port.set_flow_control(FlowControl::None)?;
+        if let Some(boot0) = self.wiring.boot0 {
+            self.drive(boot0, true)?;
+        }
+        if let Some(reset) = self.wiring.reset {
+            self.reset(reset).await?;
+            self.port.clear(ClearBuffer::Input)?;
+        }
+        self.commands = None;
+        self.sync().await
+    }
+
+    /// Resets the chip with BOOT0 driven low, if it is wired, so that it starts the
+    /// application from flash.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` with `InvalidInput` if NRST is not wired, leaving the outputs alone.
+    /// * `Io` if setting the outputs of the port failed.
+    pub async fn leave(&mut self) -> Result<(), BootloaderError> {
+        let reset = match self.wiring.reset {
+            Some(reset) => reset,
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "NRST is not wired").into())
+            }
+        };
+        if let Some(boot0) = self.wiring.boot0 {
+            self.drive(boot0, false)?;
+        }
+        self.reset(reset).await?;
+        self.commands = None;
+        Ok(())
+    }
+
+    /// Sends the byte the bootloader measures the baud rate from, until it answers.
+    ///
+    /// A bootloader that did so already takes the byte as a damaged command and
+    /// answers with NACK, which is taken as success too.
+    ///
+    /// ## Errors
+    ///
+    /// * `Timeout` if the bootloader never answered.
+    /// * `Io` if reading from or writing to the port failed.
+    pub async fn sync(&mut self) -> Result<(), BootloaderError> {
+        for _ in 0..SYNC_ATTEMPTS {
+            self.port.write_all(&[SYNC]).await?;
+            match self.read_reply(self.timeout).await {
+                Ok(()) | Err(BootloaderError::Nack) => return Ok(()),
+                // Noise from before the bootloader started.
+                Err(BootloaderError::Timeout) | Err(BootloaderError::UnexpectedReply(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(BootloaderError::Timeout)
+    }
+
+    /// Runs Get, for the version of the bootloader and the commands it supports.
+    ///
+    /// ## Errors
+    ///
+    /// * `Nack` if the bootloader turned down the command.
+    /// * `UnexpectedReply` if it answered with something other than ACK or NACK.
+    /// * `Timeout` if it didn't answer in time.
+    /// * `Io` if reading from or writing to the port failed.
+    pub async fn get(&mut self) -> Result<Info, BootloaderError> {
+        self.command(GET).await?;
+        let deadline = Instant::now() + self.timeout;
+        let len = self.read_byte(deadline).await?;
+        let version = self.read_byte(deadline).await?;
+        let mut commands = vec![0; usize::from(len)];
+        self.read_exact(&mut commands, deadline).await?;
+        self.read_reply(self.timeout).await?;
+        self.commands = Some(commands.clone());
+        Ok(Info { version, commands })
+    }
+
+    /// Runs Get ID, for the product ID of the chip, such as `0x410` for the
+    /// medium-density STM32F10x.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`get`](Bootloader::get).
+    pub async fn get_id(&mut self) -> Result<u16, BootloaderError> {
+        self.command(GET_ID).await?;
+        let deadline = Instant::now() + self.timeout;
+        let len = self.read_byte(deadline).await?;
+        let mut id = vec![0; usize::from(len) + 1];
+        self.read_exact(&mut id, deadline).await?;
+        self.read_reply(self.timeout).await?;
+        // Every part has a two byte ID, but take the last two in any case.
+        Ok(id.iter().fold(0u16, |id, &byte| id << 8 | u16::from(byte)))
+    }
+
+    /// Runs Read Memory to fill `buf` from `address` on, in as many commands of up to
+    /// 256 bytes as it takes.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`get`](Bootloader::get).  The bootloader turns down reads while read
+    /// protection is on, and of addresses that aren't readable.
+    pub async fn read_memory(
+        &mut self,
+        address: u32,
+        buf: &mut [u8],
+    ) -> Result<(), BootloaderError> {
+        let mut address = address;
+        for chunk in buf.chunks_mut(MAX_TRANSFER) {
+            self.command(READ_MEMORY).await?;
+            self.send_address(address).await?;
+            let len = (chunk.len() - 1) as u8;
+            self.port.write_all(&[len, !len]).await?;
+            self.read_reply(self.timeout).await?;
+            let deadline = Instant::now() + self.timeout;
+            self.read_exact(chunk, deadline).await?;
+            address = address.wrapping_add(chunk.len() as u32);
+        }
+        Ok(())
+    }
+
+    /// Runs Write Memory to write `data` from `address` on, in as many commands of up
+    /// to 256 bytes as it takes.
+    ///
+    /// Flash is written in words, so the last block is padded with `0xFF`, the value of
+    /// erased flash, to a multiple of four bytes.  The flash has to be erased first.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`get`](Bootloader::get).  The bootloader turns down writes while read or
+    /// write protection is on, and of addresses that aren't writable.
+    pub async fn write_memory(&mut self, address: u32, data: &[u8]) -> Result<(), BootloaderError> {
+        let mut address = address;
+        for chunk in data.chunks(MAX_TRANSFER) {
+            // `usize::div_ceil` is newer than the minimum supported Rust version.
+            #[allow(clippy::manual_div_ceil)]
+            let words = (chunk.len() + 3) / 4;
+            let mut block = chunk.to_vec();
+            block.resize(words * 4, 0xFF);
+
+            self.command(WRITE_MEMORY).await?;
+            self.send_address(address).await?;
+            let mut frame = Vec::with_capacity(block.len() + 2);
+            frame.push((block.len() - 1) as u8);
+            frame.extend_from_slice(&block);
+            frame.push(checksum(&frame));
+            self.port.write_all(&frame).await?;
+            self.read_reply(self.timeout).await?;
+            address = address.wrapping_add(chunk.len() as u32);
+        }
+        Ok(())
+    }
+
+    /// Erases flash, with Extended Erase if the bootloader supports it and Erase
+    /// otherwise.  Which of the two it supports is found out with
+    /// [`get`](Bootloader::get), unless that was run already.
+    ///
+    /// ## Errors
+    ///
+    /// * `Unsupported` if the bootloader supports neither command, or banks are to be
+    ///   erased without Extended Erase.
+    /// * `Io` if pages are to be erased but none are given, or more, or higher
+    ///   numbered ones, than the command can take.
+    ///
+    /// Otherwise as for [`get`](Bootloader::get).  The bootloader turns down erasing
+    /// while write protection is on.
+    pub async fn erase(&mut self, erase: Erase<'_>) -> Result<(), BootloaderError> {
+        let commands = match self.commands.clone() {
+            Some(commands) => commands,
+            None => self.get().await?.commands,
+        };
+        let frame = if commands.contains(&EXTENDED_ERASE) {
+            let frame = extended_erase(erase)?;
+            self.command(EXTENDED_ERASE).await?;
+            frame
+        } else if commands.contains(&ERASE) {
+            let frame = standard_erase(erase)?;
+            self.command(ERASE).await?;
+            frame
+        } else {
+            return Err(BootloaderError::Unsupported);
+        };
+        self.port.write_all(&frame).await?;
+        self.read_reply(self.erase_timeout).await
+    }
+
+    /// Runs Go, to start the code at `address`: the application, from the start of
+    /// flash, or code written to RAM.  The bootloader is gone once it acknowledged.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`get`](Bootloader::get).  The bootloader turns down jumping to
+    /// addresses that don't hold code, and doing so while read protection is on.
+    pub async fn go(&mut self, address: u32) -> Result<(), BootloaderError> {
+        self.command(GO).await?;
+        self.send_address(address).await?;
+        self.commands = None;
+        Ok(())
+    }
+
+    /// Resets the chip by pulsing NRST, wired to `reset`, low.
+    async fn reset(&mut self, reset: Pin) -> Result<(), BootloaderError> {
+        self.drive(reset, false)?;
+        time::sleep(RESET_PULSE).await;
+        self.drive(reset, true)?;
+        time::sleep(STARTUP).await;
+        Ok(())
+    }
+
+    /// Drives `pin` high or low.
+    fn drive(&mut self, pin: Pin, high: bool) -> Result<(), BootloaderError> {
+        let asserted = high != pin.inverted;
+        match pin.control {
+            Control::DataTerminalReady => self.port.write_data_terminal_ready(asserted)?,
+            Control::RequestToSend => self.port.write_request_to_send(asserted)?,
+        }
+        Ok(())
+    }
+
+    /// Sends a command code with its complement, and waits for it to be acknowledged.
+    async fn command(&mut self, code: u8) -> Result<(), BootloaderError> {
+        self.port.write_all(&[code, !code]).await?;
+        self.read_reply(self.timeout).await
+    }
+
+    /// Sends an address with its checksum, and waits for it to be acknowledged.
+    async fn send_address(&mut self, address: u32) -> Result<(), BootloaderError> {
+        let bytes = address.to_be_bytes();
+        self.port.write_all(&bytes).await?;
+        self.port.write_all(&[checksum(&bytes)]).await?;
+        self.read_reply(self.timeout).await
+    }
+
+    /// Waits for ACK.
+    async fn read_reply(&mut self, timeout: Duration) -> Result<(), BootloaderError> {
+        match self.read_byte(Instant::now() + timeout).await? {
+            ACK => Ok(()),
+            NACK => Err(BootloaderError::Nack),
+            byte => Err(BootloaderError::UnexpectedReply(byte)),
+        }
+    }
+
+    async fn read_byte(&mut self, deadline: Instant) -> Result<u8, BootloaderError> {
+        match time::timeout_at(deadline, self.port.read_u8()).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(BootloaderError::Timeout),
+        }
+    }
+
+    async fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+        deadline: Instant,
+    ) -> Result<(), BootloaderError> {
+        match time::timeout_at(deadline, self.port.read_exact(buf)).await {
+            Ok(result) => result.map(drop).map_err(BootloaderError::from),
+            Err(_) => Err(BootloaderError::Timeout),
+        }
+    }
+}
+
+/// The XOR of `bytes`, that follows everything but commands.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, byte| sum ^ byte)
+}
+
+/// What follows the Erase command.
+fn standard_erase(erase: Erase<'_>) -> Result<Vec<u8>, BootloaderError> {
+    let pages = match erase {
+        Erase::All => return Ok(vec![0xFF, 0x00]),
+        Erase::Pages(pages) => pages,
+        Erase::Bank1 | Erase::Bank2 => return Err(BootloaderError::Unsupported),
+    };
+    if pages.is_empty() || pages.len() > 255 || pages.iter().any(|&page| page > 0xFF) {
+        return Err(invalid_pages());
+    }
+    let mut frame = vec![(pages.len() - 1) as u8];
+    frame.extend(pages.iter().map(|&page| page as u8));
+    frame.push(checksum(&frame));
+    Ok(frame)
+}
+
+/// What follows the Extended Erase command.
+fn extended_erase(erase: Erase<'_>) -> Result<Vec<u8>, BootloaderError> {
+    let mut frame = Vec::new();
+    match erase {
+        Erase::All => frame.extend_from_slice(&0xFFFFu16.to_be_bytes()),
+        Erase::Bank1 => frame.extend_from_slice(&0xFFFEu16.to_be_bytes()),
+        Erase::Bank2 => frame.extend_from_slice(&0xFFFDu16.to_be_bytes()),
+        Erase::Pages(pages) => {
+            // Counts from 0xFFF0 on stand for the special erases.
+            if pages.is_empty() || pages.len() > 0xFFF0 {
+                return Err(invalid_pages());
+            }
+            frame.extend_from_slice(&((pages.len() - 1) as u16).to_be_bytes());
+            for page in pages {
+                frame.extend_from_slice(&page.to_be_bytes());
+            }
+        }
+    }
+    frame.push(checksum(&frame));
+    Ok(frame)
+}
+
+fn invalid_pages() -> BootloaderError {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "too few, too many or too high numbered pages to erase",
+    )
+    .into()
+}
@@ -0,0 +1,173 @@
+//! Read timeouts for `SerialStream`, driven by tokio timers.
+//!
+//! The port itself is always opened in non-blocking mode, so the `VMIN`/`VTIME`
+//! settings of the terminal never come into play.  Instead, every reader keeps a
+//! [`ReadTimer`] that fails a read when nothing arrives within the total timeout, and
+//! that keeps collecting bytes until the line goes idle when an inter-byte timeout
+//! is set.
+//!
+//! `AsyncRead` has no notion of a read starting or being given up, so a read waiting
+//! for its first byte tells the two apart by when and why it is polled: a read that
+//! carries on is polled right after data arriving or the deadline passing woke it.
+//! Being polled for any other reason, or long after such a wake-up, means a new read
+//! started, e.g. after the last one was cancelled by `select!` or `timeout`, and the
+//! total timeout starts over.  A wake-up meant for a cancelled read is thus ignored by
+//! the next one.
+use super::{lock, SerialStream};
+
+use tokio::io::ReadBuf;
+use tokio::time::{Instant, Sleep};
+
+use std::future::Future;
+use std::io::{self, Result as IoResult};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+/// How soon after being woken a read must be polled to carry on.  A read polled later
+/// than that gets the full timeout again, which a busy runtime can only make longer.
+const WAKE_GRACE: Duration = Duration::from_millis(10);
+
+/// Timeouts applied when reading from a `SerialStream`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Timeouts {
+    /// How long to wait for the first byte of a read.  Zero waits forever.
+    pub(crate) total: Duration,
+    /// How long the line may stay idle before a read that already got some bytes completes.
+    pub(crate) inter_byte: Option<Duration>,
+}
+
+/// Per-reader state for applying [`Timeouts`].
+///
+/// With an inter-byte timeout, bytes are collected here until the read completes, so
+/// a read that is cancelled part way through hands them to the next read instead of
+/// losing them.
+#[derive(Debug, Default)]
+pub(crate) struct ReadTimer {
+    deadline: Option<Pin<Box<Sleep>>>,
+    idle: Option<Pin<Box<Sleep>>>,
+    buffered: Vec<u8>,
+    wakeup: Arc<Wakeup>,
+}
+
+/// Wakes the reading task on behalf of the port and the timers, noting when it did.
+#[derive(Debug, Default)]
+struct Wakeup {
+    woken_at: Mutex<Option<Instant>>,
+    task: Mutex<Option<Waker>>,
+}
+
+impl Wake for Wakeup {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        *lock(&self.woken_at) = Some(Instant::now());
+        if let Some(task) = lock(&self.task).as_ref() {
+            task.wake_by_ref();
+        }
+    }
+}
+
+impl ReadTimer {
+    pub(crate) fn poll_read(
+        &mut self,
+        port: &SerialStream,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let timeouts = port.timeouts();
+        if timeouts.total == Duration::from_secs(0)
+            && timeouts.inter_byte.is_none()
+            && self.buffered.is_empty()
+        {
+            return port.poll_read_priv(cx, buf);
+        }
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let woken = match lock(&self.wakeup.woken_at).take() {
+            Some(at) => at.elapsed() <= WAKE_GRACE,
+            None => false,
+        };
+        if !woken {
+            // Not just woken by us, so this is a new read.
+            self.deadline = None;
+        }
+        {
+            let mut task = lock(&self.wakeup.task);
+            match task.as_ref() {
+                Some(task) if task.will_wake(cx.waker()) => {}
+                _ => *task = Some(cx.waker().clone()),
+            }
+        }
+        let waker = Waker::from(Arc::clone(&self.wakeup));
+        let cx = &mut Context::from_waker(&waker);
+
+        loop {
+            let wanted = buf.remaining().saturating_sub(self.buffered.len());
+            if wanted > 0 {
+                let len = self.buffered.len();
+                self.buffered.resize(len + wanted, 0);
+                let mut read = ReadBuf::new(&mut self.buffered[len..]);
+                let result = port.poll_read_priv(cx, &mut read);
+                let bytes_read = read.filled().len();
+                self.buffered.truncate(len + bytes_read);
+
+                match result {
+                    Poll::Ready(Ok(())) if bytes_read == 0 => return self.complete(buf),
+                    Poll::Ready(Ok(())) => {
+                        self.deadline = None;
+                        match timeouts.inter_byte {
+                            Some(inter_byte) => {
+                                self.idle = Some(Box::pin(tokio::time::sleep(inter_byte)));
+                                continue;
+                            }
+                            None => return self.complete(buf),
+                        }
+                    }
+                    // Hand out what has been collected so far; a lasting error will be
+                    // reported again by the next read.
+                    Poll::Ready(Err(_)) if !self.buffered.is_empty() => return self.complete(buf),
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => {}
+                }
+            }
+
+            if !self.buffered.is_empty() {
+                if wanted > 0 {
+                    if let Some(idle) = self.idle.as_mut() {
+                        ready!(idle.as_mut().poll(cx));
+                    }
+                }
+                return self.complete(buf);
+            }
+
+            if timeouts.total == Duration::from_secs(0) {
+                return Poll::Pending;
+            }
+            let deadline = self
+                .deadline
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeouts.total)));
+            ready!(deadline.as_mut().poll(cx));
+            self.deadline = None;
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no data received before the read timeout",
+            )));
+        }
+    }
+
+    /// Move as much of the collected data as fits into `buf` and reset the timers.
+    fn complete(&mut self, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        let n = buf.remaining().min(self.buffered.len());
+        buf.put_slice(&self.buffered[..n]);
+        self.buffered.drain(..n);
+        self.deadline = None;
+        self.idle = None;
+        Poll::Ready(Ok(()))
+    }
+}
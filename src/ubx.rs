@@ -0,0 +1,134 @@
+//! The u-blox UBX binary protocol, mixed with NMEA 0183 on the same port.
+//!
+//! u-blox receivers interleave UBX frames with NMEA sentences on one UART.
+//! [`UbxCodec`] tells the two apart and decodes both into [`Message`]s, and
+//! [`Client`] uses it to send UBX-CFG messages and wait for the receiver to
+//! acknowledge them, while keeping whatever else arrives in the meantime.
+//!
+//! ```no_run
+//! use tokio_serial::ubx::{Client, Message};
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let port = SerialStream::open(&tokio_serial::new("/dev/ttyACM0", 9600))?;
+//! let mut client = Client::new(port);
+//! // UBX-CFG-RATE: a measurement every 200 ms, one navigation solution per
+//! // measurement, aligned to GPS time.
+//! client.configure(0x08, &[0xC8, 0x00, 0x01, 0x00, 0x01, 0x00]).await?;
+//! while let Some(message) = client.recv().await? {
+//!     match message {
+//!         Message::Ubx(class, id, payload) => println!("{:02X} {:02X} {:?}", class, id, payload),
+//!         Message::Nmea(sentence) => println!("{:?}", sentence),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module is only available with the `ubx` feature, which enables `nmea` as well.
+use crate::nmea::{NmeaError, Sentence};
+
+use bytes::BytesMut;
+
+use std::{fmt, io};
+
+mod client;
+mod codec;
+
+pub use self::client::Client;
+pub use self::codec::UbxCodec;
+
+/// The class of acknowledgement messages.
+pub const CLASS_ACK: u8 = 0x05;
+/// The class of configuration messages.
+pub const CLASS_CFG: u8 = 0x06;
+/// The id of UBX-ACK-ACK, which acknowledges a configuration message.
+pub const ACK_ACK: u8 = 0x01;
+/// The id of UBX-ACK-NAK, which rejects a configuration message.
+pub const ACK_NAK: u8 = 0x00;
+
+/// A message from a u-blox receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// A UBX frame, given by its class, its id and its payload.
+    Ubx(u8, u8, BytesMut),
+    /// An NMEA sentence.
+    Nmea(Sentence),
+}
+
+/// Computes the 8-bit Fletcher checksum of UBX frames over `data`, which runs from the
+/// class to the end of the payload.
+pub(crate) fn checksum(data: &[u8]) -> [u8; 2] {
+    let (mut a, mut b) = (0u8, 0u8);
+    for byte in data {
+        a = a.wrapping_add(*byte);
+        b = b.wrapping_add(a);
+    }
+    [a, b]
+}
+
+/// An error from a [`UbxCodec`] or a [`Client`].
+///
+/// The codec reports bad frames and sentences and carries on with the next one, so
+/// none of `BadChecksum`, `FrameTooLong` or `Nmea` end a
+/// [`SerialFramed`](crate::frame::SerialFramed) stream.
+#[derive(Debug)]
+pub enum UbxError {
+    /// A UBX frame failed its checksum.
+    BadChecksum,
+    /// A UBX frame had a longer payload than the maximum payload length.
+    FrameTooLong,
+    /// An NMEA sentence was bad.
+    Nmea(NmeaError),
+    /// The receiver rejected a configuration message with UBX-ACK-NAK.
+    Nak {
+        /// The class of the rejected message.
+        class: u8,
+        /// The id of the rejected message.
+        id: u8,
+    },
+    /// The receiver did not acknowledge a configuration message in time.
+    Timeout,
+    /// An I/O error occurred.
+    Io(io::Error),
+}
+
+impl fmt::Display for UbxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UbxError::BadChecksum => write!(f, "UBX frame checksum mismatch"),
+            UbxError::FrameTooLong => write!(f, "max payload length exceeded"),
+            UbxError::Nmea(err) => write!(f, "{}", err),
+            UbxError::Nak { class, id } => {
+                write!(f, "message {:02X} {:02X} rejected by receiver", class, id)
+            }
+            UbxError::Timeout => write!(f, "no acknowledgement from receiver"),
+            UbxError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for UbxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UbxError::Nmea(err) => Some(err),
+            UbxError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<NmeaError> for UbxError {
+    fn from(err: NmeaError) -> Self {
+        match err {
+            NmeaError::Io(err) => UbxError::Io(err),
+            err => UbxError::Nmea(err),
+        }
+    }
+}
+
+impl From<io::Error> for UbxError {
+    fn from(err: io::Error) -> Self {
+        UbxError::Io(err)
+    }
+}
@@ -0,0 +1,173 @@
+//! Configuring a u-blox receiver while reading what it sends.
+use super::{Message, UbxCodec, UbxError, ACK_ACK, ACK_NAK, CLASS_ACK, CLASS_CFG};
+use crate::SerialStream;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{self, Instant};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+/// A connection to a u-blox receiver.
+///
+/// The client reads the UBX frames and NMEA sentences the receiver sends with a
+/// [`UbxCodec`], and sends UBX frames to it.  [`configure`](Client::configure) sends a
+/// UBX-CFG message and waits for the UBX-ACK-ACK or UBX-ACK-NAK that answers it.
+/// Everything else that arrives while it waits, including bad frames and sentences, is
+/// kept and handed out by [`recv`](Client::recv) afterwards, so nothing is lost to
+/// configuring the receiver while it is running.
+///
+/// ```no_run
+/// use tokio_serial::ubx::{Client, UbxCodec};
+/// use tokio_serial::SerialStream;
+/// use std::time::Duration;
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let port = SerialStream::open(&tokio_serial::new("/dev/ttyACM0", 9600))?;
+/// let mut client = Client::new(port)
+///     .with_codec(UbxCodec::new().with_checksum_required(false))
+///     .with_timeout(Duration::from_millis(500));
+/// // UBX-CFG-MSG: turn off GxGSV on the current port.
+/// client.configure(0x01, &[0xF0, 0x03, 0x00]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Client {
+    port: SerialStream,
+    codec: UbxCodec,
+    buf: BytesMut,
+    timeout: Duration,
+    // Messages that arrived while waiting for an acknowledgement.
+    pending: VecDeque<Result<Message, UbxError>>,
+}
+
+impl Client {
+    /// Create a client on `port`, with a default [`UbxCodec`] and an acknowledgement
+    /// timeout of one second.
+    pub fn new(port: SerialStream) -> Client {
+        Self {
+            port,
+            codec: UbxCodec::new(),
+            buf: BytesMut::new(),
+            timeout: Duration::from_secs(1),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Sets the codec used to read from the receiver and send to it.
+    pub fn with_codec(mut self, codec: UbxCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Sets how long to wait for a configuration message to be acknowledged, counted
+    /// from the end of the message.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the codec used to read from the receiver and send to it.
+    pub fn codec(&self) -> &UbxCodec {
+        &self.codec
+    }
+
+    /// Returns how long to wait for a configuration message to be acknowledged.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns a reference to the underlying port.
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the underlying port.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the client, returning the underlying port.
+    ///
+    /// Bytes that were read but not decoded yet, and messages kept while waiting for an
+    /// acknowledgement, are lost.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Returns the next message from the receiver, or `None` once the port reports end
+    /// of file.
+    ///
+    /// Bad frames and sentences are returned as errors, and the next call carries on
+    /// with the message after them.
+    pub async fn recv(&mut self) -> Result<Option<Message>, UbxError> {
+        match self.pending.pop_front() {
+            Some(message) => message.map(Some),
+            None => self.read_message().await,
+        }
+    }
+
+    /// Sends a UBX frame without waiting for an answer.
+    pub async fn send(&mut self, class: u8, id: u8, payload: &[u8]) -> Result<(), UbxError> {
+        let mut frame = BytesMut::new();
+        self.codec.encode((class, id, payload), &mut frame)?;
+        self.port.write_all(&frame).await?;
+        Ok(())
+    }
+
+    /// Sends the UBX-CFG message `id` with `payload`, and waits for the receiver to
+    /// acknowledge it.
+    ///
+    /// ## Errors
+    ///
+    /// * `Nak` if the receiver rejected the message.
+    /// * `Timeout` if no acknowledgement arrived within the timeout.
+    /// * `FrameTooLong` if the payload is longer than the codec allows.
+    /// * `Io` if the port failed or reported end of file.
+    pub async fn configure(&mut self, id: u8, payload: &[u8]) -> Result<(), UbxError> {
+        self.send(CLASS_CFG, id, payload).await?;
+        self.port.drain().await?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let message = match time::timeout_at(deadline, self.read_message()).await {
+                Ok(Ok(Some(message))) => message,
+                Ok(Ok(None)) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(Err(UbxError::Io(err))) => return Err(UbxError::Io(err)),
+                Ok(Err(err)) => {
+                    self.pending.push_back(Err(err));
+                    continue;
+                }
+                Err(_) => return Err(UbxError::Timeout),
+            };
+            let acked = |payload: &BytesMut| payload[..] == [CLASS_CFG, id];
+            match message {
+                Message::Ubx(CLASS_ACK, ACK_ACK, ref payload) if acked(payload) => return Ok(()),
+                Message::Ubx(CLASS_ACK, ACK_NAK, ref payload) if acked(payload) => {
+                    return Err(UbxError::Nak {
+                        class: CLASS_CFG,
+                        id,
+                    })
+                }
+                message => self.pending.push_back(Ok(message)),
+            }
+        }
+    }
+
+    /// Reads from the port until a whole message has arrived.  Cancelling it loses
+    /// nothing, since what was read is kept in the buffer.
+    async fn read_message(&mut self) -> Result<Option<Message>, UbxError> {
+        loop {
+            if let Some(message) = self.codec.decode(&mut self.buf)? {
+                return Ok(Some(message));
+            }
+            if self.port.read_buf(&mut self.buf).await? == 0 {
+                return self.codec.decode_eof(&mut self.buf);
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Demultiplexing of UBX frames and NMEA sentences.
+use super::{checksum, Message, UbxError};
+use crate::nmea::{self, NmeaError};
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The two bytes that start every UBX frame.
+const SYNC: [u8; 2] = [0xB5, 0x62];
+/// The sync bytes, class, id and length in front of the payload.
+const HEADER_LENGTH: usize = 6;
+
+/// A codec for the UBX frames and NMEA sentences u-blox receivers send on the same
+/// port.
+///
+/// The decoder yields a [`Message::Ubx`] for every UBX frame, with its checksum checked
+/// and removed, and a [`Message::Nmea`] for every NMEA sentence, checked and parsed as
+/// by [`NmeaCodec`](crate::nmea::NmeaCodec).  Anything that starts neither is skipped,
+/// and so is a sentence cut short by a byte that can't be part of one, such as the
+/// start of a UBX frame.  A frame that fails its checksum or is overlong is reported
+/// with an error, and decoding carries on after its sync bytes, in case they were
+/// never the start of a frame.  Bad sentences are reported with [`UbxError::Nmea`].
+///
+/// The encoder takes the class, id and payload of a UBX frame and adds the sync bytes,
+/// the length and the checksum.
+///
+/// ```
+/// use bytes::BytesMut;
+/// use tokio_serial::ubx::UbxCodec;
+/// use tokio_util::codec::Encoder;
+///
+/// let mut codec = UbxCodec::new();
+/// let mut buf = BytesMut::new();
+/// // Poll UBX-CFG-RATE.
+/// codec.encode((0x06, 0x08, b""), &mut buf).unwrap();
+/// assert_eq!(&buf[..], [0xB5, 0x62, 0x06, 0x08, 0x00, 0x00, 0x0E, 0x30]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct UbxCodec {
+    max_payload_length: usize,
+    max_sentence_length: usize,
+    checksum_required: bool,
+}
+
+impl UbxCodec {
+    /// Create a codec that requires NMEA checksums and has no limit on the payload or
+    /// sentence length other than the protocols' own.
+    pub fn new() -> UbxCodec {
+        Self {
+            max_payload_length: usize::MAX,
+            max_sentence_length: usize::MAX,
+            checksum_required: true,
+        }
+    }
+
+    /// Sets the maximum length of the payload of a UBX frame.
+    pub fn with_max_payload_length(mut self, max_payload_length: usize) -> Self {
+        self.max_payload_length = max_payload_length;
+        self
+    }
+
+    /// Sets the maximum length of an NMEA sentence, from its start delimiter to its
+    /// checksum.
+    pub fn with_max_sentence_length(mut self, max_sentence_length: usize) -> Self {
+        self.max_sentence_length = max_sentence_length;
+        self
+    }
+
+    /// Sets whether NMEA sentences without a checksum are rejected.  Checksums that are
+    /// present are always verified.
+    pub fn with_checksum_required(mut self, checksum_required: bool) -> Self {
+        self.checksum_required = checksum_required;
+        self
+    }
+
+    /// Returns the maximum length of the payload of a UBX frame.
+    pub fn max_payload_length(&self) -> usize {
+        self.max_payload_length
+    }
+
+    /// Returns the maximum length of an NMEA sentence.
+    pub fn max_sentence_length(&self) -> usize {
+        self.max_sentence_length
+    }
+
+    /// Returns whether NMEA sentences without a checksum are rejected.
+    pub fn checksum_required(&self) -> bool {
+        self.checksum_required
+    }
+
+    fn decode_ubx(&self, src: &mut BytesMut) -> Result<Option<Message>, UbxError> {
+        if src.len() < HEADER_LENGTH {
+            return Ok(None);
+        }
+        let len = usize::from(u16::from_le_bytes([src[4], src[5]]));
+        if len > self.max_payload_length {
+            src.advance(SYNC.len());
+            return Err(UbxError::FrameTooLong);
+        }
+        let frame_len = HEADER_LENGTH + len + 2;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+        if checksum(&src[SYNC.len()..HEADER_LENGTH + len]) != src[HEADER_LENGTH + len..frame_len] {
+            src.advance(SYNC.len());
+            return Err(UbxError::BadChecksum);
+        }
+
+        let mut frame = src.split_to(frame_len);
+        let (class, id) = (frame[2], frame[3]);
+        frame.advance(HEADER_LENGTH);
+        frame.truncate(len);
+        Ok(Some(Message::Ubx(class, id, frame)))
+    }
+
+    /// Decodes the sentence at the start of `src`, or returns `None` if there isn't a
+    /// whole one yet.  `Some(Ok(None))` means the sentence was cut short and has been
+    /// skipped.
+    fn decode_nmea(&self, src: &mut BytesMut) -> Option<Result<Option<Message>, UbxError>> {
+        let end = src
+            .iter()
+            .skip(1)
+            .position(|&b| !matches!(b, 0x20..=0x7E | b'\r'));
+        let end = match end {
+            Some(at) => at + 1,
+            // Leave room for the CR.
+            None if src.len() > self.max_sentence_length.saturating_add(1) => {
+                src.advance(1);
+                return Some(Err(NmeaError::SentenceTooLong.into()));
+            }
+            None => return None,
+        };
+        if src[end] != b'\n' {
+            src.advance(end);
+            return Some(Ok(None));
+        }
+
+        let line = src.split_to(end);
+        src.advance(1);
+        let sentence = line.strip_suffix(b"\r").unwrap_or(&line);
+        if sentence.len() > self.max_sentence_length {
+            return Some(Err(NmeaError::SentenceTooLong.into()));
+        }
+        let sentence = std::str::from_utf8(sentence).expect("sentence is printable ASCII");
+        Some(
+            nmea::parse_sentence(sentence, self.checksum_required)
+                .map(|sentence| Some(Message::Nmea(sentence)))
+                .map_err(UbxError::from),
+        )
+    }
+}
+
+impl Default for UbxCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for UbxCodec {
+    type Item = Message;
+    type Error = UbxError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>, UbxError> {
+        loop {
+            match src
+                .iter()
+                .position(|b| *b == SYNC[0] || *b == b'$' || *b == b'!')
+            {
+                Some(at) => src.advance(at),
+                None => {
+                    src.clear();
+                    return Ok(None);
+                }
+            }
+
+            if src[0] == SYNC[0] {
+                match src.get(1) {
+                    Some(&byte) if byte == SYNC[1] => return self.decode_ubx(src),
+                    Some(_) => src.advance(1),
+                    None => return Ok(None),
+                }
+            } else {
+                match self.decode_nmea(src) {
+                    Some(Ok(None)) => {}
+                    Some(result) => return result,
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Message>, UbxError> {
+        let message = self.decode(src)?;
+        if message.is_none() {
+            // Whatever is left is a frame or sentence that was cut short.
+            src.clear();
+        }
+        Ok(message)
+    }
+}
+
+impl<T> Encoder<(u8, u8, T)> for UbxCodec
+where
+    T: AsRef<[u8]>,
+{
+    type Error = UbxError;
+
+    fn encode(&mut self, frame: (u8, u8, T), dst: &mut BytesMut) -> Result<(), UbxError> {
+        let (class, id, payload) = frame;
+        let payload = payload.as_ref();
+        if payload.len() > self.max_payload_length || payload.len() > usize::from(u16::MAX) {
+            return Err(UbxError::FrameTooLong);
+        }
+
+        dst.reserve(HEADER_LENGTH + payload.len() + 2);
+        let start = dst.len();
+        dst.put_slice(&SYNC);
+        dst.put_u8(class);
+        dst.put_u8(id);
+        dst.put_u16_le(payload.len() as u16);
+        dst.put_slice(payload);
+        let checksum = checksum(&dst[start + SYNC.len()..]);
+        dst.put_slice(&checksum);
+        Ok(())
+    }
+}
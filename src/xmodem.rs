@@ -0,0 +1,571 @@
+//! XMODEM file transfers, with the original checksum, CRC-16 and 1K blocks.
+//!
+//! [`send`] and [`receive`] transfer data over a port with the default settings, while
+//! [`Xmodem`] changes them and reports progress as blocks are acknowledged.  Both work
+//! on a [`SerialStream`](crate::SerialStream) or anything else that implements
+//! `AsyncRead` and `AsyncWrite`.
+//!
+//! ```no_run
+//! use tokio_serial::xmodem::Xmodem;
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! let mut image = tokio::fs::File::open("firmware.bin").await?;
+//! let sent = Xmodem::new()
+//!     .with_1k(true)
+//!     .send(&mut port, &mut image, |progress| {
+//!         println!("{} bytes sent", progress.bytes)
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The receiver decides between the checksum and CRC-16, by starting the transfer
+//! with NAK or `C`.  A receiver asks for CRC-16 a few times and then falls back to the
+//! checksum, so it works with senders that only know the original protocol.  XMODEM
+//! has no way of telling where the data ends, so the receiver writes the last block
+//! with its padding.
+//!
+//! Either side gives up on a transfer by sending two CANs, and gives up when it
+//! receives them.
+//!
+//! This module is only available with the `xmodem` feature.
+use crate::crc::Crc;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{self, Instant};
+
+use std::time::Duration;
+use std::{fmt, io};
+
+/// Starts a block of 128 bytes.
+pub(crate) const SOH: u8 = 0x01;
+/// Starts a block of 1024 bytes.
+pub(crate) const STX: u8 = 0x02;
+/// Ends the transfer.
+pub(crate) const EOT: u8 = 0x04;
+pub(crate) const ACK: u8 = 0x06;
+pub(crate) const NAK: u8 = 0x15;
+/// Cancels the transfer, when sent twice in a row.
+pub(crate) const CAN: u8 = 0x18;
+/// Starts a transfer with CRC-16 rather than the checksum.
+pub(crate) const CRC: u8 = b'C';
+
+/// How many times a receiver asks for CRC-16 before falling back to the checksum.
+const CRC_ATTEMPTS: usize = 3;
+
+/// Sends `data` over `port` with the default settings, and returns how many bytes were
+/// sent.
+///
+/// See [`Xmodem::send`].
+pub async fn send<P, R>(port: &mut P, data: &mut R) -> Result<u64, XmodemError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    Xmodem::new().send(port, data, |_| {}).await
+}
+
+/// Receives data from `port` with the default settings, writes it to `data`, and
+/// returns how many bytes were written.
+///
+/// See [`Xmodem::receive`].
+pub async fn receive<P, W>(port: &mut P, data: &mut W) -> Result<u64, XmodemError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    Xmodem::new().receive(port, data, |_| {}).await
+}
+
+/// How far a transfer has got, passed to the callback of [`Xmodem::send`] and
+/// [`Xmodem::receive`] after every block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// How many blocks were transferred.
+    pub blocks: u64,
+    /// How many bytes were transferred, counting the padding of a received block but
+    /// not that of a sent one.
+    pub bytes: u64,
+    /// The size of the file, if the sender announced it, as YMODEM senders do.
+    pub total: Option<u64>,
+}
+
+/// An error from an XMODEM transfer.
+#[derive(Debug)]
+pub enum XmodemError {
+    /// The other side cancelled the transfer.
+    Cancelled,
+    /// The other side didn't start the transfer, or stopped answering, after every
+    /// retry.
+    Timeout,
+    /// A block was rejected, or arrived damaged, more often than the retries allow.
+    TooManyErrors,
+    /// The sender skipped a block.
+    OutOfSequence,
+    /// An I/O error occurred, or the port reported end of file.
+    Io(io::Error),
+}
+
+impl fmt::Display for XmodemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmodemError::Cancelled => write!(f, "transfer cancelled by the other side"),
+            XmodemError::Timeout => write!(f, "no answer from the other side"),
+            XmodemError::TooManyErrors => write!(f, "too many errors"),
+            XmodemError::OutOfSequence => write!(f, "block out of sequence"),
+            XmodemError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for XmodemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XmodemError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for XmodemError {
+    fn from(err: io::Error) -> Self {
+        XmodemError::Io(err)
+    }
+}
+
+/// The settings of an XMODEM transfer.
+///
+/// ```
+/// use tokio_serial::xmodem::Xmodem;
+/// use std::time::Duration;
+///
+/// let xmodem = Xmodem::new()
+///     .with_1k(true)
+///     .with_timeout(Duration::from_secs(3))
+///     .with_retries(5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Xmodem {
+    one_k: bool,
+    crc: bool,
+    timeout: Duration,
+    retries: usize,
+    padding: u8,
+}
+
+impl Xmodem {
+    /// Create settings with the defaults of the original protocol: 128-byte blocks,
+    /// CRC-16 if the receiver asks for it, a timeout of 10 seconds, 10 retries, and
+    /// blocks padded with Ctrl-Z.
+    pub fn new() -> Xmodem {
+        Self {
+            one_k: false,
+            crc: true,
+            timeout: Duration::from_secs(10),
+            retries: 10,
+            padding: 0x1A,
+        }
+    }
+
+    /// Sets whether to send 1024-byte blocks, as XMODEM-1K does.
+    ///
+    /// 1K blocks are only sent to receivers that asked for CRC-16, and the data that
+    /// fits in 128 bytes at the end is sent in a short block.
+    pub fn with_1k(mut self, one_k: bool) -> Self {
+        self.one_k = one_k;
+        self
+    }
+
+    /// Sets whether a receiver asks for CRC-16 before falling back to the checksum, or
+    /// only ever asks for the checksum.
+    pub fn with_crc(mut self, crc: bool) -> Self {
+        self.crc = crc;
+        self
+    }
+
+    /// Sets how long to wait for the other side to answer, or a block to arrive.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a block is sent, or asked for, again before giving up.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the byte that fills up the last block.
+    pub fn with_padding(mut self, padding: u8) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns whether 1024-byte blocks are sent.
+    pub fn one_k(&self) -> bool {
+        self.one_k
+    }
+
+    /// Returns whether a receiver asks for CRC-16.
+    pub fn crc(&self) -> bool {
+        self.crc
+    }
+
+    /// Returns how long to wait for the other side.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns how many times a block is sent, or asked for, again.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Returns the byte that fills up the last block.
+    pub fn padding(&self) -> u8 {
+        self.padding
+    }
+
+    /// Waits for the receiver on `port` to start the transfer, sends it all of `data`,
+    /// and returns how many bytes were sent.  `progress` is called after every block
+    /// the receiver acknowledged.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the receiver cancelled the transfer.
+    /// * `Timeout` if the receiver never started the transfer, or stopped answering.
+    /// * `TooManyErrors` if the receiver kept rejecting a block.
+    /// * `Io` if reading `data`, or reading from or writing to the port, failed.
+    ///
+    /// The receiver is told the transfer was cancelled, unless it cancelled it or an
+    /// I/O error occurred.
+    pub async fn send<P, R, F>(
+        &self,
+        port: &mut P,
+        data: &mut R,
+        mut progress: F,
+    ) -> Result<u64, XmodemError>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+        F: FnMut(Progress),
+    {
+        let result = self.send_all(port, data, &mut progress).await;
+        abort_on_error(port, result).await
+    }
+
+    /// Starts a transfer on `port`, writes what is received to `data`, and returns how
+    /// many bytes were written.  `progress` is called after every block received.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the sender cancelled the transfer.
+    /// * `Timeout` if the sender never answered, or stopped sending.
+    /// * `TooManyErrors` if a block kept arriving damaged.
+    /// * `OutOfSequence` if the sender skipped a block.
+    /// * `Io` if writing `data`, or reading from or writing to the port, failed.
+    ///
+    /// The sender is told the transfer was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    pub async fn receive<P, W, F>(
+        &self,
+        port: &mut P,
+        data: &mut W,
+        mut progress: F,
+    ) -> Result<u64, XmodemError>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let result = self.receive_all(port, data, &mut progress).await;
+        abort_on_error(port, result).await
+    }
+
+    async fn send_all<P, R, F>(
+        &self,
+        port: &mut P,
+        data: &mut R,
+        progress: &mut F,
+    ) -> Result<u64, XmodemError>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+        F: FnMut(Progress),
+    {
+        let crc = self.wait_for_start(port).await?;
+        let block_size = if self.one_k && crc { 1024 } else { 128 };
+        let mut buf = vec![0u8; block_size];
+        let mut done = Progress {
+            blocks: 0,
+            bytes: 0,
+            total: None,
+        };
+        loop {
+            let len = fill(data, &mut buf).await?;
+            if len == 0 {
+                break;
+            }
+            let size = if len <= 128 { 128 } else { block_size };
+            let block =
+                self.encode_block(done.blocks.wrapping_add(1) as u8, &buf[..len], size, crc);
+            self.transmit(port, &block).await?;
+            done.blocks += 1;
+            done.bytes += len as u64;
+            progress(done);
+        }
+        self.transmit(port, &[EOT]).await?;
+        Ok(done.bytes)
+    }
+
+    /// Waits for the receiver to ask for the first block, and returns whether it asked
+    /// for CRC-16.
+    async fn wait_for_start<P>(&self, port: &mut P) -> Result<bool, XmodemError>
+    where
+        P: AsyncRead + Unpin,
+    {
+        for _ in 0..=self.retries {
+            let deadline = Instant::now() + self.timeout;
+            while let Some(byte) = read_byte(port, deadline).await? {
+                match byte {
+                    NAK => return Ok(false),
+                    CRC => return Ok(true),
+                    CAN if read_byte(port, deadline).await? == Some(CAN) => {
+                        return Err(XmodemError::Cancelled)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Err(XmodemError::Timeout)
+    }
+
+    /// Sends `block` until the receiver acknowledges it.
+    pub(crate) async fn transmit<P>(&self, port: &mut P, block: &[u8]) -> Result<(), XmodemError>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut error = XmodemError::Timeout;
+        for _ in 0..=self.retries {
+            port.write_all(block).await?;
+            port.flush().await?;
+            let deadline = Instant::now() + self.timeout;
+            error = loop {
+                match read_byte(port, deadline).await? {
+                    Some(ACK) => return Ok(()),
+                    Some(NAK) => break XmodemError::TooManyErrors,
+                    Some(CAN) if read_byte(port, deadline).await? == Some(CAN) => {
+                        return Err(XmodemError::Cancelled)
+                    }
+                    Some(_) => {}
+                    None => break XmodemError::Timeout,
+                }
+            };
+        }
+        Err(error)
+    }
+
+    /// Encodes a block numbered `number` holding `data`, padded to `size` bytes.
+    pub(crate) fn encode_block(&self, number: u8, data: &[u8], size: usize, crc: bool) -> Vec<u8> {
+        let mut block = Vec::with_capacity(size + 5);
+        block.push(if size == 1024 { STX } else { SOH });
+        block.push(number);
+        block.push(!number);
+        block.extend_from_slice(data);
+        block.resize(3 + size, self.padding);
+        if crc {
+            let check = Crc::CRC_16_XMODEM.checksum(&block[3..]) as u16;
+            block.extend_from_slice(&check.to_be_bytes());
+        } else {
+            block.push(checksum(&block[3..]));
+        }
+        block
+    }
+
+    async fn receive_all<P, W, F>(
+        &self,
+        port: &mut P,
+        data: &mut W,
+        progress: &mut F,
+    ) -> Result<u64, XmodemError>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let mut crc = self.crc;
+        let mut started = false;
+        let mut errors = 0;
+        let mut done = Progress {
+            blocks: 0,
+            bytes: 0,
+            total: None,
+        };
+        let mut reply = if crc { CRC } else { NAK };
+        loop {
+            port.write_all(&[reply]).await?;
+            port.flush().await?;
+
+            let error = match self.read_block(port, crc).await? {
+                Block::Data(number, block) => {
+                    started = true;
+                    let expected = done.blocks.wrapping_add(1) as u8;
+                    if number == expected {
+                        data.write_all(&block).await?;
+                        done.blocks += 1;
+                        done.bytes += block.len() as u64;
+                        progress(done);
+                    } else if number != expected.wrapping_sub(1) {
+                        return Err(XmodemError::OutOfSequence);
+                    }
+                    // The sender missed our ACK if it sent the last block again.
+                    errors = 0;
+                    reply = ACK;
+                    continue;
+                }
+                Block::End => {
+                    port.write_all(&[ACK]).await?;
+                    port.flush().await?;
+                    data.flush().await?;
+                    return Ok(done.bytes);
+                }
+                Block::Damaged => XmodemError::TooManyErrors,
+                Block::Missing => XmodemError::Timeout,
+            };
+
+            errors += 1;
+            if errors > self.retries {
+                return Err(error);
+            }
+            reply = if started {
+                NAK
+            } else {
+                if crc && errors >= CRC_ATTEMPTS {
+                    crc = false;
+                }
+                if crc {
+                    CRC
+                } else {
+                    NAK
+                }
+            };
+        }
+    }
+
+    /// Reads the next block, skipping line noise before it.
+    pub(crate) async fn read_block<P>(&self, port: &mut P, crc: bool) -> Result<Block, XmodemError>
+    where
+        P: AsyncRead + Unpin,
+    {
+        let deadline = Instant::now() + self.timeout;
+        let size = loop {
+            match read_byte(port, deadline).await? {
+                Some(SOH) => break 128,
+                Some(STX) => break 1024,
+                Some(EOT) => return Ok(Block::End),
+                Some(CAN) if read_byte(port, deadline).await? == Some(CAN) => {
+                    return Err(XmodemError::Cancelled)
+                }
+                Some(_) => {}
+                None => return Ok(Block::Missing),
+            }
+        };
+
+        let check_len = if crc { 2 } else { 1 };
+        let mut block = vec![0u8; 2 + size + check_len];
+        let deadline = Instant::now() + self.timeout;
+        match time::timeout_at(deadline, port.read_exact(&mut block)).await {
+            Ok(result) => result?,
+            Err(_) => return Ok(Block::Damaged),
+        };
+
+        let (number, inverse) = (block[0], block[1]);
+        let (payload, check) = block[2..].split_at(size);
+        let valid = if crc {
+            Crc::CRC_16_XMODEM.checksum(payload) as u16 == u16::from_be_bytes([check[0], check[1]])
+        } else {
+            checksum(payload) == check[0]
+        };
+        if number != !inverse || !valid {
+            log::debug!("dropping damaged block");
+            return Ok(Block::Damaged);
+        }
+        block.truncate(2 + size);
+        block.drain(..2);
+        Ok(Block::Data(number, block))
+    }
+}
+
+impl Default for Xmodem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the sender sent.
+pub(crate) enum Block {
+    /// A block, with its number.
+    Data(u8, Vec<u8>),
+    /// EOT.
+    End,
+    /// A block that was cut short or failed its check.
+    Damaged,
+    /// Nothing, in time.
+    Missing,
+}
+
+/// The checksum of the original protocol: the sum of the bytes.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Reads a byte, or returns `None` if none arrives by `deadline`.
+pub(crate) async fn read_byte<P>(port: &mut P, deadline: Instant) -> io::Result<Option<u8>>
+where
+    P: AsyncRead + Unpin,
+{
+    match time::timeout_at(deadline, port.read_u8()).await {
+        Ok(result) => result.map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Reads from `data` until `buf` is full or there is nothing left, and returns how
+/// much was read.
+pub(crate) async fn fill<R>(data: &mut R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len = 0;
+    while len < buf.len() {
+        match data.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+/// Tells the other side the transfer was cancelled, if it failed for a reason the other
+/// side doesn't know about.
+pub(crate) async fn abort_on_error<P, T>(
+    port: &mut P,
+    result: Result<T, XmodemError>,
+) -> Result<T, XmodemError>
+where
+    P: AsyncWrite + Unpin,
+{
+    match result {
+        Err(XmodemError::Cancelled) | Err(XmodemError::Io(_)) | Ok(_) => result,
+        Err(err) => {
+            // The transfer failed already, so there's no point reporting this as well.
+            let _ = port.write_all(&[CAN, CAN]).await;
+            let _ = port.flush().await;
+            Err(err)
+        }
+    }
+}
@@ -0,0 +1,681 @@
+//! YMODEM batch file transfers, and the streaming YMODEM-G.
+//!
+//! YMODEM sends files as XMODEM does, with CRC-16 and 1K blocks, but announces each
+//! file with a block 0 that carries its name, size and modification time, and sends
+//! any number of files in one batch.  [`Ymodem::sender`] and [`Ymodem::receiver`] start
+//! a batch on a [`SerialStream`](crate::SerialStream) or anything else that implements
+//! `AsyncRead` and `AsyncWrite`.
+//!
+//! ```no_run
+//! use tokio_serial::ymodem::{FileInfo, Ymodem};
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! let mut image = tokio::fs::File::open("firmware.bin").await?;
+//! let size = image.metadata().await?.len();
+//!
+//! let mut batch = Ymodem::new().sender(&mut port);
+//! let info = FileInfo::new("firmware.bin").with_size(size);
+//! batch
+//!     .send(&info, &mut image, |progress| {
+//!         println!("{} of {} bytes sent", progress.bytes, size)
+//!     })
+//!     .await?;
+//! batch.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The receiver chooses YMODEM-G by starting each file with `G` rather than `C`.  The
+//! sender then sends blocks without waiting for them to be acknowledged, and the
+//! receiver gives up on the first damaged block, so it is only worth it on links that
+//! don't lose data, such as USB.  The sender follows whichever the receiver asks for.
+//!
+//! Errors are reported as [`XmodemError`]s.  As with XMODEM, either side gives up on a
+//! batch by sending two CANs, and gives up when it receives them.
+//!
+//! This module is only available with the `ymodem` feature, which enables `xmodem` as
+//! well.
+use crate::xmodem::{self, Block, Progress, Xmodem, XmodemError, ACK, CAN, CRC, EOT, NAK};
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Starts a YMODEM-G transfer, in place of [`CRC`].
+const STREAM: u8 = b'G';
+
+/// What is known about a file, as announced in block 0.
+///
+/// ```
+/// use tokio_serial::ymodem::FileInfo;
+/// use std::time::SystemTime;
+///
+/// let info = FileInfo::new("log.txt")
+///     .with_size(1234)
+///     .with_modified(SystemTime::now());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    name: String,
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    mode: Option<u32>,
+}
+
+impl FileInfo {
+    /// Describe a file called `name`, of unknown size.
+    ///
+    /// By convention, the name has no directory, or one separated by `/`.
+    pub fn new(name: impl Into<String>) -> FileInfo {
+        Self {
+            name: name.into(),
+            size: None,
+            modified: None,
+            mode: None,
+        }
+    }
+
+    /// Sets the size of the file in bytes, which lets the receiver drop the padding of
+    /// the last block.
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Sets when the file was last modified, to the second.
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Sets the Unix permission bits of the file.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Returns the name of the file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the file in bytes, if it is known.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Returns when the file was last modified, if it is known.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Returns the Unix permission bits of the file, if they are known.
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Encodes the contents of block 0: the name, then the size, the modification time
+    /// in octal seconds and the mode in octal, as far as they are known.
+    pub(crate) fn encode(&self) -> io::Result<Vec<u8>> {
+        if self.name.is_empty() || self.name.contains('\0') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file name is empty or contains NUL",
+            ));
+        }
+        let mut header = self.name.clone().into_bytes();
+        header.push(0);
+        let modified = self.modified.map(|modified| {
+            modified
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+        // Later fields can only be given along with the earlier ones.
+        if self.size.is_some() || modified.is_some() || self.mode.is_some() {
+            header.extend_from_slice(self.size.unwrap_or(0).to_string().as_bytes());
+        }
+        if modified.is_some() || self.mode.is_some() {
+            header.extend_from_slice(format!(" {:o}", modified.unwrap_or(0)).as_bytes());
+        }
+        if let Some(mode) = self.mode {
+            header.extend_from_slice(format!(" {:o}", mode).as_bytes());
+        }
+        if header.len() > 1024 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file name is too long",
+            ));
+        }
+        Ok(header)
+    }
+
+    /// Decodes the contents of block 0, returning `None` if they don't make sense.
+    /// Zeros stand for unknown values.
+    pub(crate) fn decode(block: &[u8]) -> Option<FileInfo> {
+        let end = block.iter().position(|b| *b == 0)?;
+        let name = String::from_utf8_lossy(&block[..end]).into_owned();
+        let rest = &block[end + 1..];
+        let rest = &rest[..rest.iter().position(|b| *b == 0).unwrap_or(rest.len())];
+        let mut fields = std::str::from_utf8(rest).ok()?.split_whitespace();
+
+        let size = fields.next().map(str::parse).transpose().ok()?;
+        let modified = fields
+            .next()
+            .map(|field| u64::from_str_radix(field, 8))
+            .transpose()
+            .ok()?;
+        let modified = match modified.filter(|secs| *secs != 0) {
+            Some(secs) => Some(UNIX_EPOCH.checked_add(Duration::from_secs(secs))?),
+            None => None,
+        };
+        let mode = fields
+            .next()
+            .map(|field| u32::from_str_radix(field, 8))
+            .transpose()
+            .ok()?;
+        Some(FileInfo {
+            name,
+            size,
+            modified,
+            mode: mode.filter(|mode| *mode != 0),
+        })
+    }
+}
+
+/// The settings of a YMODEM batch.
+///
+/// ```
+/// use tokio_serial::ymodem::Ymodem;
+/// use std::time::Duration;
+///
+/// let ymodem = Ymodem::new()
+///     .with_streaming(true)
+///     .with_timeout(Duration::from_secs(3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ymodem {
+    xmodem: Xmodem,
+    streaming: bool,
+}
+
+impl Ymodem {
+    /// Create settings with the defaults of the protocol: 1K blocks, plain YMODEM
+    /// rather than YMODEM-G, a timeout of 10 seconds and 10 retries.
+    pub fn new() -> Ymodem {
+        Self {
+            xmodem: Xmodem::new().with_1k(true),
+            streaming: false,
+        }
+    }
+
+    /// Sets whether to send 1024-byte blocks, or only 128-byte ones.
+    pub fn with_1k(mut self, one_k: bool) -> Self {
+        self.xmodem = self.xmodem.with_1k(one_k);
+        self
+    }
+
+    /// Sets whether a receiver asks for YMODEM-G.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Sets how long to wait for the other side to answer, or a block to arrive.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.xmodem = self.xmodem.with_timeout(timeout);
+        self
+    }
+
+    /// Sets how many times a block is sent, or asked for, again before giving up.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.xmodem = self.xmodem.with_retries(retries);
+        self
+    }
+
+    /// Returns whether 1024-byte blocks are sent.
+    pub fn one_k(&self) -> bool {
+        self.xmodem.one_k()
+    }
+
+    /// Returns whether a receiver asks for YMODEM-G.
+    pub fn streaming(&self) -> bool {
+        self.streaming
+    }
+
+    /// Returns how long to wait for the other side.
+    pub fn timeout(&self) -> Duration {
+        self.xmodem.timeout()
+    }
+
+    /// Returns how many times a block is sent, or asked for, again.
+    pub fn retries(&self) -> usize {
+        self.xmodem.retries()
+    }
+
+    /// Starts a batch of files to send over `port`.
+    pub fn sender<P>(&self, port: P) -> Sender<P>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        Sender {
+            port,
+            config: self.clone(),
+        }
+    }
+
+    /// Starts a batch of files to receive from `port`.
+    pub fn receiver<P>(&self, port: P) -> Receiver<P>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        Receiver {
+            port,
+            config: self.clone(),
+            size: None,
+        }
+    }
+}
+
+impl Default for Ymodem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends a batch of files, created by [`Ymodem::sender`].
+///
+/// Send each file with [`send`](Sender::send), then end the batch with
+/// [`finish`](Sender::finish).
+#[derive(Debug)]
+pub struct Sender<P> {
+    port: P,
+    config: Ymodem,
+}
+
+impl<P> Sender<P>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Waits for the receiver to ask for a file, sends it `info` and all of `data`,
+    /// and returns how many bytes were sent.  `progress` is called after every block,
+    /// once the receiver acknowledged it unless it asked for YMODEM-G.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the receiver cancelled the batch.
+    /// * `Timeout` if the receiver never asked for the file, or stopped answering.
+    /// * `TooManyErrors` if the receiver kept rejecting a block.
+    /// * `Io` if reading `data`, or reading from or writing to the port, failed, or
+    ///   `info` has an empty name or one too long for block 0.
+    ///
+    /// The receiver is told the batch was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    pub async fn send<R, F>(
+        &mut self,
+        info: &FileInfo,
+        data: &mut R,
+        mut progress: F,
+    ) -> Result<u64, XmodemError>
+    where
+        R: AsyncRead + Unpin,
+        F: FnMut(Progress),
+    {
+        let result = self.send_file(info, data, &mut progress).await;
+        xmodem::abort_on_error(&mut self.port, result).await
+    }
+
+    /// Waits for the receiver to ask for another file, and tells it there are none.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`send`](Sender::send).
+    pub async fn finish(mut self) -> Result<P, XmodemError> {
+        let result = self.send_header(&[]).await;
+        xmodem::abort_on_error(&mut self.port, result).await?;
+        Ok(self.port)
+    }
+
+    /// Gets a reference to the port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Gets a mutable reference to the port.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Gives the port back, without ending the batch.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    async fn send_file<R, F>(
+        &mut self,
+        info: &FileInfo,
+        data: &mut R,
+        progress: &mut F,
+    ) -> Result<u64, XmodemError>
+    where
+        R: AsyncRead + Unpin,
+        F: FnMut(Progress),
+    {
+        let header = info.encode()?;
+        self.send_header(&header).await?;
+        let streaming = self.wait_for_start().await?;
+
+        let xmodem = self.config.xmodem.clone();
+        let mut buf = vec![0u8; if xmodem.one_k() { 1024 } else { 128 }];
+        let mut done = Progress {
+            blocks: 0,
+            bytes: 0,
+            total: info.size,
+        };
+        loop {
+            let len = xmodem::fill(data, &mut buf).await?;
+            if len == 0 {
+                break;
+            }
+            let size = if len <= 128 { 128 } else { 1024 };
+            let block =
+                xmodem.encode_block(done.blocks.wrapping_add(1) as u8, &buf[..len], size, true);
+            if streaming {
+                self.port.write_all(&block).await?;
+                self.check_cancelled().await?;
+            } else {
+                xmodem.transmit(&mut self.port, &block).await?;
+            }
+            done.blocks += 1;
+            done.bytes += len as u64;
+            progress(done);
+        }
+        xmodem.transmit(&mut self.port, &[EOT]).await?;
+        Ok(done.bytes)
+    }
+
+    /// Waits for the receiver to ask for a file, and sends it block 0 with `header`,
+    /// which is empty at the end of the batch.
+    async fn send_header(&mut self, header: &[u8]) -> Result<(), XmodemError> {
+        let streaming = self.wait_for_start().await?;
+        // Block 0 is padded with NULs, not the padding of the data.
+        let size = if header.len() <= 128 { 128 } else { 1024 };
+        let mut padded = header.to_vec();
+        padded.resize(size, 0);
+        let block = self.config.xmodem.encode_block(0, &padded, size, true);
+        if streaming {
+            self.port.write_all(&block).await?;
+            self.port.flush().await?;
+            Ok(())
+        } else {
+            self.config.xmodem.transmit(&mut self.port, &block).await
+        }
+    }
+
+    /// Waits for the receiver to ask for the next block 0 or block 1, and returns
+    /// whether it asked for YMODEM-G.
+    async fn wait_for_start(&mut self) -> Result<bool, XmodemError> {
+        let timeout = self.config.timeout();
+        for _ in 0..=self.config.retries() {
+            let deadline = Instant::now() + timeout;
+            while let Some(byte) = xmodem::read_byte(&mut self.port, deadline).await? {
+                match byte {
+                    CRC => return Ok(false),
+                    STREAM => return Ok(true),
+                    CAN if xmodem::read_byte(&mut self.port, deadline).await? == Some(CAN) => {
+                        return Err(XmodemError::Cancelled)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Err(XmodemError::Timeout)
+    }
+
+    /// Checks whether the receiver cancelled a YMODEM-G transfer, without waiting for
+    /// anything that hasn't arrived yet.
+    async fn check_cancelled(&mut self) -> Result<(), XmodemError> {
+        while let Some(byte) = xmodem::read_byte(&mut self.port, Instant::now()).await? {
+            if byte == CAN {
+                let deadline = Instant::now() + self.config.timeout();
+                if xmodem::read_byte(&mut self.port, deadline).await? == Some(CAN) {
+                    return Err(XmodemError::Cancelled);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Receives a batch of files, created by [`Ymodem::receiver`].
+///
+/// Ask for each file with [`next_file`](Receiver::next_file), and receive it with
+/// [`receive`](Receiver::receive), until `next_file` returns `None` at the end of the
+/// batch.
+#[derive(Debug)]
+pub struct Receiver<P> {
+    port: P,
+    config: Ymodem,
+    // The size of the file announced by `next_file`, if it announced one.
+    size: Option<Option<u64>>,
+}
+
+impl<P> Receiver<P>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Asks the sender for the next file and returns what it says about it, or `None`
+    /// if the batch is over.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the sender cancelled the batch.
+    /// * `Timeout` if the sender never answered.
+    /// * `TooManyErrors` if block 0 kept arriving damaged.
+    /// * `OutOfSequence` if the sender sent data rather than block 0.
+    /// * `Io` if reading from or writing to the port failed.
+    ///
+    /// The sender is told the batch was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    pub async fn next_file(&mut self) -> Result<Option<FileInfo>, XmodemError> {
+        let result = self.receive_header().await;
+        xmodem::abort_on_error(&mut self.port, result).await
+    }
+
+    /// Receives the file announced by [`next_file`](Receiver::next_file), writes it to
+    /// `data`, and returns how many bytes were written.  `progress` is called after
+    /// every block received.
+    ///
+    /// When the sender announced the size of the file, the padding of the last block
+    /// is dropped; otherwise it is written along with the data.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the sender cancelled the batch.
+    /// * `Timeout` if the sender stopped sending.
+    /// * `TooManyErrors` if a block kept arriving damaged, or arrived damaged at all
+    ///   with YMODEM-G.
+    /// * `OutOfSequence` if the sender skipped a block.
+    /// * `Io` if writing `data`, or reading from or writing to the port, failed.
+    ///
+    /// The sender is told the batch was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `next_file` didn't announce a file since the last one was received.
+    pub async fn receive<W, F>(&mut self, data: &mut W, mut progress: F) -> Result<u64, XmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let size = self
+            .size
+            .take()
+            .expect("no file was announced by `next_file`");
+        let result = self.receive_file(size, data, &mut progress).await;
+        xmodem::abort_on_error(&mut self.port, result).await
+    }
+
+    /// Gets a reference to the port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Gets a mutable reference to the port.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Gives the port back.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    /// Asks for a block, with `C` or `G`.
+    fn start(&self) -> u8 {
+        if self.config.streaming {
+            STREAM
+        } else {
+            CRC
+        }
+    }
+
+    /// Sends `reply` unless it's empty.
+    async fn reply(&mut self, reply: &[u8]) -> io::Result<()> {
+        if !reply.is_empty() {
+            self.port.write_all(reply).await?;
+            self.port.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn receive_header(&mut self) -> Result<Option<FileInfo>, XmodemError> {
+        let start = self.start();
+        let mut reply = vec![start];
+        let mut errors = 0;
+        loop {
+            self.reply(&reply).await?;
+            reply.clear();
+
+            let error = match self.config.xmodem.read_block(&mut self.port, true).await? {
+                Block::Data(0, block) => match FileInfo::decode(&block) {
+                    Some(info) => {
+                        if !self.config.streaming {
+                            self.reply(&[ACK]).await?;
+                        }
+                        if info.name.is_empty() {
+                            return Ok(None);
+                        }
+                        self.size = Some(info.size);
+                        return Ok(Some(info));
+                    }
+                    None => XmodemError::TooManyErrors,
+                },
+                Block::Data(..) => return Err(XmodemError::OutOfSequence),
+                // The sender missed our ACK of the last EOT.
+                Block::End => {
+                    reply.push(ACK);
+                    reply.push(start);
+                    continue;
+                }
+                Block::Damaged => XmodemError::TooManyErrors,
+                Block::Missing => XmodemError::Timeout,
+            };
+
+            errors += 1;
+            if errors > self.config.retries() {
+                return Err(error);
+            }
+            reply.push(match error {
+                XmodemError::Timeout => start,
+                _ => NAK,
+            });
+        }
+    }
+
+    async fn receive_file<W, F>(
+        &mut self,
+        size: Option<u64>,
+        data: &mut W,
+        progress: &mut F,
+    ) -> Result<u64, XmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let streaming = self.config.streaming;
+        let start = self.start();
+        let mut reply = vec![start];
+        let mut errors = 0;
+        let mut ended = false;
+        let mut done = Progress {
+            blocks: 0,
+            bytes: 0,
+            total: size,
+        };
+        loop {
+            self.reply(&reply).await?;
+            reply.clear();
+
+            let error = match self.config.xmodem.read_block(&mut self.port, true).await? {
+                Block::Data(number, block) => {
+                    let expected = done.blocks.wrapping_add(1) as u8;
+                    if number == expected {
+                        let len = match size {
+                            Some(size) => {
+                                (size.saturating_sub(done.bytes) as usize).min(block.len())
+                            }
+                            None => block.len(),
+                        };
+                        data.write_all(&block[..len]).await?;
+                        done.blocks += 1;
+                        done.bytes += len as u64;
+                        progress(done);
+                    } else if number != expected.wrapping_sub(1) {
+                        return Err(XmodemError::OutOfSequence);
+                    } else if number == 0 {
+                        // The sender missed our ACK of block 0, so it missed what
+                        // followed it as well.
+                        reply.push(ACK);
+                        reply.push(start);
+                        continue;
+                    }
+                    errors = 0;
+                    if !streaming {
+                        reply.push(ACK);
+                    }
+                    continue;
+                }
+                // Plain YMODEM makes sure of the end by rejecting the first EOT.
+                Block::End if streaming || ended => {
+                    self.reply(&[ACK]).await?;
+                    data.flush().await?;
+                    return Ok(done.bytes);
+                }
+                Block::End => {
+                    ended = true;
+                    reply.push(NAK);
+                    continue;
+                }
+                Block::Damaged if streaming => return Err(XmodemError::TooManyErrors),
+                Block::Damaged => XmodemError::TooManyErrors,
+                Block::Missing => XmodemError::Timeout,
+            };
+
+            errors += 1;
+            if errors > self.config.retries() {
+                return Err(error);
+            }
+            match error {
+                // Our `C` or `G` may have been lost.
+                XmodemError::Timeout if done.blocks == 0 && !ended => reply.push(start),
+                _ if !streaming => reply.push(NAK),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! ZMODEM file transfers, as done by `sz` and `rz` of lrzsz.
+//!
+//! ZMODEM streams data in subpackets checked with CRC-16 or CRC-32, without waiting
+//! for each to be acknowledged.  When the receiver gets a damaged subpacket, it tells
+//! the sender where to carry on from with ZRPOS, and the sender seeks back there; a
+//! receiver can also ask to start a file part way, to recover a transfer that was cut
+//! off.  [`Zmodem::sender`] and [`Zmodem::receiver`] start a batch of files on a
+//! [`SerialStream`](crate::SerialStream) or anything else that implements `AsyncRead`
+//! and `AsyncWrite`.
+//!
+//! Files are described by the [`FileInfo`](crate::ymodem::FileInfo) of YMODEM, and
+//! progress is reported as with XMODEM, counting each subpacket as a block.
+//!
+//! ```no_run
+//! use tokio_serial::ymodem::FileInfo;
+//! use tokio_serial::zmodem::Zmodem;
+//! use tokio_serial::SerialStream;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! let mut file = tokio::fs::File::open("update.tar").await?;
+//! let size = file.metadata().await?.len();
+//!
+//! // Starts `rz` on the shell at the other end, as `sz` does.
+//! let mut batch = Zmodem::new().sender(&mut port);
+//! let info = FileInfo::new("update.tar").with_size(size);
+//! batch.send(&info, &mut file, |_| {}).await?;
+//! batch.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A terminal can pass what it reads from the port through a [`Detector`] to notice
+//! when the other side starts `sz` or `rz`, and start a receiver or sender in turn.
+//!
+//! Either side gives up on a batch by sending eight CANs, and gives up when it receives
+//! five.
+//!
+//! This module is only available with the `zmodem` feature, which enables `ymodem`
+//! as well.
+use crate::xmodem::Progress;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use std::time::Duration;
+use std::{fmt, io};
+
+mod frame;
+mod receiver;
+mod sender;
+
+pub use self::receiver::Receiver;
+pub use self::sender::Sender;
+
+use self::frame::Link;
+
+/// The start of a hex header, the way every ZMODEM session starts.
+const AUTOSTART: &[u8] = b"**\x18B0";
+
+/// An error from a ZMODEM transfer.
+#[derive(Debug)]
+pub enum ZmodemError {
+    /// The other side cancelled the batch.
+    Cancelled,
+    /// The receiver didn't want the file.  The batch carries on.
+    Skipped,
+    /// The other side didn't start the batch, or stopped answering, after every retry.
+    Timeout,
+    /// Headers or subpackets were damaged, or the receiver asked for data again, more
+    /// often than the retries allow.
+    TooManyErrors,
+    /// An I/O error occurred, or the port reported end of file.
+    Io(io::Error),
+}
+
+impl fmt::Display for ZmodemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZmodemError::Cancelled => write!(f, "transfer cancelled by the other side"),
+            ZmodemError::Skipped => write!(f, "file skipped by the receiver"),
+            ZmodemError::Timeout => write!(f, "no answer from the other side"),
+            ZmodemError::TooManyErrors => write!(f, "too many errors"),
+            ZmodemError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ZmodemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZmodemError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ZmodemError {
+    fn from(err: io::Error) -> Self {
+        ZmodemError::Io(err)
+    }
+}
+
+/// The settings of a ZMODEM batch.
+///
+/// ```
+/// use tokio_serial::zmodem::Zmodem;
+/// use std::time::Duration;
+///
+/// let zmodem = Zmodem::new()
+///     .with_subpacket_size(8192)
+///     .with_timeout(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Zmodem {
+    subpacket_size: usize,
+    timeout: Duration,
+    retries: usize,
+}
+
+impl Zmodem {
+    /// Create settings with the defaults of lrzsz: subpackets of 1024 bytes, a timeout
+    /// of 10 seconds and 10 retries.
+    pub fn new() -> Zmodem {
+        Self {
+            subpacket_size: 1024,
+            timeout: Duration::from_secs(10),
+            retries: 10,
+        }
+    }
+
+    /// Sets how much data a sender puts in each subpacket, from 1 to 8192 bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `subpacket_size` is out of range.
+    pub fn with_subpacket_size(mut self, subpacket_size: usize) -> Self {
+        assert!((1..=frame::MAX_SUBPACKET).contains(&subpacket_size));
+        self.subpacket_size = subpacket_size;
+        self
+    }
+
+    /// Sets how long to wait for the other side to answer, or data to arrive.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a header is sent again, or data asked for again, before
+    /// giving up.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Returns how much data a sender puts in each subpacket.
+    pub fn subpacket_size(&self) -> usize {
+        self.subpacket_size
+    }
+
+    /// Returns how long to wait for the other side.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns how many times a header is sent again, or data asked for again.
+    pub fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Starts a batch of files to send over `port`.
+    pub fn sender<P>(&self, port: P) -> Sender<P>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        Sender::new(Link::new(port), self.clone())
+    }
+
+    /// Starts a batch of files to receive from `port`.
+    pub fn receiver<P>(&self, port: P) -> Receiver<P>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        Receiver::new(Link::new(port), self.clone())
+    }
+}
+
+impl Default for Zmodem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the other side of a detected session wants to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Start {
+    /// The other side ran `sz` and wants to send, so start a [`Receiver`].
+    Receive,
+    /// The other side ran `rz` and wants to receive, so start a [`Sender`].
+    Send,
+}
+
+/// Notices the start of a ZMODEM session in data read from a terminal.
+///
+/// Both `sz` and `rz` start with a hex header, `**\x18B00` for the ZRQINIT of a
+/// sender and `**\x18B01` for the ZRINIT of a receiver.  The detector remembers how
+/// much of it it has seen, so the sequence may be split across reads.
+///
+/// ```
+/// use tokio_serial::zmodem::{Detector, Start};
+///
+/// let mut detector = Detector::new();
+/// assert_eq!(detector.detect(b"$ sz file.txt\r\nrz\r**\x18"), None);
+/// assert_eq!(detector.detect(b"B00000000000000\r\x8a\x11"), Some((3, Start::Receive)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    matched: usize,
+}
+
+impl Detector {
+    /// Create a detector that has seen nothing yet.
+    pub fn new() -> Detector {
+        Self::default()
+    }
+
+    /// Looks for the start of a session in `data`, and returns what the other side
+    /// wants along with where in `data` the sequence ended.  What came before the
+    /// sequence is terminal output; the rest of the header that follows it is skipped
+    /// by the receiver or sender.
+    pub fn detect(&mut self, data: &[u8]) -> Option<(usize, Start)> {
+        for (at, &byte) in data.iter().enumerate() {
+            if self.matched == AUTOSTART.len() {
+                let start = match byte {
+                    b'0' => Some(Start::Receive),
+                    b'1' => Some(Start::Send),
+                    _ => None,
+                };
+                self.matched = 0;
+                if let Some(start) = start {
+                    return Some((at + 1, start));
+                }
+            }
+            self.matched = if byte == AUTOSTART[self.matched] {
+                self.matched + 1
+            } else if byte == b'*' {
+                // A run of pads still counts as two.
+                if self.matched == 2 {
+                    2
+                } else {
+                    1
+                }
+            } else {
+                0
+            };
+        }
+        None
+    }
+}
+
+/// Tells the other side the batch was cancelled, if it failed for a reason the other
+/// side doesn't know about.
+async fn abort_on_error<P, T>(
+    link: &mut Link<P>,
+    result: Result<T, ZmodemError>,
+) -> Result<T, ZmodemError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    match result {
+        Err(ZmodemError::Cancelled) | Err(ZmodemError::Skipped) | Err(ZmodemError::Io(_)) => result,
+        Ok(_) => result,
+        Err(err) => {
+            // The batch failed already, so there's no point reporting this as well.
+            let _ = link.write_abort().await;
+            Err(err)
+        }
+    }
+}
+
+/// Progress through a file, at `position`.
+fn progress(blocks: u64, position: u64, total: Option<u64>) -> Progress {
+    Progress {
+        blocks,
+        bytes: position,
+        total,
+    }
+}
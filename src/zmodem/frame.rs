@@ -0,0 +1,436 @@
+//! Headers and data subpackets, and the escaping that carries them.
+use super::ZmodemError;
+use crate::crc::Crc;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{self, Instant};
+
+use std::io;
+
+/// Starts a header.
+const ZPAD: u8 = b'*';
+/// Escapes the byte that follows, and is CAN as well.
+pub(super) const ZDLE: u8 = 0x18;
+/// Header formats, following `ZPAD ZDLE`.
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+
+/// Header types.
+pub(super) const ZRQINIT: u8 = 0;
+pub(super) const ZRINIT: u8 = 1;
+pub(super) const ZSINIT: u8 = 2;
+pub(super) const ZACK: u8 = 3;
+pub(super) const ZFILE: u8 = 4;
+pub(super) const ZSKIP: u8 = 5;
+pub(super) const ZNAK: u8 = 6;
+pub(super) const ZABORT: u8 = 7;
+pub(super) const ZFIN: u8 = 8;
+pub(super) const ZRPOS: u8 = 9;
+pub(super) const ZDATA: u8 = 10;
+pub(super) const ZEOF: u8 = 11;
+pub(super) const ZFERR: u8 = 12;
+pub(super) const ZCHALLENGE: u8 = 14;
+pub(super) const ZCAN: u8 = 16;
+
+/// Bits of ZF0 in ZRINIT.
+pub(super) const CANFDX: u8 = 0x01;
+pub(super) const CANOVIO: u8 = 0x02;
+pub(super) const CANFC32: u8 = 0x20;
+pub(super) const ESCCTL: u8 = 0x40;
+
+/// ZF0 of ZFILE, for a binary transfer.
+pub(super) const ZCBIN: u8 = 1;
+
+/// Subpacket ends, following ZDLE.
+pub(super) const ZCRCE: u8 = b'h';
+pub(super) const ZCRCG: u8 = b'i';
+pub(super) const ZCRCQ: u8 = b'j';
+pub(super) const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+/// The longest data subpacket we accept.
+pub(super) const MAX_SUBPACKET: usize = 8192;
+
+/// A header, with its four bytes of flags or position in the order they are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Header {
+    pub(super) kind: u8,
+    pub(super) data: [u8; 4],
+    /// Whether the header was sent with CRC-32, and so any subpackets that follow it.
+    pub(super) crc32: bool,
+}
+
+impl Header {
+    pub(super) fn new(kind: u8, data: [u8; 4]) -> Header {
+        Header {
+            kind,
+            data,
+            crc32: false,
+        }
+    }
+
+    /// A header carrying a file position, which ZMODEM keeps to 32 bits.
+    pub(super) fn at(kind: u8, position: u64) -> Header {
+        Header::new(kind, (position as u32).to_le_bytes())
+    }
+
+    /// A header carrying flags, ZF0 first.
+    pub(super) fn flags(kind: u8, zf0: u8) -> Header {
+        Header::new(kind, [0, 0, 0, zf0])
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        u64::from(u32::from_le_bytes(self.data))
+    }
+
+    pub(super) fn zf0(&self) -> u8 {
+        self.data[3]
+    }
+}
+
+/// A byte after unescaping.
+enum Escaped {
+    Byte(u8),
+    /// The end of a subpacket.
+    End(u8),
+    /// ZDLE followed by nothing that can be escaped.
+    Bad,
+}
+
+/// The port, with what was read from it but not used yet.
+#[derive(Debug)]
+pub(super) struct Link<P> {
+    pub(super) port: P,
+    buf: Box<[u8]>,
+    start: usize,
+    end: usize,
+    /// Whether to send data with CRC-32, as the receiver asked.
+    pub(super) crc32: bool,
+    /// Whether to escape every control character, as the receiver asked.
+    pub(super) escape_controls: bool,
+}
+
+impl<P> Link<P>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    pub(super) fn new(port: P) -> Link<P> {
+        Link {
+            port,
+            buf: vec![0; 1024].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            crc32: false,
+            escape_controls: false,
+        }
+    }
+
+    /// Reads a byte as it was sent.
+    async fn raw(&mut self, deadline: Instant) -> Result<u8, ZmodemError> {
+        if self.start == self.end {
+            let n = match time::timeout_at(deadline, self.port.read(&mut self.buf)).await {
+                Ok(result) => result?,
+                Err(_) => return Err(ZmodemError::Timeout),
+            };
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            self.start = 0;
+            self.end = n;
+        }
+        self.start += 1;
+        Ok(self.buf[self.start - 1])
+    }
+
+    /// Skips whatever has arrived that cannot start a header, without waiting, and
+    /// returns whether a header may have started.
+    pub(super) async fn header_waiting(&mut self) -> Result<bool, ZmodemError> {
+        loop {
+            let byte = match self.raw(Instant::now()).await {
+                Ok(byte) => byte,
+                Err(ZmodemError::Timeout) => return Ok(false),
+                Err(err) => return Err(err),
+            };
+            if byte == ZPAD || byte == ZDLE {
+                self.start -= 1;
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Reads a byte of a header or subpacket, undoing the escaping.
+    async fn escaped(&mut self, deadline: Instant) -> Result<Escaped, ZmodemError> {
+        loop {
+            let byte = self.raw(deadline).await?;
+            match byte {
+                ZDLE => break,
+                // Flow control characters are always escaped, so these were added
+                // along the way.
+                XON | XOFF | 0x91 | 0x93 => continue,
+                _ => return Ok(Escaped::Byte(byte)),
+            }
+        }
+
+        let mut cancels = 1;
+        loop {
+            let byte = self.raw(deadline).await?;
+            return Ok(match byte {
+                ZDLE => {
+                    cancels += 1;
+                    if cancels == 5 {
+                        return Err(ZmodemError::Cancelled);
+                    }
+                    continue;
+                }
+                _ if cancels > 1 => Escaped::Bad,
+                XON | XOFF | 0x91 | 0x93 => continue,
+                ZCRCE | ZCRCG | ZCRCQ | ZCRCW => Escaped::End(byte),
+                ZRUB0 => Escaped::Byte(0x7F),
+                ZRUB1 => Escaped::Byte(0xFF),
+                _ if byte & 0x60 == 0x40 => Escaped::Byte(byte ^ 0x40),
+                _ => Escaped::Bad,
+            });
+        }
+    }
+
+    /// Reads an escaped byte, returning `None` for the end of a subpacket or a bad
+    /// escape.
+    async fn escaped_byte(&mut self, deadline: Instant) -> Result<Option<u8>, ZmodemError> {
+        match self.escaped(deadline).await? {
+            Escaped::Byte(byte) => Ok(Some(byte)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Skips to the next header and reads it, returning `None` if it was damaged.
+    pub(super) async fn read_header(
+        &mut self,
+        deadline: Instant,
+    ) -> Result<Option<Header>, ZmodemError> {
+        // Hunt for ZPAD ZDLE, noticing five CANs in a row on the way.
+        let mut cancels = 0;
+        let mut padded = false;
+        let format = loop {
+            let byte = self.raw(deadline).await?;
+            if byte == ZDLE {
+                cancels += 1;
+                if cancels == 5 {
+                    return Err(ZmodemError::Cancelled);
+                }
+            } else {
+                cancels = 0;
+            }
+            match byte {
+                ZPAD => padded = true,
+                ZDLE if padded => {
+                    let format = self.raw(deadline).await?;
+                    match format {
+                        ZBIN | ZHEX | ZBIN32 => break format,
+                        ZPAD => cancels = 0,
+                        _ => {
+                            // Put it back, in case it was a CAN.
+                            self.start -= 1;
+                            padded = false;
+                        }
+                    }
+                }
+                _ => padded = false,
+            }
+        };
+
+        if format == ZHEX {
+            let mut bytes = [0u8; 7];
+            for byte in bytes.iter_mut() {
+                let high = hex_digit(self.raw(deadline).await?);
+                let low = hex_digit(self.raw(deadline).await?);
+                match (high, low) {
+                    (Some(high), Some(low)) => *byte = high << 4 | low,
+                    _ => return Ok(None),
+                }
+            }
+            // The line ending and XON that follow are skipped with the next hunt.
+            let check = u16::from_be_bytes([bytes[5], bytes[6]]);
+            if Crc::CRC_16_XMODEM.checksum(&bytes[..5]) as u16 != check {
+                log::debug!("dropping hex header with bad CRC");
+                return Ok(None);
+            }
+            return Ok(Some(Header::new(
+                bytes[0],
+                [bytes[1], bytes[2], bytes[3], bytes[4]],
+            )));
+        }
+
+        let crc32 = format == ZBIN32;
+        let mut bytes = [0u8; 9];
+        let len = if crc32 { 9 } else { 7 };
+        for byte in bytes[..len].iter_mut() {
+            match self.escaped_byte(deadline).await? {
+                Some(value) => *byte = value,
+                None => return Ok(None),
+            }
+        }
+        if !check(crc32, &bytes[..5], &bytes[5..len]) {
+            log::debug!("dropping binary header with bad CRC");
+            return Ok(None);
+        }
+        Ok(Some(Header {
+            kind: bytes[0],
+            data: [bytes[1], bytes[2], bytes[3], bytes[4]],
+            crc32,
+        }))
+    }
+
+    /// Reads the "OO" (over and out) that a sender ends the session with.
+    pub(super) async fn read_over(&mut self, deadline: Instant) -> Result<(), ZmodemError> {
+        for _ in 0..2 {
+            if self.raw(deadline).await? != b'O' {
+                self.start -= 1;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a data subpacket and returns its data and how it ended, or `None` if it
+    /// was damaged.
+    pub(super) async fn read_subpacket(
+        &mut self,
+        crc32: bool,
+        deadline: Instant,
+    ) -> Result<Option<(Vec<u8>, u8)>, ZmodemError> {
+        let mut data = Vec::new();
+        let end = loop {
+            match self.escaped(deadline).await? {
+                Escaped::Byte(_) if data.len() == MAX_SUBPACKET => {
+                    log::debug!("dropping overlong subpacket");
+                    return Ok(None);
+                }
+                Escaped::Byte(byte) => data.push(byte),
+                Escaped::End(end) => break end,
+                Escaped::Bad => return Ok(None),
+            }
+        };
+
+        let mut crc = [0u8; 4];
+        let len = if crc32 { 4 } else { 2 };
+        for byte in crc[..len].iter_mut() {
+            match self.escaped_byte(deadline).await? {
+                Some(value) => *byte = value,
+                None => return Ok(None),
+            }
+        }
+        data.push(end);
+        if !check(crc32, &data, &crc[..len]) {
+            log::debug!("dropping subpacket with bad CRC");
+            return Ok(None);
+        }
+        data.pop();
+        Ok(Some((data, end)))
+    }
+
+    /// Sends a header in hex, as every header without data following it may be.
+    pub(super) async fn write_hex_header(&mut self, header: Header) -> io::Result<()> {
+        let mut bytes = [0u8; 7];
+        bytes[0] = header.kind;
+        bytes[1..5].copy_from_slice(&header.data);
+        let check = Crc::CRC_16_XMODEM.checksum(&bytes[..5]) as u16;
+        bytes[5..].copy_from_slice(&check.to_be_bytes());
+
+        let mut frame = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for byte in &bytes {
+            frame.push(HEX[usize::from(byte >> 4)]);
+            frame.push(HEX[usize::from(byte & 0x0F)]);
+        }
+        frame.extend_from_slice(b"\r\x8A");
+        // XON undoes an XOFF caused by line noise, except on the last headers.
+        if header.kind != ZACK && header.kind != ZFIN {
+            frame.push(XON);
+        }
+        self.write(&frame).await
+    }
+
+    /// Sends a header in binary, with CRC-32 if the receiver can check it.
+    pub(super) async fn write_binary_header(&mut self, header: Header) -> io::Result<()> {
+        let mut bytes = vec![header.kind];
+        bytes.extend_from_slice(&header.data);
+        let mut frame = vec![ZPAD, ZDLE, if self.crc32 { ZBIN32 } else { ZBIN }];
+        let check = self.checksum(&bytes);
+        self.escape(&bytes, &mut frame);
+        self.escape(&check, &mut frame);
+        self.write(&frame).await
+    }
+
+    /// Sends a data subpacket ending with `end`.
+    pub(super) async fn write_subpacket(&mut self, data: &[u8], end: u8) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(data.len() + data.len() / 8 + 12);
+        self.escape(data, &mut frame);
+        frame.push(ZDLE);
+        frame.push(end);
+        let mut checked = data.to_vec();
+        checked.push(end);
+        let check = self.checksum(&checked);
+        self.escape(&check, &mut frame);
+        self.write(&frame).await
+    }
+
+    /// Sends the sequence that aborts a transfer: eight CANs, then as many backspaces
+    /// to erase them from a terminal.
+    pub(super) async fn write_abort(&mut self) -> io::Result<()> {
+        self.write(&[[ZDLE; 8], [0x08; 8]].concat()).await
+    }
+
+    pub(super) async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.port.write_all(data).await?;
+        self.port.flush().await
+    }
+
+    /// The CRC of sent data, in the order its bytes are sent.
+    fn checksum(&self, data: &[u8]) -> Vec<u8> {
+        if self.crc32 {
+            Crc::CRC_32_ISO_HDLC.checksum(data).to_le_bytes().to_vec()
+        } else {
+            (Crc::CRC_16_XMODEM.checksum(data) as u16)
+                .to_be_bytes()
+                .to_vec()
+        }
+    }
+
+    fn escape(&self, data: &[u8], dst: &mut Vec<u8>) {
+        let mut last = 0u8;
+        for &byte in data {
+            let escape = match byte {
+                ZDLE | 0x10 | 0x90 | XON | 0x91 | XOFF | 0x93 => true,
+                // `@` followed by CR is how Telenet is told to escape to its prompt.
+                0x0D | 0x8D => self.escape_controls || last & 0x7F == b'@',
+                _ => self.escape_controls && byte & 0x60 == 0,
+            };
+            if escape {
+                dst.push(ZDLE);
+                dst.push(byte ^ 0x40);
+            } else {
+                dst.push(byte);
+            }
+            last = byte;
+        }
+    }
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Checks `data` against the CRC that followed it.
+fn check(crc32: bool, data: &[u8], crc: &[u8]) -> bool {
+    if crc32 {
+        Crc::CRC_32_ISO_HDLC.checksum(data).to_le_bytes() == crc
+    } else {
+        (Crc::CRC_16_XMODEM.checksum(data) as u16).to_be_bytes() == crc
+    }
+}
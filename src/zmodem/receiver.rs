@@ -0,0 +1,342 @@
+//! The receiving side of a batch.
+use super::frame::{
+    Header, Link, CANFC32, CANFDX, CANOVIO, ZABORT, ZACK, ZCRCE, ZCRCG, ZCRCQ, ZCRCW, ZDATA, ZEOF,
+    ZFERR, ZFILE, ZFIN, ZNAK, ZRINIT, ZRPOS, ZRQINIT, ZSINIT, ZSKIP,
+};
+use super::{abort_on_error, Zmodem, ZmodemError};
+use crate::xmodem::Progress;
+use crate::ymodem::FileInfo;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+/// What we can do, sent in ZRINIT: data while we're writing to disk, errors while
+/// the sender is sending, and CRC-32.
+const CAPABILITIES: u8 = CANFDX | CANOVIO | CANFC32;
+
+/// Receives a batch of files, created by [`Zmodem::receiver`].
+///
+/// Ask for each file with [`next_file`](Receiver::next_file), and then receive it with
+/// [`receive`](Receiver::receive) or [`resume`](Receiver::resume), or turn it down with
+/// [`skip`](Receiver::skip), until `next_file` returns `None` at the end of the batch.
+#[derive(Debug)]
+pub struct Receiver<P> {
+    link: Link<P>,
+    config: Zmodem,
+    // Whether ZRINIT was sent since the last file, and so the sender knows about us.
+    ready: bool,
+    // The file announced by `next_file`.
+    file: Option<FileInfo>,
+}
+
+impl<P> Receiver<P>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    pub(super) fn new(link: Link<P>, config: Zmodem) -> Receiver<P> {
+        Self {
+            link,
+            config,
+            ready: false,
+            file: None,
+        }
+    }
+
+    /// Waits for the sender to announce the next file and returns what it says about
+    /// it, or `None` if the batch is over.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the sender cancelled the batch.
+    /// * `Timeout` if the sender never answered.
+    /// * `TooManyErrors` if the file header kept arriving damaged.
+    /// * `Io` if reading from or writing to the port failed.
+    ///
+    /// The sender is told the batch was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    pub async fn next_file(&mut self) -> Result<Option<FileInfo>, ZmodemError> {
+        let result = self.receive_header().await;
+        abort_on_error(&mut self.link, result).await
+    }
+
+    /// Receives the file announced by [`next_file`](Receiver::next_file) from the
+    /// start, writes it to `data`, and returns how many bytes were written.
+    /// `progress` is called after every subpacket received.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the sender cancelled the batch.
+    /// * `Timeout` if the sender stopped sending.
+    /// * `TooManyErrors` if data kept arriving damaged.
+    /// * `Io` if writing `data`, or reading from or writing to the port, failed.
+    ///
+    /// The sender is told the batch was cancelled, unless it cancelled it or an I/O
+    /// error occurred.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `next_file` didn't announce a file since the last one was received.
+    pub async fn receive<W, F>(&mut self, data: &mut W, progress: F) -> Result<u64, ZmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        self.resume(data, 0, progress).await
+    }
+
+    /// Receives the file announced by [`next_file`](Receiver::next_file) from
+    /// `offset` on, as when part of it was received already, writes it to `data`, and
+    /// returns how many bytes were written.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`receive`](Receiver::receive).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `next_file` didn't announce a file since the last one was received.
+    pub async fn resume<W, F>(
+        &mut self,
+        data: &mut W,
+        offset: u64,
+        mut progress: F,
+    ) -> Result<u64, ZmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let file = self
+            .file
+            .take()
+            .expect("no file was announced by `next_file`");
+        let result = self.receive_file(&file, data, offset, &mut progress).await;
+        abort_on_error(&mut self.link, result).await
+    }
+
+    /// Turns down the file announced by [`next_file`](Receiver::next_file).
+    ///
+    /// ## Errors
+    ///
+    /// `Io` if writing to the port failed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `next_file` didn't announce a file since the last one was received.
+    pub async fn skip(&mut self) -> Result<(), ZmodemError> {
+        self.file
+            .take()
+            .expect("no file was announced by `next_file`");
+        self.link.write_hex_header(Header::flags(ZSKIP, 0)).await?;
+        // The sender goes on to the next file without waiting for ZRINIT.
+        self.ready = true;
+        Ok(())
+    }
+
+    /// Gets a reference to the port.
+    pub fn get_ref(&self) -> &P {
+        &self.link.port
+    }
+
+    /// Gets a mutable reference to the port.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.link.port
+    }
+
+    /// Gives the port back.  Anything read from the port but not used yet is lost.
+    pub fn into_inner(self) -> P {
+        self.link.port
+    }
+
+    fn deadline(&self) -> Instant {
+        Instant::now() + self.config.timeout
+    }
+
+    /// Reads the sender's next header, and fails if it is giving up.
+    async fn read_header(&mut self) -> Result<Option<Header>, ZmodemError> {
+        match self.link.read_header(self.deadline()).await? {
+            Some(header) if header.kind == ZABORT || header.kind == ZFERR => {
+                Err(ZmodemError::Cancelled)
+            }
+            header => Ok(header),
+        }
+    }
+
+    async fn send_zrinit(&mut self) -> Result<(), ZmodemError> {
+        self.link
+            .write_hex_header(Header::flags(ZRINIT, CAPABILITIES))
+            .await?;
+        self.ready = true;
+        Ok(())
+    }
+
+    async fn receive_header(&mut self) -> Result<Option<FileInfo>, ZmodemError> {
+        if !self.ready {
+            self.send_zrinit().await?;
+        }
+        let mut errors = 0;
+        loop {
+            let error = match self.read_header().await {
+                Ok(Some(header)) => match header.kind {
+                    ZRQINIT => {
+                        self.send_zrinit().await?;
+                        continue;
+                    }
+                    ZSINIT => {
+                        // The attention sequence is of no use to us.
+                        let deadline = self.deadline();
+                        if self
+                            .link
+                            .read_subpacket(header.crc32, deadline)
+                            .await?
+                            .is_some()
+                        {
+                            self.link.write_hex_header(Header::at(ZACK, 1)).await?;
+                        } else {
+                            self.link.write_hex_header(Header::flags(ZNAK, 0)).await?;
+                        }
+                        continue;
+                    }
+                    ZFILE => {
+                        let deadline = self.deadline();
+                        let info = self
+                            .link
+                            .read_subpacket(header.crc32, deadline)
+                            .await?
+                            .and_then(|(block, _)| FileInfo::decode(&block));
+                        match info {
+                            Some(info) if !info.name().is_empty() => {
+                                self.ready = false;
+                                self.file = Some(info.clone());
+                                return Ok(Some(info));
+                            }
+                            _ => ZmodemError::TooManyErrors,
+                        }
+                    }
+                    ZFIN => {
+                        self.link.write_hex_header(Header::flags(ZFIN, 0)).await?;
+                        // Take the "OO" that ends the session, so it doesn't end up on a
+                        // terminal.
+                        let deadline = self.deadline();
+                        let _ = self.link.read_over(deadline).await;
+                        return Ok(None);
+                    }
+                    // Left over from a file we received or skipped.
+                    ZDATA | ZEOF => continue,
+                    _ => ZmodemError::TooManyErrors,
+                },
+                Ok(None) => ZmodemError::TooManyErrors,
+                Err(ZmodemError::Timeout) => ZmodemError::Timeout,
+                Err(err) => return Err(err),
+            };
+
+            errors += 1;
+            if errors > self.config.retries {
+                return Err(error);
+            }
+            self.send_zrinit().await?;
+        }
+    }
+
+    async fn receive_file<W, F>(
+        &mut self,
+        file: &FileInfo,
+        data: &mut W,
+        offset: u64,
+        progress: &mut F,
+    ) -> Result<u64, ZmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        let mut position = offset;
+        let mut blocks = 0;
+        let mut errors = 0;
+        self.link
+            .write_hex_header(Header::at(ZRPOS, position))
+            .await?;
+        loop {
+            let error = match self.read_header().await {
+                Ok(Some(header)) if header.kind == ZDATA && header.position() == position => {
+                    match self
+                        .receive_frame(header, data, &mut position, &mut blocks, file, progress)
+                        .await?
+                    {
+                        Some(error) => error,
+                        None => {
+                            errors = 0;
+                            continue;
+                        }
+                    }
+                }
+                Ok(Some(header)) if header.kind == ZEOF && header.position() == position => {
+                    data.flush().await?;
+                    self.send_zrinit().await?;
+                    return Ok(position - offset);
+                }
+                Ok(Some(header)) if header.kind == ZFILE => {
+                    // The sender missed our ZRPOS.
+                    let deadline = self.deadline();
+                    let _ = self.link.read_subpacket(header.crc32, deadline).await?;
+                    ZmodemError::TooManyErrors
+                }
+                // Data from before we asked for it again.
+                Ok(Some(_)) => continue,
+                Ok(None) => ZmodemError::TooManyErrors,
+                Err(ZmodemError::Timeout) => ZmodemError::Timeout,
+                Err(err) => return Err(err),
+            };
+
+            errors += 1;
+            if errors > self.config.retries {
+                return Err(error);
+            }
+            self.link
+                .write_hex_header(Header::at(ZRPOS, position))
+                .await?;
+        }
+    }
+
+    /// Receives the subpackets of a frame, until the frame ends or one is damaged or
+    /// missing.
+    async fn receive_frame<W, F>(
+        &mut self,
+        header: Header,
+        data: &mut W,
+        position: &mut u64,
+        blocks: &mut u64,
+        file: &FileInfo,
+        progress: &mut F,
+    ) -> Result<Option<ZmodemError>, ZmodemError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(Progress),
+    {
+        loop {
+            let deadline = self.deadline();
+            let (block, end) = match self.link.read_subpacket(header.crc32, deadline).await {
+                Ok(Some(subpacket)) => subpacket,
+                Ok(None) => return Ok(Some(ZmodemError::TooManyErrors)),
+                Err(ZmodemError::Timeout) => return Ok(Some(ZmodemError::Timeout)),
+                Err(err) => return Err(err),
+            };
+            data.write_all(&block).await?;
+            *position += block.len() as u64;
+            *blocks += 1;
+            progress(super::progress(*blocks, *position, file.size()));
+
+            match end {
+                ZCRCW | ZCRCQ => {
+                    self.link
+                        .write_hex_header(Header::at(ZACK, *position))
+                        .await?;
+                    if end == ZCRCW {
+                        return Ok(None);
+                    }
+                }
+                ZCRCE => return Ok(None),
+                ZCRCG => {}
+                _ => unreachable!(),
+            }
+        }
+    }
+}
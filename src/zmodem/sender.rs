@@ -0,0 +1,326 @@
+//! The sending side of a batch.
+use super::frame::{
+    Header, Link, CANFC32, CANFDX, ESCCTL, ZABORT, ZACK, ZCAN, ZCBIN, ZCHALLENGE, ZCRCE, ZCRCG,
+    ZCRCW, ZDATA, ZEOF, ZFERR, ZFILE, ZFIN, ZNAK, ZRINIT, ZRPOS, ZRQINIT, ZSKIP,
+};
+use super::{abort_on_error, Zmodem, ZmodemError};
+use crate::xmodem::{self, Progress};
+use crate::ymodem::FileInfo;
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite};
+use tokio::time::Instant;
+
+use std::io::SeekFrom;
+
+/// Sends a batch of files, created by [`Zmodem::sender`].
+///
+/// Send each file with [`send`](Sender::send), then end the batch with
+/// [`finish`](Sender::finish).
+#[derive(Debug)]
+pub struct Sender<P> {
+    link: Link<P>,
+    config: Zmodem,
+    // Whether the receiver answered ZRQINIT yet.
+    ready: bool,
+    // How much the receiver can take before we wait for it, if it is limited.
+    window: Option<u64>,
+}
+
+impl<P> Sender<P>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    pub(super) fn new(link: Link<P>, config: Zmodem) -> Sender<P> {
+        Self {
+            link,
+            config,
+            ready: false,
+            window: None,
+        }
+    }
+
+    /// Sends `info`, then `data` from wherever the receiver asks to start, and returns
+    /// how far into `data` the receiver got.  `progress` is called after every
+    /// subpacket with how far into the file it took us.
+    ///
+    /// Before the first file, `rz` and a carriage return are sent to start a receiver
+    /// on a shell at the other end, and the receiver is asked to start.  `data` is
+    /// sought back whenever the receiver lost some of it.
+    ///
+    /// ## Errors
+    ///
+    /// * `Cancelled` if the receiver cancelled the batch.
+    /// * `Skipped` if the receiver didn't want the file; the batch can carry on.
+    /// * `Timeout` if the receiver never started, or stopped answering.
+    /// * `TooManyErrors` if the receiver kept asking for data again.
+    /// * `Io` if reading or seeking `data`, or reading from or writing to the port,
+    ///   failed, or `info` has an empty name or one too long.
+    ///
+    /// The receiver is told the batch was cancelled, unless it cancelled it, skipped the
+    /// file or an I/O error occurred.
+    pub async fn send<R, F>(
+        &mut self,
+        info: &FileInfo,
+        data: &mut R,
+        mut progress: F,
+    ) -> Result<u64, ZmodemError>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+        F: FnMut(Progress),
+    {
+        let result = self.send_file(info, data, &mut progress).await;
+        abort_on_error(&mut self.link, result).await
+    }
+
+    /// Tells the receiver there are no more files, and gives the port back.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`send`](Sender::send).
+    pub async fn finish(mut self) -> Result<P, ZmodemError> {
+        let result = self.send_fin().await;
+        abort_on_error(&mut self.link, result).await?;
+        Ok(self.link.port)
+    }
+
+    /// Gets a reference to the port.
+    pub fn get_ref(&self) -> &P {
+        &self.link.port
+    }
+
+    /// Gets a mutable reference to the port.
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.link.port
+    }
+
+    /// Gives the port back, without ending the batch.  Anything read from the port but
+    /// not used yet is lost.
+    pub fn into_inner(self) -> P {
+        self.link.port
+    }
+
+    fn deadline(&self) -> Instant {
+        Instant::now() + self.config.timeout
+    }
+
+    /// Reads the receiver's next header, and fails if it is giving up.
+    async fn read_header(&mut self) -> Result<Option<Header>, ZmodemError> {
+        match self.link.read_header(self.deadline()).await? {
+            Some(header) if [ZABORT, ZFERR, ZCAN].contains(&header.kind) => {
+                Err(ZmodemError::Cancelled)
+            }
+            header => Ok(header),
+        }
+    }
+
+    /// Asks the receiver to start, and learns what it can do.
+    async fn start(&mut self) -> Result<(), ZmodemError> {
+        self.link.write(b"rz\r").await?;
+        for _ in 0..=self.config.retries {
+            self.link
+                .write_hex_header(Header::flags(ZRQINIT, 0))
+                .await?;
+            let header = match self.read_header().await {
+                Ok(Some(header)) => header,
+                Ok(None) | Err(ZmodemError::Timeout) => continue,
+                Err(err) => return Err(err),
+            };
+            match header.kind {
+                ZRINIT => {
+                    self.accept(header);
+                    return Ok(());
+                }
+                ZCHALLENGE => {
+                    let answer = Header::new(ZACK, header.data);
+                    self.link.write_hex_header(answer).await?;
+                }
+                _ => {}
+            }
+        }
+        Err(ZmodemError::Timeout)
+    }
+
+    /// Takes on the capabilities of the receiver from its ZRINIT.
+    fn accept(&mut self, zrinit: Header) {
+        let flags = zrinit.zf0();
+        self.link.crc32 = flags & CANFC32 != 0;
+        self.link.escape_controls = flags & ESCCTL != 0;
+        let buffer = u64::from(u16::from_le_bytes([zrinit.data[0], zrinit.data[1]]));
+        self.window = if buffer != 0 {
+            Some(buffer)
+        } else if flags & CANFDX == 0 {
+            // It can't tell us about errors while we're sending.
+            Some(self.config.subpacket_size as u64)
+        } else {
+            None
+        };
+        self.ready = true;
+    }
+
+    async fn send_file<R, F>(
+        &mut self,
+        info: &FileInfo,
+        data: &mut R,
+        progress: &mut F,
+    ) -> Result<u64, ZmodemError>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+        F: FnMut(Progress),
+    {
+        let mut header = info.encode()?;
+        header.push(0);
+        if !self.ready {
+            self.start().await?;
+        }
+
+        let mut position = self.offer(&header).await?;
+        let mut buf = vec![0u8; self.config.subpacket_size];
+        let mut blocks = 0;
+        let mut errors = 0;
+        let mut asked = position;
+        loop {
+            data.seek(SeekFrom::Start(position)).await?;
+            self.link
+                .write_binary_header(Header::at(ZDATA, position))
+                .await?;
+            let mut sent = 0;
+            let at = loop {
+                let len = xmodem::fill(data, &mut buf).await?;
+                sent += len as u64;
+                let end = if len < buf.len() {
+                    ZCRCE
+                } else if self.window.is_some_and(|window| sent >= window) {
+                    ZCRCW
+                } else {
+                    ZCRCG
+                };
+                self.link.write_subpacket(&buf[..len], end).await?;
+                position += len as u64;
+                blocks += 1;
+                progress(super::progress(blocks, position, info.size()));
+
+                match end {
+                    ZCRCE => match self.end_file(position).await? {
+                        None => return Ok(position),
+                        Some(at) => break at,
+                    },
+                    // Carry on with a new frame once the receiver caught up.
+                    ZCRCW => break self.wait_for_ack(position).await?.unwrap_or(position),
+                    _ => {
+                        if self.link.header_waiting().await? {
+                            if let Some(header) = self.read_header().await? {
+                                if header.kind == ZRPOS {
+                                    break header.position();
+                                }
+                            }
+                        }
+                    }
+                }
+            };
+
+            // Only count the receiver asking for the same data again.
+            if at > asked {
+                errors = 0;
+            } else {
+                errors += 1;
+                if errors > self.config.retries {
+                    return Err(ZmodemError::TooManyErrors);
+                }
+            }
+            asked = at;
+            position = at;
+        }
+    }
+
+    /// Sends ZFILE until the receiver asks for data, and returns where it asked to
+    /// start.
+    async fn offer(&mut self, header: &[u8]) -> Result<u64, ZmodemError> {
+        for _ in 0..=self.config.retries {
+            self.link
+                .write_binary_header(Header::flags(ZFILE, ZCBIN))
+                .await?;
+            self.link.write_subpacket(header, ZCRCW).await?;
+            loop {
+                let reply = match self.read_header().await {
+                    Ok(Some(reply)) => reply,
+                    Ok(None) | Err(ZmodemError::Timeout) => break,
+                    Err(err) => return Err(err),
+                };
+                match reply.kind {
+                    ZRPOS => return Ok(reply.position()),
+                    ZSKIP => return Err(ZmodemError::Skipped),
+                    // A receiver that didn't like ZFILE.  A ZRINIT may be an answer to an
+                    // earlier ZRQINIT, so a receiver that missed ZFILE gets it again when
+                    // it times out instead.
+                    ZNAK => break,
+                    _ => {}
+                }
+            }
+        }
+        Err(ZmodemError::Timeout)
+    }
+
+    /// Waits for the receiver to acknowledge the data up to `position`, returning
+    /// `None` if it did and where to start again if it lost some of it.
+    async fn wait_for_ack(&mut self, position: u64) -> Result<Option<u64>, ZmodemError> {
+        loop {
+            match self.read_header().await {
+                Ok(Some(reply)) if reply.kind == ZACK => return Ok(None),
+                Ok(Some(reply)) if reply.kind == ZRPOS => return Ok(Some(reply.position())),
+                Ok(_) => {}
+                // Start the frame again, where the receiver should be.
+                Err(ZmodemError::Timeout) => return Ok(Some(position)),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Sends ZEOF until the receiver takes it, returning `None` once it did and where
+    /// to start again if it lost some of the data.
+    async fn end_file(&mut self, position: u64) -> Result<Option<u64>, ZmodemError> {
+        for _ in 0..=self.config.retries {
+            self.link
+                .write_binary_header(Header::at(ZEOF, position))
+                .await?;
+            loop {
+                let reply = match self.read_header().await {
+                    Ok(Some(reply)) => reply,
+                    Ok(None) | Err(ZmodemError::Timeout) => break,
+                    Err(err) => return Err(err),
+                };
+                match reply.kind {
+                    ZRINIT => {
+                        self.accept(reply);
+                        return Ok(None);
+                    }
+                    ZRPOS => return Ok(Some(reply.position())),
+                    ZSKIP => return Err(ZmodemError::Skipped),
+                    _ => {}
+                }
+            }
+        }
+        Err(ZmodemError::Timeout)
+    }
+
+    async fn send_fin(&mut self) -> Result<(), ZmodemError> {
+        if !self.ready {
+            self.start().await?;
+        }
+        for _ in 0..=self.config.retries {
+            self.link.write_hex_header(Header::flags(ZFIN, 0)).await?;
+            loop {
+                match self.read_header().await {
+                    Ok(Some(reply)) if reply.kind == ZFIN => {
+                        // Over and out.
+                        self.link.write(b"OO").await?;
+                        return Ok(());
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) | Err(ZmodemError::Timeout) => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Err(ZmodemError::Timeout)
+    }
+}
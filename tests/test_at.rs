@@ -0,0 +1,191 @@
+#![cfg(all(feature = "at", unix))]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::at::{AtClient, AtError, ErrorReport};
+use tokio_serial::SerialStream;
+
+/// Reads what the client sent up to and including `end`.
+async fn read_until(modem: &mut SerialStream, end: u8) -> Vec<u8> {
+    let mut received = Vec::new();
+    loop {
+        let byte = modem.read_u8().await.unwrap();
+        received.push(byte);
+        if byte == end {
+            return received;
+        }
+    }
+}
+
+async fn expect_command(modem: &mut SerialStream, command: &str) {
+    let received = read_until(modem, b'\r').await;
+    assert_eq!(received, format!("{}\r", command).as_bytes());
+}
+
+#[tokio::test]
+async fn collects_the_response() {
+    let (mut modem, port) = SerialStream::pair().unwrap();
+    let mut client = AtClient::new(port);
+
+    let peer = tokio::spawn(async move {
+        expect_command(&mut modem, "AT+CSQ").await;
+        // With echo on.
+        modem
+            .write_all(b"AT+CSQ\r\r\n+CSQ: 20,99\r\n\r\nOK\r\n")
+            .await
+            .unwrap();
+        expect_command(&mut modem, "AT+CGMR").await;
+        modem
+            .write_all(b"\r\nEG25GGBR07A08M2G\r\n\r\nREV 1\r\n\r\nOK\r\n")
+            .await
+            .unwrap();
+        modem
+    });
+
+    assert_eq!(client.command("AT+CSQ").await.unwrap(), vec!["+CSQ: 20,99"]);
+    assert_eq!(
+        client.command("AT+CGMR").await.unwrap(),
+        vec!["EG25GGBR07A08M2G", "REV 1"]
+    );
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn reports_errors() {
+    let (mut modem, port) = SerialStream::pair().unwrap();
+    let mut client = AtClient::new(port);
+
+    let peer = tokio::spawn(async move {
+        expect_command(&mut modem, "AT+FOO").await;
+        modem.write_all(b"\r\nERROR\r\n").await.unwrap();
+        expect_command(&mut modem, "AT+CPIN?").await;
+        modem.write_all(b"\r\n+CME ERROR: 10\r\n").await.unwrap();
+        expect_command(&mut modem, "AT+CPIN?").await;
+        modem
+            .write_all(b"\r\n+CME ERROR: SIM not inserted\r\n")
+            .await
+            .unwrap();
+        expect_command(&mut modem, "AT+CMGR=1").await;
+        modem.write_all(b"\r\n+CMS ERROR: 321\r\n").await.unwrap();
+        modem
+    });
+
+    assert!(matches!(
+        client.command("AT+FOO").await,
+        Err(AtError::Error)
+    ));
+    assert!(matches!(
+        client.command("AT+CPIN?").await,
+        Err(AtError::Cme(ErrorReport::Code(10)))
+    ));
+    match client.command("AT+CPIN?").await {
+        Err(AtError::Cme(ErrorReport::Text(text))) => assert_eq!(text, "SIM not inserted"),
+        other => panic!("{:?}", other),
+    }
+    assert!(matches!(
+        client.command("AT+CMGR=1").await,
+        Err(AtError::Cms(ErrorReport::Code(321)))
+    ));
+    assert!(matches!(
+        client.command("AT\r+CSQ").await,
+        Err(AtError::InvalidCommand)
+    ));
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn routes_urcs_to_subscribers() {
+    let (mut modem, port) = SerialStream::pair().unwrap();
+    let mut client = AtClient::new(port);
+    let mut urcs = client.subscribe();
+
+    let peer = tokio::spawn(async move {
+        expect_command(&mut modem, "AT+COPS?").await;
+        modem
+            .write_all(b"\r\n+CREG: 5\r\n\r\n+COPS: 0,0,\"Operator\",7\r\n\r\nRING\r\n\r\nOK\r\n")
+            .await
+            .unwrap();
+        // The answer to AT+CREG? is not a URC.
+        expect_command(&mut modem, "AT+CREG?").await;
+        modem
+            .write_all(b"\r\n+CREG: 0,1\r\n\r\nOK\r\n")
+            .await
+            .unwrap();
+        modem.write_all(b"\r\n+CMTI: \"SM\",3\r\n").await.unwrap();
+        modem
+    });
+
+    assert_eq!(
+        client.command("AT+COPS?").await.unwrap(),
+        vec!["+COPS: 0,0,\"Operator\",7"]
+    );
+    assert_eq!(urcs.recv().await.unwrap(), "+CREG: 5");
+    assert_eq!(urcs.recv().await.unwrap(), "RING");
+
+    assert_eq!(
+        client.command("AT+CREG?").await.unwrap(),
+        vec!["+CREG: 0,1"]
+    );
+    assert!(urcs.try_recv().is_err());
+
+    let _ = tokio::time::timeout(Duration::from_millis(200), client.listen()).await;
+    assert_eq!(urcs.recv().await.unwrap(), "+CMTI: \"SM\",3");
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn sends_data_after_the_prompt() {
+    let (mut modem, port) = SerialStream::pair().unwrap();
+    let mut client = AtClient::new(port);
+
+    let peer = tokio::spawn(async move {
+        expect_command(&mut modem, "AT+CMGS=\"+15551234567\"").await;
+        modem.write_all(b"\r\n> ").await.unwrap();
+        assert_eq!(read_until(&mut modem, 0x1A).await, b"Hello\x1A");
+        modem
+            .write_all(b"\r\n+CMGS: 12\r\n\r\nOK\r\n")
+            .await
+            .unwrap();
+
+        // Never prompted, so the client gives up with ESC.
+        expect_command(&mut modem, "AT+CMGS=\"+15551234567\"").await;
+        assert_eq!(modem.read_u8().await.unwrap(), 0x1B);
+        modem
+    });
+
+    assert_eq!(
+        client
+            .command_with_prompt("AT+CMGS=\"+15551234567\"", b"Hello", Duration::from_secs(1))
+            .await
+            .unwrap(),
+        vec!["+CMGS: 12"]
+    );
+    assert!(matches!(
+        client
+            .command_with_prompt(
+                "AT+CMGS=\"+15551234567\"",
+                b"Hello",
+                Duration::from_millis(100)
+            )
+            .await,
+        Err(AtError::Timeout)
+    ));
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn times_out_per_command() {
+    let (_modem, port) = SerialStream::pair().unwrap();
+    let mut client = AtClient::new(port).with_timeout(Duration::from_millis(50));
+
+    let started = tokio::time::Instant::now();
+    assert!(matches!(client.command("AT").await, Err(AtError::Timeout)));
+    assert!(started.elapsed() < Duration::from_millis(500));
+    assert!(matches!(
+        client
+            .command_with_timeout("AT+COPS=?", Duration::from_millis(150))
+            .await,
+        Err(AtError::Timeout)
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
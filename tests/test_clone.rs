@@ -0,0 +1,26 @@
+#![cfg(unix)]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::{SerialPort, SerialStream};
+
+#[tokio::test]
+async fn clone_changes_settings_while_original_does_io() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let exclusive = slave.exclusive();
+
+    let mut control: Box<dyn SerialPort> = slave.try_clone().expect("unable to clone port");
+    control.set_baud_rate(57600).unwrap();
+    assert_eq!(slave.baud_rate().unwrap(), 57600);
+    assert_eq!(slave.exclusive(), exclusive);
+
+    master.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+
+    drop(control);
+
+    slave.write_all(b"pong").await.unwrap();
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"pong");
+}
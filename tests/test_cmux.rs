@@ -0,0 +1,257 @@
+#![cfg(all(feature = "cmux", unix))]
+
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+use tokio_serial::cmux::{Cmux, Multiplexer};
+use tokio_serial::SerialStream;
+
+const SABM: u8 = 0x2F;
+const UA: u8 = 0x63;
+const DM: u8 = 0x0F;
+const DISC: u8 = 0x43;
+const UIH: u8 = 0xEF;
+const PF: u8 = 0x10;
+
+fn fcs(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xE0
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    0xFF - crc
+}
+
+/// The other side of the multiplexer, a responder as a modem would be.
+struct Peer {
+    port: SerialStream,
+}
+
+impl Peer {
+    /// Reads the next frame, returning its DLCI, type and information field.
+    async fn recv(&mut self) -> (u8, u8, Vec<u8>) {
+        let mut header = [0u8; 4];
+        self.port.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0xF9);
+        let kind = header[2] & !PF;
+        // The initiator sets C/R on commands.
+        let command = kind != UA && kind != DM;
+        assert_eq!(header[1] & 0x03, u8::from(command) << 1 | 1);
+        assert_eq!(
+            header[3] & 1,
+            1,
+            "frames are short enough for one length octet"
+        );
+        let mut rest = vec![0u8; usize::from(header[3] >> 1) + 2];
+        self.port.read_exact(&mut rest).await.unwrap();
+        let info = rest[..rest.len() - 2].to_vec();
+        let checked = if kind == UIH {
+            header[1..].to_vec()
+        } else {
+            [&header[1..], &info[..]].concat()
+        };
+        assert_eq!(rest[rest.len() - 2], fcs(&checked));
+        assert_eq!(rest[rest.len() - 1], 0xF9);
+        (header[1] >> 2, kind, info)
+    }
+
+    /// Sends a frame, as a response if it is UA or DM and as a command otherwise.
+    async fn send(&mut self, dlci: u8, kind: u8, info: &[u8]) {
+        let command = kind != UA && kind != DM;
+        let address = dlci << 2 | u8::from(!command) << 1 | 1;
+        let control = if kind == UIH { kind } else { kind | PF };
+        let header = [address, control, (info.len() as u8) << 1 | 1];
+        let checked = if kind == UIH {
+            header.to_vec()
+        } else {
+            [&header[..], info].concat()
+        };
+        let mut frame = vec![0xF9];
+        frame.extend_from_slice(&header);
+        frame.extend_from_slice(info);
+        frame.push(fcs(&checked));
+        frame.push(0xF9);
+        self.port.write_all(&frame).await.unwrap();
+    }
+
+    /// Accepts the SABM for `dlci`, and answers the MSC that follows on a channel.
+    async fn accept(&mut self, dlci: u8) {
+        assert_eq!(self.recv().await, (dlci, SABM, vec![]));
+        self.send(dlci, UA, &[]).await;
+        if dlci != 0 {
+            let msc = vec![0xE3, 0x05, dlci << 2 | 0x03, 0x8D];
+            assert_eq!(self.recv().await, (0, UIH, msc));
+            self.send(0, UIH, &[0xE1, 0x05, dlci << 2 | 0x03, 0x8D])
+                .await;
+        }
+    }
+}
+
+type DriverTask = tokio::task::JoinHandle<std::io::Result<SerialStream>>;
+
+fn start(cmux: Cmux) -> (Multiplexer, DriverTask, Peer) {
+    let (peer, port) = SerialStream::pair().unwrap();
+    let (mux, driver) = cmux.with_timeout(Duration::from_millis(500)).start(port);
+    (mux, tokio::spawn(driver.run()), Peer { port: peer })
+}
+
+#[tokio::test]
+async fn opens_channels_and_shuts_down() {
+    let (mux, driver, mut peer) = start(Cmux::new());
+
+    let peer = tokio::spawn(async move {
+        // SABM on the control channel, byte for byte.
+        let mut sabm = [0u8; 6];
+        peer.port.read_exact(&mut sabm).await.unwrap();
+        assert_eq!(sabm, [0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9]);
+        peer.send(0, UA, &[]).await;
+        peer.accept(1).await;
+
+        assert_eq!(peer.recv().await, (1, UIH, b"hello".to_vec()));
+        peer.send(1, UIH, b"HELLO").await;
+
+        // Shutting the channel down.
+        assert_eq!(peer.recv().await, (1, DISC, vec![]));
+        peer.send(1, UA, &[]).await;
+        // Closing the multiplexer.
+        assert_eq!(peer.recv().await, (0, UIH, vec![0xC3, 0x01]));
+        peer.send(0, UIH, &[0xC1, 0x01]).await;
+        peer
+    });
+
+    let mut channel = mux.open(1).await.unwrap();
+    assert_eq!(channel.dlci(), 1);
+    assert_eq!(mux.open(1).await.unwrap_err().kind(), ErrorKind::AddrInUse);
+    channel.write_all(b"hello").await.unwrap();
+    let mut buf = [0u8; 5];
+    channel.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"HELLO");
+
+    channel.shutdown().await.unwrap();
+    assert!(channel.write_all(b"more").await.is_err());
+    mux.close().await.unwrap();
+    assert!(driver.await.unwrap().is_ok());
+    peer.await.unwrap();
+
+    assert_eq!(
+        mux.open(2).await.unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+}
+
+#[tokio::test]
+async fn splits_writes_into_frames() {
+    let (mux, driver, mut peer) = start(Cmux::new().with_max_frame_size(31));
+
+    let peer = tokio::spawn(async move {
+        peer.accept(0).await;
+        peer.accept(3).await;
+        let mut received = Vec::new();
+        while received.len() < 100 {
+            let (dlci, kind, info) = peer.recv().await;
+            assert_eq!((dlci, kind), (3, UIH));
+            assert!(info.len() <= 31);
+            received.extend(info);
+        }
+        assert_eq!(received, (0..100).collect::<Vec<u8>>());
+        peer
+    });
+
+    let mut channel = mux.open(3).await.unwrap();
+    channel
+        .write_all(&(0..100).collect::<Vec<u8>>())
+        .await
+        .unwrap();
+    let _peer = peer.await.unwrap();
+    drop(channel);
+    drop(mux);
+    // The driver stops once nothing is left to use it.
+    assert!(driver.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn honours_flow_control() {
+    let (mux, _driver, mut peer) = start(Cmux::new());
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+
+    let peer = tokio::spawn(async move {
+        peer.accept(0).await;
+        peer.accept(1).await;
+        // Stop the channel, and wait for the answer.
+        peer.send(0, UIH, &[0xE3, 0x05, 0x07, 0x8F]).await;
+        assert_eq!(peer.recv().await, (0, UIH, vec![0xE1, 0x05, 0x07, 0x8F]));
+        stopped_tx.send(()).unwrap();
+
+        assert!(timeout(Duration::from_millis(200), peer.recv())
+            .await
+            .is_err());
+        peer.send(0, UIH, &[0xE3, 0x05, 0x07, 0x8D]).await;
+        assert_eq!(peer.recv().await, (0, UIH, vec![0xE1, 0x05, 0x07, 0x8D]));
+        assert_eq!(peer.recv().await, (1, UIH, b"held".to_vec()));
+        peer
+    });
+
+    let mut channel = mux.open(1).await.unwrap();
+    stopped_rx.await.unwrap();
+    channel.write_all(b"held").await.unwrap();
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn reports_refused_and_unanswered_channels() {
+    let (mux, _driver, mut peer) = start(Cmux::new().with_retries(1));
+
+    let peer = tokio::spawn(async move {
+        peer.accept(0).await;
+        assert_eq!(peer.recv().await, (2, SABM, vec![]));
+        peer.send(2, DM, &[]).await;
+        // Never answered, so sent twice.
+        assert_eq!(peer.recv().await, (3, SABM, vec![]));
+        assert_eq!(peer.recv().await, (3, SABM, vec![]));
+        peer
+    });
+
+    assert_eq!(
+        mux.open(2).await.unwrap_err().kind(),
+        ErrorKind::ConnectionRefused
+    );
+    assert_eq!(mux.open(3).await.unwrap_err().kind(), ErrorKind::TimedOut);
+    assert_eq!(
+        mux.open(0).await.unwrap_err().kind(),
+        ErrorKind::InvalidInput
+    );
+    peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn other_side_can_close_a_channel() {
+    let (mux, _driver, mut peer) = start(Cmux::new());
+
+    let peer = tokio::spawn(async move {
+        peer.accept(0).await;
+        peer.accept(1).await;
+        peer.send(1, UIH, b"bye").await;
+        peer.send(1, DISC, &[]).await;
+        let (dlci, kind, _) = peer.recv().await;
+        assert_eq!((dlci, kind), (1, UA));
+        peer
+    });
+
+    let mut channel = mux.open(1).await.unwrap();
+    let mut received = Vec::new();
+    channel.read_to_end(&mut received).await.unwrap();
+    assert_eq!(received, b"bye");
+    assert_eq!(
+        channel.write_all(b"hello").await.unwrap_err().kind(),
+        ErrorKind::BrokenPipe
+    );
+    peer.await.unwrap();
+}
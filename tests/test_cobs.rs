@@ -0,0 +1,123 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{CobsCodec, CobsCodecError, CobsVariant};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn encode(codec: &mut CobsCodec, frame: &[u8]) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    codec.encode(frame, &mut buf).unwrap();
+    buf.to_vec()
+}
+
+fn decode(codec: &mut CobsCodec, encoded: &[u8]) -> Vec<u8> {
+    let mut buf = BytesMut::from(encoded);
+    let frame = codec.decode(&mut buf).unwrap().unwrap().to_vec();
+    assert!(buf.is_empty());
+    frame
+}
+
+#[test]
+fn cobs_vectors() {
+    let long: Vec<u8> = (1..=254).collect();
+    let mut long_encoded = vec![0xFF];
+    long_encoded.extend(&long);
+    long_encoded.push(0x00);
+    let longer: Vec<u8> = (0..=254).collect();
+    let mut longer_encoded = vec![0x01, 0xFF];
+    longer_encoded.extend(1..=254);
+    longer_encoded.push(0x00);
+
+    let vectors: Vec<(&[u8], &[u8])> = vec![
+        (&[0x00], &[0x01, 0x01, 0x00]),
+        (&[0x00, 0x00], &[0x01, 0x01, 0x01, 0x00]),
+        (
+            &[0x11, 0x22, 0x00, 0x33],
+            &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00],
+        ),
+        (
+            &[0x11, 0x22, 0x33, 0x44],
+            &[0x05, 0x11, 0x22, 0x33, 0x44, 0x00],
+        ),
+        (
+            &[0x11, 0x00, 0x00, 0x00],
+            &[0x02, 0x11, 0x01, 0x01, 0x01, 0x00],
+        ),
+        (&long, &long_encoded),
+        (&longer, &longer_encoded),
+    ];
+
+    let mut codec = CobsCodec::new();
+    for (frame, encoded) in vectors {
+        assert_eq!(encode(&mut codec, frame), encoded);
+        assert_eq!(decode(&mut codec, encoded), frame);
+    }
+}
+
+#[test]
+fn cobs_r_vectors() {
+    let mut codec = CobsCodec::new().with_variant(CobsVariant::Reduced);
+    let vectors: Vec<(&[u8], &[u8])> = vec![
+        (&[0x11, 0x22, 0x33, 0x44], &[0x44, 0x11, 0x22, 0x33, 0x00]),
+        (
+            &[0x11, 0x22, 0x33, 0x02],
+            &[0x05, 0x11, 0x22, 0x33, 0x02, 0x00],
+        ),
+        (&[0x11, 0x00, 0x33, 0x44], &[0x02, 0x11, 0x44, 0x33, 0x00]),
+        (&[0x00], &[0x01, 0x01, 0x00]),
+    ];
+    for (frame, encoded) in vectors {
+        assert_eq!(encode(&mut codec, frame), encoded);
+        assert_eq!(decode(&mut codec, encoded), frame);
+    }
+}
+
+#[test]
+fn resynchronizes_after_corruption() {
+    let mut codec = CobsCodec::new().with_max_frame_length(4);
+    // the first frame lost a byte, the second one is too long
+    let mut buf = BytesMut::from(
+        &[
+            0x05, 0x11, 0x22, 0x33, 0x00, 0x06, 0x01, 0x02, 0x03, 0x04, 0x05, 0x00, 0x02, 0x0A,
+            0x00,
+        ][..],
+    );
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(CobsCodecError::InvalidFrame)
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(CobsCodecError::FrameTooLong)
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0x0A]);
+
+    // garbage without any delimiter is dropped once it can't be a frame any more
+    let mut buf = BytesMut::from(&[0x01; 16][..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(CobsCodecError::FrameTooLong)
+    ));
+    buf.extend_from_slice(&[0x01, 0x00, 0x02, 0x0B, 0x00]);
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0x0B]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn round_trip_over_pty() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_serial::{frame::SerialFramed, SerialStream};
+
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut master = SerialFramed::new(master, CobsCodec::new());
+    let mut slave = SerialFramed::new(slave, CobsCodec::new());
+
+    master.send(&b"\x00first\x00"[..]).await.unwrap();
+    master.get_mut().write_all(b"\x09noise\x00").await.unwrap();
+    master.send(&b"second"[..]).await.unwrap();
+
+    assert_eq!(&slave.next().await.unwrap().unwrap()[..], b"\x00first\x00");
+    assert!(slave.next().await.unwrap().is_err());
+    assert_eq!(&slave.next().await.unwrap().unwrap()[..], b"second");
+}
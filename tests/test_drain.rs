@@ -0,0 +1,70 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time,
+};
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn drain_completes_once_data_is_sent() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+
+    master.write_all(b"drained").await.unwrap();
+    time::timeout(Duration::from_secs(5), master.drain())
+        .await
+        .expect("drain did not complete")
+        .unwrap();
+
+    let mut buf = [0u8; 7];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"drained");
+}
+
+#[tokio::test]
+async fn shutdown_drains_when_enabled() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    assert!(!master.drain_on_shutdown());
+    master.set_drain_on_shutdown(true);
+
+    let (_rx, mut tx) = master.into_split();
+    tx.write_all(b"bye").await.unwrap();
+    time::timeout(Duration::from_secs(5), tx.shutdown())
+        .await
+        .expect("shutdown did not complete")
+        .unwrap();
+
+    let mut buf = [0u8; 3];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"bye");
+}
+
+#[tokio::test]
+async fn flush_drains() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+
+    master.write_all(b"one").await.unwrap();
+    time::timeout(Duration::from_secs(5), master.flush())
+        .await
+        .expect("flush did not complete")
+        .unwrap();
+
+    let (_rx, mut tx) = master.split();
+    tx.write_all(b"two").await.unwrap();
+    time::timeout(Duration::from_secs(5), tx.flush())
+        .await
+        .expect("flush did not complete")
+        .unwrap();
+
+    let (_rx, mut tx) = master.into_split();
+    tx.write_all(b"three").await.unwrap();
+    time::timeout(Duration::from_secs(5), tx.flush())
+        .await
+        .expect("flush did not complete")
+        .unwrap();
+
+    let mut buf = [0u8; 11];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"onetwothree");
+}
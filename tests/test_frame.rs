@@ -0,0 +1,177 @@
+#![cfg(all(unix, feature = "codec"))]
+
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time,
+};
+use tokio_serial::{
+    frame::{GapFramed, SerialFramed, SerialFramedParts, SerialFramedRead, SerialFramedWrite},
+    SerialStream,
+};
+use tokio_util::codec::{BytesCodec, LengthDelimitedCodec, LinesCodec};
+
+#[tokio::test]
+async fn framed_switches_codec_without_losing_bytes() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut framed = SerialFramed::new(slave, LinesCodec::new());
+    framed.set_max_buffer_size(Some(64));
+    framed.set_backpressure_boundary(16);
+
+    // the binary frame arrives in the same read as the text line before it
+    master
+        .write_all(b"BOOT\n\x00\x00\x00\x03\x01\x02\x03")
+        .await
+        .unwrap();
+    assert_eq!(framed.next().await.unwrap().unwrap(), "BOOT");
+
+    let parts = framed.into_parts();
+    assert!(!parts.read_buf.is_empty());
+    assert_eq!(parts.max_buffer_size, Some(64));
+    assert_eq!(parts.backpressure_boundary, 16);
+    let mut binary = SerialFramedParts::new(parts.port, LengthDelimitedCodec::new());
+    binary.read_buf = parts.read_buf;
+    binary.max_buffer_size = parts.max_buffer_size;
+    binary.backpressure_boundary = parts.backpressure_boundary;
+    let mut framed = SerialFramed::from_parts(binary);
+    assert_eq!(framed.max_buffer_size(), Some(64));
+    assert_eq!(framed.backpressure_boundary(), 16);
+    assert_eq!(&framed.next().await.unwrap().unwrap()[..], b"\x01\x02\x03");
+
+    let mut framed = framed.map_codec(|_| LinesCodec::new());
+    master.write_all(b"DONE\n").await.unwrap();
+    assert_eq!(framed.next().await.unwrap().unwrap(), "DONE");
+}
+
+#[tokio::test]
+async fn framed_read_and_write_on_owned_halves() {
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let (master_rx, master_tx) = master.into_split();
+    let mut master_rx = SerialFramedRead::new(master_rx, LinesCodec::new());
+    let mut master_tx = SerialFramedWrite::new(master_tx, LinesCodec::new());
+    let (mut slave_rx, mut slave_tx) = SerialFramed::new(slave, LinesCodec::new()).into_split();
+
+    master_tx.send("ping").await.unwrap();
+    assert_eq!(slave_rx.next().await.unwrap().unwrap(), "ping");
+    slave_tx.send("pong").await.unwrap();
+    assert_eq!(master_rx.next().await.unwrap().unwrap(), "pong");
+}
+
+#[tokio::test]
+async fn framed_keeps_partial_frames_across_reads() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut framed = SerialFramed::new(slave, LinesCodec::new());
+
+    tokio::spawn(async move {
+        master.write_all(b"hel").await.unwrap();
+        time::sleep(Duration::from_millis(50)).await;
+        master.write_all(b"lo\nwor").await.unwrap();
+        time::sleep(Duration::from_millis(50)).await;
+        master.write_all(b"ld\n").await.unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+    });
+
+    assert_eq!(framed.next().await.unwrap().unwrap(), "hello");
+    assert_eq!(framed.next().await.unwrap().unwrap(), "world");
+}
+
+#[tokio::test]
+async fn framed_limits_read_buffer() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut framed = SerialFramed::new(slave, LinesCodec::new());
+    framed.set_max_buffer_size(Some(16));
+
+    master.write_all(b"0123456789abcdefXYZ\n").await.unwrap();
+
+    assert!(framed.next().await.unwrap().is_err());
+    assert!(framed.next().await.is_none());
+    assert_eq!(framed.read_buffer().len(), 16);
+}
+
+#[tokio::test]
+async fn framed_decodes_remainder_on_hangup() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut framed = SerialFramed::new(slave, LinesCodec::new());
+
+    master.write_all(b"one\ntwo").await.unwrap();
+    assert_eq!(framed.next().await.unwrap().unwrap(), "one");
+    drop(master);
+
+    let rest = time::timeout(Duration::from_secs(5), framed.next())
+        .await
+        .expect("hangup was not noticed");
+    assert_eq!(rest.unwrap().unwrap(), "two");
+    assert!(framed.next().await.is_none());
+}
+
+#[tokio::test]
+async fn framed_sink_writes_frames_larger_than_the_port_buffer() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut framed = SerialFramed::new(slave, BytesCodec::new());
+    framed.set_backpressure_boundary(1024);
+    assert_eq!(framed.backpressure_boundary(), 1024);
+
+    let frame: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    let expected = frame.clone();
+    let reader = tokio::spawn(async move {
+        let mut received = vec![0u8; expected.len()];
+        master.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, expected);
+    });
+
+    time::timeout(Duration::from_secs(5), framed.send(Bytes::from(frame)))
+        .await
+        .expect("frame was not written")
+        .unwrap();
+    assert!(framed.write_buffer().is_empty());
+    reader.await.unwrap();
+}
+
+#[tokio::test]
+async fn gap_framed_splits_on_silence() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut frames = GapFramed::new(slave, Duration::from_millis(50));
+
+    tokio::spawn(async move {
+        master.write_all(b"\x01\x03").await.unwrap();
+        time::sleep(Duration::from_millis(5)).await;
+        master.write_all(b"\x00\x10").await.unwrap();
+        time::sleep(Duration::from_millis(200)).await;
+        master.write_all(b"\x02\x04").await.unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let first = frames.next().await.unwrap().unwrap();
+    assert_eq!(&first[..], b"\x01\x03\x00\x10");
+    let second = frames.next().await.unwrap().unwrap();
+    assert_eq!(&second[..], b"\x02\x04");
+}
+
+#[tokio::test]
+async fn gap_from_character_time() {
+    use tokio_serial::SerialPort;
+
+    let (_master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    slave.set_baud_rate(9600).unwrap();
+    slave.set_data_bits(tokio_serial::DataBits::Eight).unwrap();
+    slave.set_stop_bits(tokio_serial::StopBits::Two).unwrap();
+
+    // 11 bits per character at 9600 baud (pseudo terminals ignore parity, so use two stop bits)
+    let character_time = slave.character_time().unwrap();
+    assert_eq!(
+        character_time,
+        Duration::from_nanos(11 * 1_000_000_000 / 9600)
+    );
+
+    let frames = GapFramed::with_character_gap(slave, 3.5).unwrap();
+    let expected = character_time.as_secs_f64() * 3.5;
+    assert!((frames.gap().as_secs_f64() - expected).abs() < 1e-6);
+
+    for &characters in &[-1.0, f64::NAN, f64::INFINITY, 1e300] {
+        let (_master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+        let err = GapFramed::with_character_gap(slave, characters).unwrap_err();
+        assert_eq!(err.kind(), tokio_serial::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{Fcs, HdlcCodec, HdlcCodecError};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn fcs_check_values() {
+    let mut codec = HdlcCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode(&b"123456789"[..], &mut buf).unwrap();
+    assert_eq!(&buf[..], b"\x7E123456789\x6E\x90\x7E");
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"123456789");
+
+    let mut codec = HdlcCodec::new().with_fcs(Fcs::Fcs32);
+    let mut buf = BytesMut::new();
+    codec.encode(&b"123456789"[..], &mut buf).unwrap();
+    assert_eq!(&buf[..], b"\x7E123456789\x26\x39\xF4\xCB\x7E");
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"123456789");
+}
+
+#[test]
+fn accm_controls_escaping() {
+    let mut codec = HdlcCodec::new().with_fcs(Fcs::Fcs32).with_accm(0);
+    let frame = [0x01, 0x7E, 0x7D, 0x11];
+    let mut buf = BytesMut::new();
+    codec.encode(&frame[..], &mut buf).unwrap();
+    assert_eq!(&buf[..6], &[0x7E, 0x01, 0x7D, 0x5E, 0x7D, 0x5D]);
+    assert_eq!(buf[6], 0x11);
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &frame[..]);
+
+    // with XON/XOFF in the map, stray ones on the line are dropped
+    let mut codec = HdlcCodec::new().with_accm(0x000A_0000);
+    let mut buf = BytesMut::new();
+    codec.encode(&[0x11, 0x13, 0x01][..], &mut buf).unwrap();
+    let mut noisy = BytesMut::new();
+    for byte in buf {
+        noisy.extend_from_slice(&[byte, 0x11]);
+    }
+    assert_eq!(
+        &codec.decode(&mut noisy).unwrap().unwrap()[..],
+        &[0x11, 0x13, 0x01]
+    );
+}
+
+#[test]
+fn bad_fcs_is_reported_and_skipped() {
+    let mut codec = HdlcCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode(&b"first"[..], &mut buf).unwrap();
+    buf[2] ^= 0x01;
+    codec.encode(&b"second"[..], &mut buf).unwrap();
+    buf.extend_from_slice(&[0x7E, 0x41, 0x7E]);
+    codec.encode(&b"third"[..], &mut buf).unwrap();
+
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(HdlcCodecError::BadFcs)
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"second");
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(HdlcCodecError::TooShort)
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"third");
+}
+
+#[test]
+fn aborted_frames_are_dropped() {
+    let mut codec = HdlcCodec::new();
+    let mut buf = BytesMut::from(&[0x7E, 0x01, 0x02, 0x7D, 0x7E][..]);
+    codec.encode(&b"ok"[..], &mut buf).unwrap();
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"ok");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn round_trip_over_pty() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_serial::{frame::SerialFramed, SerialStream};
+
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let codec = HdlcCodec::new().with_fcs(Fcs::Fcs32);
+    let mut master = SerialFramed::new(master, codec.clone());
+    let mut slave = SerialFramed::new(slave, codec);
+
+    let frame: Vec<u8> = (0..=255).collect();
+    master.send(frame.as_slice()).await.unwrap();
+    assert_eq!(&slave.next().await.unwrap().unwrap()[..], &frame[..]);
+}
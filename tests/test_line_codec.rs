@@ -0,0 +1,75 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{LineCodec, LineCodecError, LineTerminator, Utf8Policy};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn decodes_lines_split_across_reads() {
+    let mut codec = LineCodec::new().with_terminator(LineTerminator::CrLf);
+    let mut buf = BytesMut::from(&b"hello\r"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    buf.extend_from_slice(b"\nworld\r\n");
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("hello"));
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("world"));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn any_of_terminators() {
+    let mut codec = LineCodec::new().with_terminator(LineTerminator::AnyOf(b"\r;".to_vec()));
+    let mut buf = BytesMut::from(&b"a;b\rc"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("a"));
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("b"));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert_eq!(codec.decode_eof(&mut buf).unwrap().as_deref(), Some("c"));
+}
+
+#[test]
+fn overlong_lines_are_skipped() {
+    let mut codec = LineCodec::new().with_max_length(4);
+    let mut buf = BytesMut::from(&b"0123456"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(LineCodecError::MaxLineLengthExceeded)
+    ));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    buf.extend_from_slice(b"789\nok\n");
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("ok"));
+
+    let mut buf = BytesMut::from(&b"too long\nfine\n"[..]);
+    assert!(codec.decode(&mut buf).is_err());
+    assert_eq!(codec.decode(&mut buf).unwrap().as_deref(), Some("fine"));
+}
+
+#[test]
+fn utf8_policies() {
+    let mut buf = BytesMut::from(&b"caf\xe9\nok\n"[..]);
+    let mut strict = LineCodec::new();
+    assert!(matches!(
+        strict.decode(&mut buf),
+        Err(LineCodecError::InvalidUtf8(_))
+    ));
+    assert_eq!(strict.decode(&mut buf).unwrap().as_deref(), Some("ok"));
+
+    let mut buf = BytesMut::from(&b"caf\xe9\n"[..]);
+    let mut lossy = LineCodec::new().with_utf8_policy(Utf8Policy::Lossy);
+    assert_eq!(
+        lossy.decode(&mut buf).unwrap().as_deref(),
+        Some("caf\u{fffd}")
+    );
+
+    let mut buf = BytesMut::from(&b"caf\xe9\n"[..]);
+    let mut raw = LineCodec::raw();
+    assert_eq!(&raw.decode(&mut buf).unwrap().unwrap()[..], b"caf\xe9");
+}
+
+#[test]
+fn encoder_appends_terminator() {
+    let mut codec = LineCodec::new().with_terminator(LineTerminator::CrLf);
+    let mut buf = BytesMut::new();
+    codec.encode("AT", &mut buf).unwrap();
+    codec.encode(String::from("ATI"), &mut buf).unwrap();
+    assert_eq!(&buf[..], b"AT\r\nATI\r\n");
+}
@@ -0,0 +1,238 @@
+#![cfg(all(unix, feature = "modbus"))]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::modbus::{Client, ExceptionCode, ModbusError, Request, Transport};
+use tokio_serial::SerialStream;
+
+fn crc(frame: &[u8]) -> [u8; 2] {
+    // CRC-16/MODBUS, computed independently of the crate.
+    let mut crc = 0xFFFFu16;
+    for &byte in frame {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc.to_le_bytes()
+}
+
+fn with_crc(frame: &[u8]) -> Vec<u8> {
+    let mut adu = frame.to_vec();
+    adu.extend_from_slice(&crc(frame));
+    adu
+}
+
+async fn expect_request(server: &mut SerialStream, expected: &[u8]) {
+    let mut request = vec![0u8; expected.len()];
+    server.read_exact(&mut request).await.unwrap();
+    assert_eq!(request, expected);
+}
+
+fn client(port: SerialStream) -> Client {
+    Client::new(port)
+        .unwrap()
+        .with_timeout(Duration::from_millis(200))
+}
+
+#[tokio::test]
+async fn read_holding_registers() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master);
+
+    let server = tokio::spawn(async move {
+        expect_request(
+            &mut server,
+            &[0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC4, 0x0B],
+        )
+        .await;
+        let response = with_crc(&[0x01, 0x03, 0x04, 0x00, 0x0A, 0x01, 0x02]);
+        server.write_all(&response).await.unwrap();
+    });
+
+    let registers = client.read_holding_registers(1, 0, 2).await.unwrap();
+    assert_eq!(registers, vec![0x000A, 0x0102]);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn write_multiple_coils() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master);
+
+    let server = tokio::spawn(async move {
+        let request = [0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01];
+        expect_request(&mut server, &with_crc(&request)).await;
+        let response = with_crc(&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A]);
+        server.write_all(&response).await.unwrap();
+    });
+
+    let coils = [
+        true, false, true, true, false, false, true, true, true, false,
+    ];
+    client
+        .write_multiple_coils(0x11, 0x13, &coils)
+        .await
+        .unwrap();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn exception_response() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master);
+
+    let server = tokio::spawn(async move {
+        expect_request(
+            &mut server,
+            &with_crc(&[0x0A, 0x04, 0x10, 0x00, 0x00, 0x01]),
+        )
+        .await;
+        server
+            .write_all(&with_crc(&[0x0A, 0x84, 0x02]))
+            .await
+            .unwrap();
+    });
+
+    let err = client
+        .read_input_registers(10, 0x1000, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ModbusError::Exception {
+            function: 0x84,
+            code: ExceptionCode::IllegalDataAddress
+        }
+    ));
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn retries_after_timeout_and_bad_crc() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master).with_retries(2);
+
+    let server = tokio::spawn(async move {
+        let request = with_crc(&[0x01, 0x06, 0x00, 0x01, 0x12, 0x34]);
+        // Ignore the first attempt, then garble the answer to the second.
+        expect_request(&mut server, &request).await;
+        expect_request(&mut server, &request).await;
+        let mut garbled = request.clone();
+        garbled[7] ^= 0xFF;
+        server.write_all(&garbled).await.unwrap();
+        expect_request(&mut server, &request).await;
+        server.write_all(&request).await.unwrap();
+    });
+
+    client
+        .write_single_register(1, 0x0001, 0x1234)
+        .await
+        .unwrap();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn times_out_without_retries() {
+    let (master, _server) = SerialStream::pair().unwrap();
+    let mut client = client(master);
+
+    let err = client
+        .call_with_timeout(
+            1,
+            &Request::ReadCoils {
+                address: 0,
+                count: 8,
+            },
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ModbusError::Timeout));
+}
+
+#[tokio::test]
+async fn broadcast_is_not_answered() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master);
+
+    let request = Request::WriteSingleCoil {
+        address: 0x00AC,
+        value: true,
+    };
+    client.broadcast(&request).await.unwrap();
+    expect_request(
+        &mut server,
+        &with_crc(&[0x00, 0x05, 0x00, 0xAC, 0xFF, 0x00]),
+    )
+    .await;
+
+    let read = Request::ReadCoils {
+        address: 0,
+        count: 1,
+    };
+    assert!(matches!(
+        client.broadcast(&read).await,
+        Err(ModbusError::InvalidRequest(_))
+    ));
+    assert!(matches!(
+        client.call(0, &read).await,
+        Err(ModbusError::InvalidRequest(_))
+    ));
+    let too_many = Request::ReadHoldingRegisters {
+        address: 0,
+        count: 126,
+    };
+    assert!(matches!(
+        client.call(1, &too_many).await,
+        Err(ModbusError::InvalidRequest(_))
+    ));
+}
+
+#[tokio::test]
+async fn ascii_transport() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master).with_transport(Transport::Ascii);
+
+    let server = tokio::spawn(async move {
+        expect_request(&mut server, b":010300000002FA\r\n").await;
+        server.write_all(b":010304000A0102EB\r\n").await.unwrap();
+    });
+
+    let registers = client.read_holding_registers(1, 0, 2).await.unwrap();
+    assert_eq!(registers, vec![0x000A, 0x0102]);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn counts_lrc_errors_apart_from_timeouts() {
+    let (master, mut server) = SerialStream::pair().unwrap();
+    let mut client = client(master)
+        .with_transport(Transport::Ascii)
+        .with_retries(2);
+
+    let server = tokio::spawn(async move {
+        let request = b":010600011234B2\r\n";
+        expect_request(&mut server, request).await;
+        expect_request(&mut server, request).await;
+        server.write_all(b":010600011234B3\r\n").await.unwrap();
+        expect_request(&mut server, request).await;
+        server.write_all(request).await.unwrap();
+    });
+
+    client
+        .write_single_register(1, 0x0001, 0x1234)
+        .await
+        .unwrap();
+    server.await.unwrap();
+
+    let counters = client.counters();
+    assert_eq!(counters.requests, 3);
+    assert_eq!(counters.timeouts, 1);
+    assert_eq!(counters.lrc_errors, 1);
+    assert_eq!(counters.crc_errors, 0);
+}
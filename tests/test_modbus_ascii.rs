@@ -0,0 +1,106 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{ModbusAsciiCodec, ModbusAsciiCodecError};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn encodes_hex_and_lrc() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut buf = BytesMut::new();
+    codec
+        .encode(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02][..], &mut buf)
+        .unwrap();
+    assert_eq!(&buf[..], b":010300000002FA\r\n");
+
+    codec.encode(&[0xF7, 0x10][..], &mut buf).unwrap();
+    assert_eq!(&buf[17..], b":F710F9\r\n");
+}
+
+#[test]
+fn decodes_frames() {
+    let mut codec = ModbusAsciiCodec::new();
+    // Noise before the start, lower case digits, and a bare LF are all accepted.
+    let mut buf = BytesMut::from(&b"\x00junk:0103040102abcdAA\r\n:F710f9\n"[..]);
+    let sum: u32 = [0x01, 0x03, 0x04, 0x01, 0x02, 0xAB, 0xCD].iter().sum();
+    let lrc = (sum as u8).wrapping_neg();
+    buf[20..22].copy_from_slice(format!("{:02X}", lrc).as_bytes());
+    assert_eq!(
+        &codec.decode(&mut buf).unwrap().unwrap()[..],
+        &[0x01, 0x03, 0x04, 0x01, 0x02, 0xAB, 0xCD]
+    );
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0xF7, 0x10]);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn decodes_in_pieces() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut buf = BytesMut::new();
+    for &byte in b":010300000002FA\r\n" {
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(&[byte]);
+    }
+    assert_eq!(
+        &codec.decode(&mut buf).unwrap().unwrap()[..],
+        &[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]
+    );
+}
+
+#[test]
+fn skips_bad_frames() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut buf = BytesMut::from(&b":010300000002FB\r\n:01030G\r\n:0103\r\n:F710F9\r\n"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(ModbusAsciiCodecError::BadLrc)
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(ModbusAsciiCodecError::InvalidFrame)
+    ));
+    // Too short for an LRC on top of a unit id and function code.
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(ModbusAsciiCodecError::InvalidFrame)
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0xF7, 0x10]);
+}
+
+#[test]
+fn restarts_on_colon() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut buf = BytesMut::from(&b":0103000:F710F9\r\n"[..]);
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0xF7, 0x10]);
+}
+
+#[test]
+fn max_frame_length() {
+    let mut codec = ModbusAsciiCodec::new().with_max_frame_length(2);
+    let mut buf = BytesMut::new();
+    assert!(matches!(
+        codec.encode(&[1u8, 2, 3][..], &mut buf),
+        Err(ModbusAsciiCodecError::FrameTooLong)
+    ));
+
+    let mut buf = BytesMut::from(&b":0102030405"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(ModbusAsciiCodecError::FrameTooLong)
+    ));
+    buf.extend_from_slice(b"0607\r\n:F710F9\r\n");
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0xF7, 0x10]);
+}
+
+#[test]
+fn drops_incomplete_frame_at_eof() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut buf = BytesMut::from(&b":F710F9\r\n:0103"[..]);
+    assert_eq!(
+        &codec.decode_eof(&mut buf).unwrap().unwrap()[..],
+        &[0xF7, 0x10]
+    );
+    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+    assert!(buf.is_empty());
+}
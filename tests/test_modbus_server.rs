@@ -0,0 +1,141 @@
+#![cfg(all(unix, feature = "modbus"))]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::modbus::{
+    Client, DataStore, ExceptionCode, MemoryStore, ModbusError, Request, RtuServer,
+};
+use tokio_serial::SerialStream;
+
+fn client(port: SerialStream) -> Client {
+    Client::new(port)
+        .unwrap()
+        .with_timeout(Duration::from_millis(200))
+}
+
+fn exception(err: ModbusError) -> ExceptionCode {
+    match err {
+        ModbusError::Exception { code, .. } => code,
+        err => panic!("expected an exception response, got {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn serves_all_tables() {
+    let (master, slave) = SerialStream::pair().unwrap();
+    let mut store = MemoryStore::new(16);
+    store.discrete_inputs[3] = true;
+    store.input_registers[1] = 0xBEEF;
+    let server = tokio::spawn(RtuServer::new(slave, 7, store).unwrap().run());
+    let mut client = client(master);
+
+    client.write_single_coil(7, 2, true).await.unwrap();
+    client
+        .write_multiple_coils(7, 8, &[true, false, true])
+        .await
+        .unwrap();
+    let coils = client.read_coils(7, 0, 11).await.unwrap();
+    assert_eq!(
+        coils,
+        [false, false, true, false, false, false, false, false, true, false, true]
+    );
+    assert_eq!(
+        client.read_discrete_inputs(7, 2, 3).await.unwrap(),
+        [false, true, false]
+    );
+    assert_eq!(
+        client.read_input_registers(7, 0, 2).await.unwrap(),
+        [0, 0xBEEF]
+    );
+
+    client.write_single_register(7, 0, 0x1234).await.unwrap();
+    client
+        .write_multiple_registers(7, 1, &[1, 2, 3])
+        .await
+        .unwrap();
+    let read = client
+        .read_write_multiple_registers(7, 0, 5, 4, &[4])
+        .await
+        .unwrap();
+    assert_eq!(read, [0x1234, 1, 2, 3, 4]);
+    assert_eq!(
+        client.read_holding_registers(7, 3, 2).await.unwrap(),
+        [3, 4]
+    );
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn refuses_bad_requests() {
+    let (master, slave) = SerialStream::pair().unwrap();
+    tokio::spawn(
+        RtuServer::new(slave, 1, MemoryStore::new(10))
+            .unwrap()
+            .run(),
+    );
+    let mut client = client(master);
+
+    let err = client.read_holding_registers(1, 8, 3).await.unwrap_err();
+    assert_eq!(exception(err), ExceptionCode::IllegalDataAddress);
+    let err = client.write_single_coil(1, 10, true).await.unwrap_err();
+    assert_eq!(exception(err), ExceptionCode::IllegalDataAddress);
+
+    // A store without input registers.
+    struct CoilsOnly;
+    impl DataStore for CoilsOnly {
+        fn read_coils(&mut self, _: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+            Ok(vec![true; usize::from(count)])
+        }
+    }
+    let (master, slave) = SerialStream::pair().unwrap();
+    tokio::spawn(RtuServer::new(slave, 1, CoilsOnly).unwrap().run());
+    let mut client = self::client(master);
+    assert_eq!(client.read_coils(1, 0, 2).await.unwrap(), [true, true]);
+    let err = client.read_input_registers(1, 0, 1).await.unwrap_err();
+    assert_eq!(exception(err), ExceptionCode::IllegalFunction);
+}
+
+#[tokio::test]
+async fn answers_unsupported_function_codes() {
+    let (mut master, slave) = SerialStream::pair().unwrap();
+    tokio::spawn(
+        RtuServer::new(slave, 1, MemoryStore::new(10))
+            .unwrap()
+            .run(),
+    );
+
+    // Report Server ID, with its CRC.
+    master.write_all(&[0x01, 0x11, 0xC0, 0x2C]).await.unwrap();
+    let mut response = [0u8; 5];
+    master.read_exact(&mut response).await.unwrap();
+    assert_eq!(&response[..3], &[0x01, 0x91, 0x01]);
+}
+
+#[tokio::test]
+async fn ignores_other_units_and_obeys_broadcasts() {
+    let (master, slave) = SerialStream::pair().unwrap();
+    let mut server = RtuServer::new(slave, 2, MemoryStore::new(4)).unwrap();
+    let mut client = client(master);
+
+    let request = Request::WriteSingleRegister {
+        address: 0,
+        value: 42,
+    };
+    let (call, process) = tokio::join!(client.call(3, &request), server.process());
+    process.unwrap();
+    assert!(matches!(call, Err(ModbusError::Timeout)));
+    assert_eq!(server.store().holding_registers[0], 0);
+
+    let (broadcast, process) = tokio::join!(client.broadcast(&request), server.process());
+    broadcast.unwrap();
+    process.unwrap();
+    assert_eq!(server.store().holding_registers[0], 42);
+
+    // Nothing was sent back for either request.
+    let mut buf = [0u8; 8];
+    let read =
+        tokio::time::timeout(Duration::from_millis(50), client.get_mut().read(&mut buf)).await;
+    assert!(read.is_err());
+}
@@ -0,0 +1,189 @@
+use futures_util::StreamExt;
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tokio_serial::{
+    ClearBuffer, DataBits, FlowControl, ModemEvents, ModemLine, Parity, SerialPort, StopBits,
+};
+
+/// A port whose CTS line is driven by the test.
+#[derive(Clone)]
+struct MockPort {
+    cts: Arc<AtomicBool>,
+    // The descriptor handed to `ModemEvents::watching`, if any.
+    #[cfg(unix)]
+    fd: RawFd,
+}
+
+#[cfg(unix)]
+impl AsRawFd for MockPort {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Read for MockPort {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+impl Write for MockPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for MockPort {
+    fn name(&self) -> Option<String> {
+        None
+    }
+    fn baud_rate(&self) -> tokio_serial::Result<u32> {
+        Ok(9600)
+    }
+    fn data_bits(&self) -> tokio_serial::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+    fn flow_control(&self) -> tokio_serial::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+    fn parity(&self) -> tokio_serial::Result<Parity> {
+        Ok(Parity::None)
+    }
+    fn stop_bits(&self) -> tokio_serial::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+    fn set_baud_rate(&mut self, _: u32) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn set_data_bits(&mut self, _: DataBits) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn set_flow_control(&mut self, _: FlowControl) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn set_parity(&mut self, _: Parity) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn set_stop_bits(&mut self, _: StopBits) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn set_timeout(&mut self, _: Duration) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn write_request_to_send(&mut self, _: bool) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn write_data_terminal_ready(&mut self, _: bool) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn read_clear_to_send(&mut self) -> tokio_serial::Result<bool> {
+        Ok(self.cts.load(Ordering::SeqCst))
+    }
+    fn read_data_set_ready(&mut self) -> tokio_serial::Result<bool> {
+        Ok(true)
+    }
+    fn read_ring_indicator(&mut self) -> tokio_serial::Result<bool> {
+        Ok(false)
+    }
+    fn read_carrier_detect(&mut self) -> tokio_serial::Result<bool> {
+        Ok(true)
+    }
+    fn bytes_to_read(&self) -> tokio_serial::Result<u32> {
+        Ok(0)
+    }
+    fn bytes_to_write(&self) -> tokio_serial::Result<u32> {
+        Ok(0)
+    }
+    fn clear(&self, _: ClearBuffer) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn try_clone(&self) -> tokio_serial::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+    fn set_break(&self) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+    fn clear_break(&self) -> tokio_serial::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn polling_reports_changes() {
+    let cts = Arc::new(AtomicBool::new(false));
+    let port = MockPort {
+        cts: cts.clone(),
+        #[cfg(unix)]
+        fd: -1,
+    };
+    let mut events = ModemEvents::polling(port, Duration::from_millis(5));
+
+    // raise CTS only after the first reading has established the initial levels
+    tokio::spawn(async move {
+        time::sleep(Duration::from_millis(20)).await;
+        cts.store(true, Ordering::SeqCst);
+    });
+
+    let change = time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("no modem event")
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.line, ModemLine::ClearToSend);
+    assert!(change.level);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn pty_falls_back_to_polling() {
+    let (master, _slave) = tokio_serial::SerialStream::pair().expect("unable to create ptty pair");
+    let cts = Arc::new(AtomicBool::new(false));
+    let port = MockPort {
+        cts: cts.clone(),
+        fd: master.as_raw_fd(),
+    };
+
+    // pseudo terminals have no modem lines, so watching them is given up on and the
+    // lines are read from the mock instead
+    let mut events = ModemEvents::watching(port, Duration::from_millis(5)).unwrap();
+    tokio::spawn(async move {
+        time::sleep(Duration::from_millis(50)).await;
+        cts.store(true, Ordering::SeqCst);
+    });
+
+    let change = time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("modem events did not fall back to polling")
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.line, ModemLine::ClearToSend);
+    assert!(change.level);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dropping_events_releases_the_port() {
+    let (_master, slave) = tokio_serial::SerialStream::pair().expect("unable to create ptty pair");
+    let path = slave.name().expect("pty has no name");
+    assert!(slave.exclusive());
+
+    let events = slave.modem_events().unwrap();
+    drop(events);
+    drop(slave);
+
+    // the helper thread's descriptor is closed too, so the exclusive port can be
+    // opened again
+    tokio_serial::SerialStream::open(&tokio_serial::new(path, 9600)).unwrap();
+}
@@ -0,0 +1,38 @@
+#![cfg(target_os = "linux")]
+//! Kept apart from the other modem tests, since the handler for `SIGRTMAX` is
+//! process-wide and this test has to set it up before the crate looks at it.
+
+use tokio_serial::SerialStream;
+
+extern "C" fn handler(_: libc::c_int) {}
+
+fn sigrtmax_handler() -> libc::sighandler_t {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        assert_eq!(
+            libc::sigaction(libc::SIGRTMAX(), std::ptr::null(), &mut action),
+            0
+        );
+        action.sa_sigaction
+    }
+}
+
+#[tokio::test]
+async fn keeps_an_existing_sigrtmax_handler() {
+    let ours = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = ours;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(
+            libc::sigaction(libc::SIGRTMAX(), &action, std::ptr::null_mut()),
+            0
+        );
+    }
+
+    let (_master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let events = slave.modem_events().unwrap();
+    drop(events);
+
+    assert_eq!(sigrtmax_handler(), ours);
+}
@@ -0,0 +1,271 @@
+#![cfg(feature = "nmea")]
+
+use bytes::BytesMut;
+use tokio_serial::nmea::{
+    Date, FixQuality, FixType, NmeaCodec, NmeaError, SatelliteInfo, Sentence, Talker, Time,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn parse(sentence: &str) -> Sentence {
+    Sentence::parse(sentence).unwrap()
+}
+
+fn assert_close(value: Option<f64>, expected: f64) {
+    let value = value.unwrap();
+    assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+}
+
+#[test]
+fn parses_gga() {
+    let gga = match parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47") {
+        Sentence::Gga(gga) => gga,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(gga.talker, Talker::Gps);
+    assert_eq!(
+        gga.time,
+        Some(Time {
+            hour: 12,
+            minute: 35,
+            second: 19,
+            millisecond: 0
+        })
+    );
+    assert_close(gga.latitude, 48.0 + 7.038 / 60.0);
+    assert_close(gga.longitude, 11.0 + 31.0 / 60.0);
+    assert_eq!(gga.fix_quality, FixQuality::Gps);
+    assert_eq!(gga.satellites, Some(8));
+    assert_eq!(gga.hdop, Some(0.9));
+    assert_eq!(gga.altitude, Some(545.4));
+    assert_eq!(gga.geoid_separation, Some(46.9));
+    assert_eq!(gga.dgps_age, None);
+    assert_eq!(gga.dgps_station, None);
+}
+
+#[test]
+fn parses_rmc() {
+    let rmc = match parse("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A") {
+        Sentence::Rmc(rmc) => rmc,
+        other => panic!("{:?}", other),
+    };
+    assert!(rmc.valid);
+    assert_eq!(rmc.speed_knots, Some(22.4));
+    assert_eq!(rmc.course, Some(84.4));
+    assert_eq!(
+        rmc.date,
+        Some(Date {
+            year: 1994,
+            month: 3,
+            day: 23
+        })
+    );
+    assert_eq!(rmc.magnetic_variation, Some(-3.1));
+    assert_eq!(rmc.mode, None);
+}
+
+#[test]
+fn parses_gsa_and_gsv() {
+    let gsa = match parse("$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39") {
+        Sentence::Gsa(gsa) => gsa,
+        other => panic!("{:?}", other),
+    };
+    assert!(gsa.automatic);
+    assert_eq!(gsa.fix_type, FixType::Fix3D);
+    assert_eq!(gsa.satellites, vec![4, 5, 9, 12, 24]);
+    assert_eq!(
+        (gsa.pdop, gsa.hdop, gsa.vdop),
+        (Some(2.5), Some(1.3), Some(2.1))
+    );
+    assert_eq!(gsa.system_id, None);
+
+    let gsv = match parse("$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75") {
+        Sentence::Gsv(gsv) => gsv,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!((gsv.sentence_count, gsv.sentence_number), (2, 1));
+    assert_eq!(gsv.satellites_in_view, 8);
+    assert_eq!(gsv.satellites.len(), 4);
+    assert_eq!(
+        gsv.satellites[3],
+        SatelliteInfo {
+            id: 14,
+            elevation: Some(22),
+            azimuth: Some(228),
+            snr: Some(45)
+        }
+    );
+    assert_eq!(gsv.signal_id, None);
+
+    // NMEA 4.1 adds a signal id after the satellites.
+    let gsv = match parse("$GNGSV,1,1,02,65,10,020,30,66,,,,1*4B") {
+        Sentence::Gsv(gsv) => gsv,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(gsv.talker, Talker::Gnss);
+    assert_eq!(gsv.satellites[1].snr, None);
+    assert_eq!(gsv.signal_id, Some(1));
+}
+
+#[test]
+fn parses_vtg_gll_and_zda() {
+    let vtg = match parse("$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48") {
+        Sentence::Vtg(vtg) => vtg,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(vtg.course_true, Some(54.7));
+    assert_eq!(vtg.course_magnetic, Some(34.4));
+    assert_eq!(vtg.speed_knots, Some(5.5));
+    assert_eq!(vtg.speed_kmh, Some(10.2));
+
+    let gll = match parse("$GPGLL,4916.45,N,12311.12,W,225444,A,*1D") {
+        Sentence::Gll(gll) => gll,
+        other => panic!("{:?}", other),
+    };
+    assert_close(gll.latitude, 49.0 + 16.45 / 60.0);
+    assert_close(gll.longitude, -(123.0 + 11.12 / 60.0));
+    assert!(gll.valid);
+    assert_eq!(gll.mode, None);
+
+    let zda = match parse("$GPZDA,201530.00,04,07,2002,00,00*60") {
+        Sentence::Zda(zda) => zda,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(
+        zda.date,
+        Some(Date {
+            year: 2002,
+            month: 7,
+            day: 4
+        })
+    );
+    assert_eq!(zda.time.unwrap().hour, 20);
+    assert_eq!(zda.local_zone_hours, Some(0));
+}
+
+#[test]
+fn passes_unknown_sentences_through() {
+    let sentence = "$PUBX,00,081350.00,4717.113210,N,00833.915187,E,546.589,G3,2.1,2.0,0.007,77.52,0.007,,0.92,1.19,0.77,9,0,0*5F";
+    let raw = match parse(sentence) {
+        Sentence::Unknown(raw) => raw,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(raw.as_str(), sentence);
+    assert!(raw.is_proprietary());
+    assert_eq!(raw.talker(), None);
+    assert_eq!(raw.sentence_type(), "PUBX");
+    assert_eq!(raw.fields().nth(1), Some("081350.00"));
+
+    let raw = match parse("$GPTXT,01,01,02,ANTSTATUS=OK*3B") {
+        Sentence::Unknown(raw) => raw,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(raw.talker(), Some(Talker::Gps));
+    assert_eq!(raw.sentence_type(), "TXT");
+    assert_eq!(raw.fields().last(), Some("ANTSTATUS=OK"));
+}
+
+#[test]
+fn rejects_bad_sentences() {
+    assert!(matches!(
+        Sentence::parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48"),
+        Err(NmeaError::BadChecksum {
+            computed: 0x47,
+            received: 0x48
+        })
+    ));
+    assert!(matches!(
+        Sentence::parse("$GPGGA,12351,,,,,0,,,,,,,,*52"),
+        Err(NmeaError::InvalidSentence(_))
+    ));
+    assert!(matches!(
+        Sentence::parse("GPGGA,123519"),
+        Err(NmeaError::InvalidSentence(_))
+    ));
+}
+
+#[test]
+fn decodes_lines() {
+    let mut codec = NmeaCodec::new();
+    let mut buf = BytesMut::from(
+        &b"38.0,M,,*4F\r\n\
+           $GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*49\r\n\
+           $GPZDA,201530.00,04,07,2002,00,00\r\n\
+           $GPTXT,01,01,02,ANTSTATUS=OK*3B\r\n\
+           $GPZDA,201530.00,04,07,2002,00,00*60\r\n\
+           $GPGLL,4916.45"[..],
+    );
+
+    // The partial line at the start is skipped, and bad sentences don't stop decoding.
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(NmeaError::BadChecksum { .. })
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(NmeaError::MissingChecksum)
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Ok(Some(Sentence::Unknown(_)))
+    ));
+    assert!(matches!(codec.decode(&mut buf), Ok(Some(Sentence::Zda(_)))));
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+    assert!(buf.is_empty());
+
+    let mut codec = NmeaCodec::new().with_checksum_required(false);
+    let mut buf = BytesMut::from(&b"$GPZDA,201530.00,04,07,2002,00,00\n"[..]);
+    assert!(matches!(codec.decode(&mut buf), Ok(Some(Sentence::Zda(_)))));
+}
+
+#[test]
+fn max_sentence_length() {
+    let mut codec = NmeaCodec::new().with_max_sentence_length(32);
+    let mut buf = BytesMut::from(&b"$GPGGA,123519,4807.038,N,01131.000,E,1,08"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(NmeaError::SentenceTooLong)
+    ));
+    buf.extend_from_slice(b",0.9,545.4,M,46.9,M,,*47\r\n$GPTXT,01,01,02,ANTSTATUS=OK*3B\r\n");
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Ok(Some(Sentence::Unknown(_)))
+    ));
+}
+
+#[test]
+fn encodes_with_checksum() {
+    let mut codec = NmeaCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode("$PMTK220,1000", &mut buf).unwrap();
+    assert_eq!(&buf[..], b"$PMTK220,1000*1F\r\n");
+    assert!(matches!(
+        codec.encode("PMTK220,1000", &mut buf),
+        Err(NmeaError::InvalidSentence(_))
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn checksum_errors_do_not_end_the_stream() {
+    use futures_util::stream::StreamExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio_serial::frame::SerialFramed;
+    use tokio_serial::SerialStream;
+
+    let (mut receiver, port) = SerialStream::pair().unwrap();
+    let mut sentences = SerialFramed::new(port, NmeaCodec::new());
+
+    receiver
+        .write_all(
+            b"$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*49\r\n\
+              $GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\r\n",
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        sentences.next().await,
+        Some(Err(NmeaError::BadChecksum { .. }))
+    ));
+    assert!(matches!(sentences.next().await, Some(Ok(Sentence::Vtg(_)))));
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{Crc, Endianness, PacketCodec, PacketCodecError};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn crc_check_values() {
+    let check = b"123456789";
+    assert_eq!(Crc::CRC_8_SMBUS.checksum(check), 0xF4);
+    assert_eq!(Crc::CRC_16_MODBUS.checksum(check), 0x4B37);
+    assert_eq!(Crc::CRC_16_XMODEM.checksum(check), 0x31C3);
+    assert_eq!(Crc::CRC_16_IBM_SDLC.checksum(check), 0x906E);
+    assert_eq!(Crc::CRC_32_ISO_HDLC.checksum(check), 0xCBF4_3926);
+}
+
+#[test]
+fn encodes_header_and_crc() {
+    let mut codec = PacketCodec::new(&[0xAA, 0x55][..]);
+    let mut buf = BytesMut::new();
+    codec.encode(&b"123456789"[..], &mut buf).unwrap();
+    let crc = Crc::CRC_16_XMODEM.checksum(b"\x00\x09123456789") as u16;
+    let mut expected = b"\xAA\x55\x00\x09123456789".to_vec();
+    expected.extend(&crc.to_be_bytes());
+    assert_eq!(&buf[..], &expected[..]);
+
+    let mut codec = PacketCodec::new(&[0x7E][..])
+        .with_length_field(4, Endianness::Little)
+        .with_length_includes_header(true)
+        .with_crc(Some(Crc::CRC_32_ISO_HDLC));
+    let mut buf = BytesMut::new();
+    codec.encode(&b"abc"[..], &mut buf).unwrap();
+    assert_eq!(&buf[..5], b"\x7E\x08\x00\x00\x00");
+    assert_eq!(buf.len(), 5 + 3 + 4);
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"abc");
+    assert!(buf.is_empty());
+
+    let mut codec = PacketCodec::new(&[0x7E][..]).with_length_field(1, Endianness::Big);
+    assert!(matches!(
+        codec.encode(&[0u8; 256][..], &mut BytesMut::new()),
+        Err(PacketCodecError::PayloadTooLong)
+    ));
+}
+
+#[test]
+fn decodes_packets_split_across_reads() {
+    let mut codec = PacketCodec::new(&[0xAA, 0x55][..]).with_crc(Some(Crc::CRC_8_SMBUS));
+    let mut encoded = BytesMut::new();
+    codec.encode(&b"hello"[..], &mut encoded).unwrap();
+
+    let mut buf = BytesMut::from(&b"noise\xAA"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert_eq!(&buf[..], b"\xAA");
+    buf.extend_from_slice(&encoded[1..6]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    buf.extend_from_slice(&encoded[6..]);
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"hello");
+}
+
+#[test]
+fn hunts_for_sync_after_bad_crc() {
+    let mut codec = PacketCodec::new(&[0xAA][..]).with_length_field(1, Endianness::Big);
+    let mut inner = BytesMut::new();
+    codec.encode(&b"inner"[..], &mut inner).unwrap();
+
+    // a corrupted packet whose payload happens to contain a whole valid packet
+    let mut outer = BytesMut::new();
+    codec.encode(&inner[..], &mut outer).unwrap();
+    let last = outer.len() - 1;
+    outer[last] ^= 0xFF;
+
+    assert!(matches!(
+        codec.decode(&mut outer),
+        Err(PacketCodecError::BadCrc)
+    ));
+    assert_eq!(&codec.decode(&mut outer).unwrap().unwrap()[..], b"inner");
+
+    let mut codec = codec.with_max_payload_length(4);
+    let mut buf = BytesMut::from(&b"\xAA\x10rest"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(PacketCodecError::InvalidLength)
+    ));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn rejects_huge_lengths_by_default() {
+    let mut codec = PacketCodec::new(&[0xAA][..]).with_length_field(4, Endianness::Big);
+    assert_eq!(codec.max_payload_length(), 8 * 1024 * 1024);
+
+    // noise that looks like the header of a 4 GiB packet
+    let mut buf = BytesMut::from(&b"\xAA\xFF\xFF\xFF\xF0"[..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(PacketCodecError::InvalidLength)
+    ));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(buf.capacity() < 1024);
+
+    codec.encode(&b"ok"[..], &mut buf).unwrap();
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"ok");
+
+    // a length within the limit waits for the packet without allocating for it
+    let mut buf = BytesMut::from(&b"\xAA\x00\x10\x00\x00"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(buf.capacity() < 1024);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn round_trip_over_pty() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_serial::{frame::SerialFramed, SerialStream};
+
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let codec = PacketCodec::new(&b"SY"[..]).with_crc(Some(Crc::CRC_16_MODBUS));
+    let mut master = SerialFramed::new(master, codec.clone());
+    let mut slave = SerialFramed::new(slave, codec);
+
+    let payload: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+    master.send(payload.as_slice()).await.unwrap();
+    master.send(&b""[..]).await.unwrap();
+    assert_eq!(&slave.next().await.unwrap().unwrap()[..], &payload[..]);
+    assert!(slave.next().await.unwrap().unwrap().is_empty());
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use tokio_serial::frame::{SlipCodec, SlipCodecError};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn escapes_special_bytes() {
+    let mut codec = SlipCodec::new();
+    let mut buf = BytesMut::new();
+    codec
+        .encode(&[0x01, 0xC0, 0xDB, 0x02][..], &mut buf)
+        .unwrap();
+    assert_eq!(&buf[..], &[0xC0, 0x01, 0xDB, 0xDC, 0xDB, 0xDD, 0x02, 0xC0]);
+
+    let mut codec = SlipCodec::new().with_leading_end(false);
+    let mut buf = BytesMut::new();
+    codec.encode(&[0x01][..], &mut buf).unwrap();
+    assert_eq!(&buf[..], &[0x01, 0xC0]);
+}
+
+#[test]
+fn decodes_frames_split_across_reads() {
+    let mut codec = SlipCodec::new();
+    let mut buf = BytesMut::from(&[0xC0, 0xC0, 0x01, 0xDB][..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    buf.extend_from_slice(&[0xDC, 0x02, 0xC0, 0x03]);
+    assert_eq!(
+        &codec.decode(&mut buf).unwrap().unwrap()[..],
+        &[0x01, 0xC0, 0x02]
+    );
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert_eq!(codec.decode_eof(&mut buf).unwrap(), None);
+}
+
+#[test]
+fn recovers_from_bad_frames() {
+    let mut codec = SlipCodec::new().with_max_frame_length(3);
+    let mut buf = BytesMut::from(&[0x01, 0xDB, 0x05, 0x02, 0xC0, 0x0A, 0xC0][..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(SlipCodecError::InvalidEscape(0x05))
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0x0A]);
+
+    let mut buf = BytesMut::from(&[0x01, 0x02, 0x03, 0x04, 0x05, 0xC0, 0x0B, 0xC0][..]);
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(SlipCodecError::FrameTooLong)
+    ));
+    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[0x0B]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn round_trip_over_pty() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_serial::{frame::SerialFramed, SerialStream};
+
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut master = SerialFramed::new(master, SlipCodec::new());
+    let mut slave = SerialFramed::new(slave, SlipCodec::new());
+
+    let frames: Vec<Vec<u8>> = vec![
+        vec![0xC0, 0xDB, 0xDC, 0xDD],
+        (0..=255).collect(),
+        b"plain".to_vec(),
+    ];
+    for frame in &frames {
+        master.send(frame.as_slice()).await.unwrap();
+    }
+    for frame in &frames {
+        assert_eq!(&slave.next().await.unwrap().unwrap()[..], &frame[..]);
+    }
+}
@@ -0,0 +1,51 @@
+#![cfg(unix)]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::{SerialPort, SerialStream};
+
+#[tokio::test]
+async fn owned_halves_in_separate_tasks() {
+    let (master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let (mut master_rx, mut master_tx) = master.into_split();
+
+    let echo = tokio::spawn(async move {
+        let mut slave = slave;
+        let mut buf = [0u8; 5];
+        slave.read_exact(&mut buf).await.unwrap();
+        slave.write_all(&buf).await.unwrap();
+    });
+
+    let reader = tokio::spawn(async move {
+        let mut buf = [0u8; 5];
+        master_rx.read_exact(&mut buf).await.unwrap();
+        (master_rx, buf)
+    });
+
+    master_tx.write_all(b"hello").await.unwrap();
+    let (master_rx, buf) = reader.await.unwrap();
+    echo.await.unwrap();
+    assert_eq!(&buf, b"hello");
+
+    master_rx
+        .reunite(master_tx)
+        .expect("halves of the same port should reunite");
+}
+
+#[tokio::test]
+async fn reunite_mismatched_halves() {
+    let (a, b) = SerialStream::pair().expect("unable to create ptty pair");
+    let (a_rx, _a_tx) = a.into_split();
+    let (_b_rx, b_tx) = b.into_split();
+
+    assert!(a_rx.reunite(b_tx).is_err());
+}
+
+#[tokio::test]
+async fn write_half_reaches_settings() {
+    let (master, _slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let (_rx, tx) = master.into_split();
+
+    let mut port = tx.as_ref();
+    port.set_baud_rate(19200).unwrap();
+    assert_eq!(port.baud_rate().unwrap(), 19200);
+}
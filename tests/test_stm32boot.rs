@@ -0,0 +1,326 @@
+#![cfg(all(feature = "stm32boot", unix))]
+
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::JoinHandle;
+use tokio_serial::stm32boot::{Bootloader, BootloaderError, Control, Erase, Wiring};
+use tokio_serial::{DataBits, Parity, SerialPort, SerialStream, StopBits};
+
+const ACK: u8 = 0x79;
+const NACK: u8 = 0x1F;
+const FLASH: u32 = 0x0800_0000;
+const PAGE: usize = 1024;
+
+/// A bootloader with four pages of flash.
+struct Device {
+    port: SerialStream,
+    extended: bool,
+    flash: Vec<u8>,
+    go: Option<u32>,
+}
+
+impl Device {
+    fn start(port: SerialStream, extended: bool) -> JoinHandle<Device> {
+        let device = Device {
+            port,
+            extended,
+            flash: (0..4 * PAGE).map(|i| i as u8).collect(),
+            go: None,
+        };
+        tokio::spawn(device.run())
+    }
+
+    fn commands(&self) -> Vec<u8> {
+        let erase = if self.extended { 0x44 } else { 0x43 };
+        vec![0x00, 0x01, 0x02, 0x11, 0x21, 0x31, erase]
+    }
+
+    async fn run(mut self) -> Device {
+        while self.port.read_u8().await.unwrap() != 0x7F {}
+        self.port.write_u8(ACK).await.unwrap();
+        loop {
+            let code = match self.port.read_u8().await {
+                Ok(code) => code,
+                Err(_) => return self,
+            };
+            let complement = self.port.read_u8().await.unwrap();
+            if code ^ complement != 0xFF || !self.commands().contains(&code) {
+                self.port.write_u8(NACK).await.unwrap();
+                continue;
+            }
+            self.port.write_u8(ACK).await.unwrap();
+            let reply = match code {
+                0x00 => {
+                    let commands = self.commands();
+                    let mut reply = vec![commands.len() as u8, 0x31];
+                    reply.extend(commands);
+                    self.port.write_all(&reply).await.unwrap();
+                    ACK
+                }
+                0x02 => {
+                    self.port.write_all(&[1, 0x04, 0x10]).await.unwrap();
+                    ACK
+                }
+                0x11 => {
+                    let at = match self.address().await {
+                        Some(at) => at,
+                        None => continue,
+                    };
+                    let len = self.port.read_u8().await.unwrap();
+                    let complement = self.port.read_u8().await.unwrap();
+                    let end = at + usize::from(len) + 1;
+                    if len ^ complement != 0xFF || end > self.flash.len() {
+                        NACK
+                    } else {
+                        self.port.write_u8(ACK).await.unwrap();
+                        let data = self.flash[at..end].to_vec();
+                        self.port.write_all(&data).await.unwrap();
+                        continue;
+                    }
+                }
+                0x21 => {
+                    let at = match self.address().await {
+                        Some(at) => at,
+                        None => continue,
+                    };
+                    self.port.write_u8(ACK).await.unwrap();
+                    self.go = Some(FLASH + at as u32);
+                    return self;
+                }
+                0x31 => {
+                    let at = match self.address().await {
+                        Some(at) => at,
+                        None => continue,
+                    };
+                    let len = usize::from(self.port.read_u8().await.unwrap()) + 1;
+                    let mut data = vec![0; len + 1];
+                    self.port.read_exact(&mut data).await.unwrap();
+                    let sum = data.iter().fold((len - 1) as u8, |sum, byte| sum ^ byte);
+                    if sum != 0 || len % 4 != 0 || at + len > self.flash.len() {
+                        NACK
+                    } else {
+                        self.flash[at..at + len].copy_from_slice(&data[..len]);
+                        ACK
+                    }
+                }
+                0x43 => {
+                    let len = self.port.read_u8().await.unwrap();
+                    if len == 0xFF {
+                        assert_eq!(self.port.read_u8().await.unwrap(), 0x00);
+                        self.flash.iter_mut().for_each(|byte| *byte = 0xFF);
+                    } else {
+                        let mut pages = vec![0; usize::from(len) + 2];
+                        self.port.read_exact(&mut pages).await.unwrap();
+                        assert_eq!(pages.iter().fold(len, |sum, byte| sum ^ byte), 0);
+                        for &page in &pages[..pages.len() - 1] {
+                            self.erase(usize::from(page));
+                        }
+                    }
+                    ACK
+                }
+                0x44 => {
+                    let len = self.port.read_u16().await.unwrap();
+                    let mut rest = vec![
+                        0;
+                        if len >= 0xFFF0 {
+                            1
+                        } else {
+                            usize::from(len) * 2 + 3
+                        }
+                    ];
+                    self.port.read_exact(&mut rest).await.unwrap();
+                    let sum = len
+                        .to_be_bytes()
+                        .iter()
+                        .chain(&rest)
+                        .fold(0, |sum, byte| sum ^ byte);
+                    assert_eq!(sum, 0);
+                    match len {
+                        0xFFFF => self.flash.iter_mut().for_each(|byte| *byte = 0xFF),
+                        0xFFFE | 0xFFFD => {}
+                        _ => {
+                            for page in rest[..rest.len() - 1].chunks(2) {
+                                self.erase(usize::from(u16::from_be_bytes([page[0], page[1]])));
+                            }
+                        }
+                    }
+                    ACK
+                }
+                _ => NACK,
+            };
+            self.port.write_u8(reply).await.unwrap();
+        }
+    }
+
+    /// Reads an address, and acknowledges it if it is in flash.
+    async fn address(&mut self) -> Option<usize> {
+        let mut bytes = [0u8; 5];
+        self.port.read_exact(&mut bytes).await.unwrap();
+        let address = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let valid = bytes.iter().fold(0, |sum, byte| sum ^ byte) == 0
+            && (FLASH..FLASH + self.flash.len() as u32).contains(&address);
+        if valid {
+            self.port.write_u8(ACK).await.unwrap();
+            Some((address - FLASH) as usize)
+        } else {
+            self.port.write_u8(NACK).await.unwrap();
+            None
+        }
+    }
+
+    fn erase(&mut self, page: usize) {
+        self.flash[page * PAGE..(page + 1) * PAGE]
+            .iter_mut()
+            .for_each(|byte| *byte = 0xFF);
+    }
+}
+
+fn bootloader(extended: bool) -> (Bootloader, JoinHandle<Device>) {
+    let (host, device) = SerialStream::pair().unwrap();
+    let boot = Bootloader::new(host).with_timeout(Duration::from_millis(500));
+    (boot, Device::start(device, extended))
+}
+
+#[tokio::test]
+async fn enters_and_identifies() {
+    let (mut boot, device) = bootloader(true);
+    // Linux ptys always read back as 8N1, whatever they are set to.
+    boot.get_mut().set_data_bits(DataBits::Seven).unwrap();
+    boot.get_mut().set_parity(Parity::Odd).unwrap();
+    boot.get_mut().set_stop_bits(StopBits::Two).unwrap();
+    let keeps_framing = boot.get_ref().parity().unwrap() == Parity::Odd;
+    boot.enter().await.unwrap();
+
+    let port = boot.get_ref();
+    assert_eq!(port.data_bits().unwrap(), DataBits::Eight);
+    assert_eq!(port.stop_bits().unwrap(), StopBits::One);
+    if keeps_framing {
+        assert_eq!(port.parity().unwrap(), Parity::Even);
+    }
+
+    let info = boot.get().await.unwrap();
+    assert_eq!(info.version, 0x31);
+    assert_eq!(info.commands, [0x00, 0x01, 0x02, 0x11, 0x21, 0x31, 0x44]);
+    assert_eq!(boot.get_id().await.unwrap(), 0x410);
+    // Syncing again is turned down, which is fine.
+    boot.sync().await.unwrap();
+    assert_eq!(boot.get_id().await.unwrap(), 0x410);
+
+    drop(boot);
+    device.await.unwrap();
+}
+
+#[tokio::test]
+async fn writes_reads_and_runs() {
+    let (mut boot, device) = bootloader(true);
+    boot.enter().await.unwrap();
+
+    let firmware: Vec<u8> = (0..601).map(|i| (i * 3) as u8).collect();
+    boot.write_memory(FLASH + 0x100, &firmware).await.unwrap();
+    let mut read = vec![0; 603];
+    boot.read_memory(FLASH + 0x100, &mut read).await.unwrap();
+    assert_eq!(read[..601], firmware[..]);
+    // Padded to a whole word.
+    assert_eq!(read[601..], [0xFF, 0xFF]);
+
+    boot.go(FLASH).await.unwrap();
+    assert_eq!(device.await.unwrap().go, Some(FLASH));
+}
+
+#[tokio::test]
+async fn erases_with_either_command() {
+    for &extended in &[true, false] {
+        let (mut boot, device) = bootloader(extended);
+        boot.enter().await.unwrap();
+
+        boot.erase(Erase::Pages(&[1, 3])).await.unwrap();
+        let mut flash = vec![0; 4 * PAGE];
+        boot.read_memory(FLASH, &mut flash).await.unwrap();
+        assert!(flash[..PAGE].iter().enumerate().all(|(i, &b)| b == i as u8));
+        assert!(flash[PAGE..2 * PAGE].iter().all(|&b| b == 0xFF));
+        assert!(flash[3 * PAGE..].iter().all(|&b| b == 0xFF));
+
+        if extended {
+            boot.erase(Erase::Bank2).await.unwrap();
+        } else {
+            assert!(matches!(
+                boot.erase(Erase::Bank2).await,
+                Err(BootloaderError::Unsupported)
+            ));
+            assert!(matches!(
+                boot.erase(Erase::Pages(&[256])).await,
+                Err(BootloaderError::Io(_))
+            ));
+        }
+        boot.erase(Erase::All).await.unwrap();
+        boot.read_memory(FLASH, &mut flash).await.unwrap();
+        assert!(flash.iter().all(|&b| b == 0xFF));
+
+        drop(boot);
+        device.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn reports_nacks_and_timeouts() {
+    let (mut boot, device) = bootloader(true);
+    boot.enter().await.unwrap();
+    assert!(matches!(
+        boot.write_memory(0x2000_0000, &[1, 2, 3, 4]).await,
+        Err(BootloaderError::Nack)
+    ));
+    let mut buf = [0; 16];
+    assert!(matches!(
+        boot.read_memory(FLASH + 4 * PAGE as u32 - 8, &mut buf)
+            .await,
+        Err(BootloaderError::Nack)
+    ));
+    // The bootloader carries on after turning something down.
+    assert_eq!(boot.get_id().await.unwrap(), 0x410);
+    drop(boot);
+    device.await.unwrap();
+
+    let (host, _device) = SerialStream::pair().unwrap();
+    let mut boot = Bootloader::new(host).with_timeout(Duration::from_millis(50));
+    assert!(matches!(boot.enter().await, Err(BootloaderError::Timeout)));
+}
+
+#[tokio::test]
+async fn drives_boot0_and_nrst() {
+    // A pty has no modem control outputs, so driving a wired pin fails before the
+    // bootloader is spoken to, with BOOT0 driven whether or not NRST is wired.
+    let wirings = [
+        Wiring::new().with_boot0(Control::RequestToSend, false),
+        Wiring::new().with_reset(Control::DataTerminalReady, true),
+        Wiring::new()
+            .with_boot0(Control::RequestToSend, false)
+            .with_reset(Control::DataTerminalReady, true),
+    ];
+    for &wiring in &wirings {
+        let (host, device) = SerialStream::pair().unwrap();
+        let mut boot = Bootloader::new(host).with_wiring(wiring);
+        assert!(matches!(boot.enter().await, Err(BootloaderError::Io(_))));
+        let err = device.try_read(&mut [0; 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    // Leaving needs NRST, and nothing is driven without it.
+    let wirings = [
+        Wiring::new(),
+        Wiring::new().with_boot0(Control::RequestToSend, false),
+    ];
+    for &wiring in &wirings {
+        let (mut boot, device) = bootloader(true);
+        boot.enter().await.unwrap();
+        boot = boot.with_wiring(wiring);
+        match boot.leave().await {
+            Err(BootloaderError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidInput),
+            other => panic!("unexpected {:?}", other),
+        }
+        // Still in the bootloader.
+        assert_eq!(boot.get_id().await.unwrap(), 0x410);
+        drop(boot);
+        device.await.unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+use std::io;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time,
+};
+use tokio_serial::{SerialPort, SerialStream};
+
+#[tokio::test]
+async fn read_times_out_without_data() {
+    let (_master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    slave.set_timeout(Duration::from_millis(50)).unwrap();
+    assert_eq!(slave.timeout(), Duration::from_millis(50));
+
+    let mut buf = [0u8; 8];
+    let err = time::timeout(Duration::from_secs(5), slave.read(&mut buf))
+        .await
+        .expect("read did not time out")
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn cancelled_read_does_not_shorten_the_next() {
+    let (_master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    slave.set_timeout(Duration::from_millis(300)).unwrap();
+
+    let mut buf = [0u8; 8];
+    time::timeout(Duration::from_millis(200), slave.read(&mut buf))
+        .await
+        .expect_err("read completed without data");
+
+    let start = time::Instant::now();
+    let err = slave.read(&mut buf).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn inter_byte_timeout_collects_until_idle() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    slave.set_timeout(Duration::from_secs(5)).unwrap();
+    slave.set_inter_byte_timeout(Some(Duration::from_millis(200)));
+
+    tokio::spawn(async move {
+        master.write_all(b"abc").await.unwrap();
+        time::sleep(Duration::from_millis(20)).await;
+        master.write_all(b"def").await.unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let mut buf = [0u8; 32];
+    let n = slave.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"abcdef");
+}
+
+#[tokio::test]
+async fn inter_byte_timeout_set_through_a_split_half() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let (mut reader, writer) = slave.into_split();
+    writer
+        .as_ref()
+        .set_inter_byte_timeout(Some(Duration::from_millis(200)));
+    assert_eq!(
+        reader.as_ref().inter_byte_timeout(),
+        Some(Duration::from_millis(200))
+    );
+
+    tokio::spawn(async move {
+        master.write_all(b"abc").await.unwrap();
+        time::sleep(Duration::from_millis(20)).await;
+        master.write_all(b"def").await.unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+    });
+
+    let mut buf = [0u8; 32];
+    let n = reader.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"abcdef");
+}
+
+#[tokio::test]
+async fn cancelled_read_deadline_passing_does_not_fail_the_next() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    slave.set_timeout(Duration::from_millis(200)).unwrap();
+
+    let mut buf = [0u8; 8];
+    time::timeout(Duration::from_millis(50), slave.read(&mut buf))
+        .await
+        .expect_err("read completed without data");
+    // The deadline of the cancelled read passes meanwhile.
+    time::sleep(Duration::from_millis(300)).await;
+
+    tokio::spawn(async move {
+        time::sleep(Duration::from_millis(20)).await;
+        master.write_all(b"late").await.unwrap();
+        time::sleep(Duration::from_secs(1)).await;
+    });
+    let n = slave.read(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"late");
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "ubx")]
+
+use bytes::BytesMut;
+use tokio_serial::nmea::{NmeaError, Sentence};
+use tokio_serial::ubx::{Message, UbxCodec, UbxError};
+use tokio_util::codec::{Decoder, Encoder};
+
+const VTG: &[u8] = b"$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48\r\n";
+// UBX-ACK-ACK for UBX-CFG-PRT.
+const ACK_PRT: &[u8] = &[0xB5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x00, 0x0E, 0x37];
+// UBX-ACK-NAK for UBX-CFG-PRT.
+const NAK_PRT: &[u8] = &[0xB5, 0x62, 0x05, 0x00, 0x02, 0x00, 0x06, 0x00, 0x0D, 0x32];
+
+fn is_vtg(message: &Message) -> bool {
+    matches!(message, Message::Nmea(Sentence::Vtg(_)))
+}
+
+#[test]
+fn demultiplexes_ubx_and_nmea() {
+    let mut codec = UbxCodec::new();
+    let mut buf = BytesMut::new();
+    buf.extend_from_slice(b"10.2,K*48\r\n");
+    buf.extend_from_slice(VTG);
+    buf.extend_from_slice(ACK_PRT);
+    // A sentence cut short by a frame.
+    buf.extend_from_slice(b"$GPVTG,054.7,T");
+    buf.extend_from_slice(ACK_PRT);
+    buf.extend_from_slice(VTG);
+
+    assert!(is_vtg(&codec.decode(&mut buf).unwrap().unwrap()));
+    for _ in 0..2 {
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Message::Ubx(0x05, 0x01, BytesMut::from(&[0x06, 0x00][..])))
+        );
+    }
+    assert!(is_vtg(&codec.decode(&mut buf).unwrap().unwrap()));
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn waits_for_whole_frames() {
+    let mut codec = UbxCodec::new();
+    let mut buf = BytesMut::new();
+    for (i, byte) in ACK_PRT.iter().enumerate() {
+        buf.extend_from_slice(&[*byte]);
+        let message = codec.decode(&mut buf).unwrap();
+        assert_eq!(message.is_some(), i == ACK_PRT.len() - 1);
+    }
+    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+
+    buf.extend_from_slice(&ACK_PRT[..7]);
+    assert!(codec.decode_eof(&mut buf).unwrap().is_none());
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn bad_frames_and_sentences_do_not_stop_decoding() {
+    let mut codec = UbxCodec::new().with_max_payload_length(4);
+    let mut buf = BytesMut::new();
+    let mut corrupt = ACK_PRT.to_vec();
+    corrupt[7] ^= 0x01;
+    buf.extend_from_slice(&corrupt);
+    // Sync bytes followed by a length over the limit.
+    buf.extend_from_slice(&[0xB5, 0x62, 0x01, 0x07, 0x5C, 0x00]);
+    buf.extend_from_slice(b"$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*49\r\n");
+    buf.extend_from_slice(NAK_PRT);
+
+    assert!(matches!(codec.decode(&mut buf), Err(UbxError::BadChecksum)));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(UbxError::FrameTooLong)
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Err(UbxError::Nmea(NmeaError::BadChecksum { .. }))
+    ));
+    assert!(matches!(
+        codec.decode(&mut buf),
+        Ok(Some(Message::Ubx(0x05, 0x00, _)))
+    ));
+}
+
+#[test]
+fn encodes_frames() {
+    let mut codec = UbxCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode((0x05, 0x01, [0x06, 0x00]), &mut buf).unwrap();
+    assert_eq!(&buf[..], ACK_PRT);
+
+    let mut codec = codec.with_max_payload_length(1);
+    assert!(matches!(
+        codec.encode((0x05, 0x01, [0x06, 0x00]), &mut buf),
+        Err(UbxError::FrameTooLong)
+    ));
+}
+
+#[cfg(unix)]
+mod client {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_serial::ubx::Client;
+    use tokio_serial::SerialStream;
+
+    // UBX-CFG-PRT polling the settings of port 1.
+    const CFG_PRT: &[u8] = &[0xB5, 0x62, 0x06, 0x00, 0x01, 0x00, 0x01, 0x08, 0x22];
+
+    async fn expect_frame(receiver: &mut SerialStream, frame: &[u8]) {
+        let mut buf = vec![0u8; frame.len()];
+        receiver.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, frame);
+    }
+
+    #[tokio::test]
+    async fn configure_waits_for_the_ack() {
+        let (mut receiver, port) = SerialStream::pair().unwrap();
+        let mut client = Client::new(port);
+
+        let peer = tokio::spawn(async move {
+            expect_frame(&mut receiver, CFG_PRT).await;
+            // An acknowledgement for another message and a sentence arrive first.
+            receiver
+                .write_all(&[0xB5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x01, 0x0F, 0x38])
+                .await
+                .unwrap();
+            receiver.write_all(VTG).await.unwrap();
+            receiver.write_all(ACK_PRT).await.unwrap();
+            receiver.write_all(VTG).await.unwrap();
+
+            expect_frame(&mut receiver, CFG_PRT).await;
+            receiver.write_all(NAK_PRT).await.unwrap();
+
+            expect_frame(&mut receiver, CFG_PRT).await;
+            receiver
+        });
+
+        client.configure(0x00, &[0x01]).await.unwrap();
+        assert!(matches!(
+            client.recv().await.unwrap(),
+            Some(Message::Ubx(0x05, 0x01, ref payload)) if payload[..] == [0x06, 0x01]
+        ));
+        assert!(is_vtg(&client.recv().await.unwrap().unwrap()));
+        assert!(is_vtg(&client.recv().await.unwrap().unwrap()));
+
+        assert!(matches!(
+            client.configure(0x00, &[0x01]).await,
+            Err(UbxError::Nak {
+                class: 0x06,
+                id: 0x00
+            })
+        ));
+
+        let mut client = client.with_timeout(Duration::from_millis(100));
+        assert!(matches!(
+            client.configure(0x00, &[0x01]).await,
+            Err(UbxError::Timeout)
+        ));
+        peer.await.unwrap();
+    }
+}
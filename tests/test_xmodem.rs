@@ -0,0 +1,229 @@
+#![cfg(all(feature = "xmodem", unix))]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::xmodem::{self, Progress, Xmodem, XmodemError};
+use tokio_serial::SerialStream;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+
+fn crc16(data: &[u8]) -> [u8; 2] {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc.to_be_bytes()
+}
+
+fn block(number: u8, data: &[u8]) -> Vec<u8> {
+    let mut block = vec![SOH, number, !number];
+    block.extend_from_slice(data);
+    block.resize(131, 0x1A);
+    let sum = block[3..].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    block.push(sum);
+    block
+}
+
+async fn read_byte(port: &mut SerialStream) -> u8 {
+    tokio::time::timeout(Duration::from_secs(5), port.read_u8())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+fn fast() -> Xmodem {
+    Xmodem::new().with_timeout(Duration::from_millis(200))
+}
+
+#[tokio::test]
+async fn transfers_with_crc() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+
+    let sending = data.clone();
+    let send = tokio::spawn(async move {
+        let mut progress = Vec::new();
+        let sent = fast()
+            .send(&mut sender, &mut &sending[..], |p| progress.push(p.bytes))
+            .await
+            .unwrap();
+        (sent, progress)
+    });
+
+    let mut received = Vec::new();
+    let mut blocks = 0;
+    let written = fast()
+        .receive(&mut receiver, &mut received, |p: Progress| {
+            blocks = p.blocks
+        })
+        .await
+        .unwrap();
+    let (sent, progress) = send.await.unwrap();
+
+    assert_eq!(sent, 300);
+    assert_eq!(progress, vec![128, 256, 300]);
+    assert_eq!(written, 384);
+    assert_eq!(blocks, 3);
+    assert_eq!(&received[..300], &data[..]);
+    assert!(received[300..].iter().all(|b| *b == 0x1A));
+}
+
+#[tokio::test]
+async fn falls_back_to_checksum_and_short_blocks() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let data = vec![0x55u8; 1000];
+
+    let sending = data.clone();
+    let send = tokio::spawn(async move {
+        fast()
+            .with_1k(true)
+            .send(&mut sender, &mut &sending[..], |_| {})
+            .await
+    });
+
+    let mut received = Vec::new();
+    let mut blocks = 0;
+    fast()
+        .with_crc(false)
+        .receive(&mut receiver, &mut received, |p| blocks = p.blocks)
+        .await
+        .unwrap();
+    assert_eq!(send.await.unwrap().unwrap(), 1000);
+    // Without CRC-16 the sender keeps to 128-byte blocks.
+    assert_eq!(blocks, 8);
+    assert_eq!(&received[..1000], &data[..]);
+}
+
+#[tokio::test]
+async fn sends_1k_blocks_and_retries_rejected_ones() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let data: Vec<u8> = (0..1100).map(|i| (i % 251) as u8).collect();
+
+    let sending = data.clone();
+    let send = tokio::spawn(async move {
+        fast()
+            .with_1k(true)
+            .send(&mut sender, &mut &sending[..], |_| {})
+            .await
+    });
+
+    receiver.write_all(b"C").await.unwrap();
+    let mut first = vec![0u8; 1029];
+    for reply in [NAK, ACK] {
+        receiver.read_exact(&mut first).await.unwrap();
+        receiver.write_all(&[reply]).await.unwrap();
+    }
+    assert_eq!(&first[..3], &[STX, 1, 0xFE]);
+    assert_eq!(&first[3..1027], &data[..1024]);
+    assert_eq!(first[1027..], crc16(&first[3..1027]));
+
+    // What is left fits in a short block.
+    let mut second = vec![0u8; 133];
+    receiver.read_exact(&mut second).await.unwrap();
+    receiver.write_all(&[ACK]).await.unwrap();
+    assert_eq!(&second[..3], &[SOH, 2, 0xFD]);
+    assert_eq!(&second[3..79], &data[1024..]);
+    assert!(second[79..131].iter().all(|b| *b == 0x1A));
+    assert_eq!(second[131..], crc16(&second[3..131]));
+
+    // Some receivers reject the first EOT.
+    assert_eq!(read_byte(&mut receiver).await, EOT);
+    receiver.write_all(&[NAK]).await.unwrap();
+    assert_eq!(read_byte(&mut receiver).await, EOT);
+    receiver.write_all(&[ACK]).await.unwrap();
+    assert_eq!(send.await.unwrap().unwrap(), 1100);
+}
+
+#[tokio::test]
+async fn receives_damaged_and_repeated_blocks() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        let mut received = Vec::new();
+        fast()
+            .with_crc(false)
+            .receive(&mut receiver, &mut received, |_| {})
+            .await
+            .map(|_| received)
+    });
+
+    assert_eq!(read_byte(&mut sender).await, NAK);
+    let mut damaged = block(1, b"first");
+    damaged[130] ^= 0xFF;
+    sender.write_all(&damaged).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, NAK);
+    // Line noise before a block is skipped.
+    sender.write_all(b"\r\n").await.unwrap();
+    sender.write_all(&block(1, b"first")).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    // As if the ACK was lost.
+    sender.write_all(&block(1, b"first")).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    sender.write_all(&block(2, b"second")).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    sender.write_all(&[EOT]).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+
+    let received = receive.await.unwrap().unwrap();
+    assert_eq!(received.len(), 256);
+    assert_eq!(&received[..5], b"first");
+    assert_eq!(&received[128..134], b"second");
+}
+
+#[tokio::test]
+async fn stops_when_cancelled() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let send = tokio::spawn(async move { xmodem::send(&mut sender, &mut &[1u8; 10][..]).await });
+    receiver.write_all(b"C").await.unwrap();
+    let mut first = [0u8; 133];
+    receiver.read_exact(&mut first).await.unwrap();
+    receiver.write_all(&[CAN, CAN]).await.unwrap();
+    assert!(matches!(send.await.unwrap(), Err(XmodemError::Cancelled)));
+
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let receive =
+        tokio::spawn(async move { xmodem::receive(&mut receiver, &mut Vec::new()).await });
+    assert_eq!(read_byte(&mut sender).await, b'C');
+    sender.write_all(&[CAN, CAN]).await.unwrap();
+    assert!(matches!(
+        receive.await.unwrap(),
+        Err(XmodemError::Cancelled)
+    ));
+}
+
+#[tokio::test]
+async fn gives_up_when_nobody_answers() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        fast()
+            .with_retries(4)
+            .receive(&mut receiver, &mut Vec::new(), |_| {})
+            .await
+    });
+    // Asks for CRC-16 three times, then for the checksum, then cancels.
+    let mut asked = [0u8; 7];
+    sender.read_exact(&mut asked).await.unwrap();
+    assert_eq!(asked, [b'C', b'C', b'C', NAK, NAK, CAN, CAN]);
+    assert!(matches!(receive.await.unwrap(), Err(XmodemError::Timeout)));
+
+    let (mut sender, _receiver) = SerialStream::pair().unwrap();
+    let result = fast()
+        .with_retries(1)
+        .send(&mut sender, &mut &[0u8; 10][..], |_| {})
+        .await;
+    assert!(matches!(result, Err(XmodemError::Timeout)));
+}
@@ -0,0 +1,232 @@
+#![cfg(all(feature = "ymodem", unix))]
+
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::xmodem::XmodemError;
+use tokio_serial::ymodem::{FileInfo, Ymodem};
+use tokio_serial::SerialStream;
+
+const SOH: u8 = 0x01;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+
+fn crc16(data: &[u8]) -> [u8; 2] {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc.to_be_bytes()
+}
+
+fn block(number: u8, data: &[u8], padding: u8) -> Vec<u8> {
+    let mut block = vec![SOH, number, !number];
+    block.extend_from_slice(data);
+    block.resize(131, padding);
+    let crc = crc16(&block[3..]);
+    block.extend_from_slice(&crc);
+    block
+}
+
+async fn read_byte(port: &mut SerialStream) -> u8 {
+    tokio::time::timeout(Duration::from_secs(5), port.read_u8())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+async fn read_block(port: &mut SerialStream) -> Vec<u8> {
+    let mut block = vec![0u8; 133];
+    port.read_exact(&mut block).await.unwrap();
+    block
+}
+
+fn fast() -> Ymodem {
+    Ymodem::new().with_timeout(Duration::from_millis(200))
+}
+
+async fn transfer_batch(ymodem: Ymodem) {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let first: Vec<u8> = (0..2000).map(|i| (i % 253) as u8).collect();
+    let second = b"no size given".to_vec();
+    let first_info = FileInfo::new("dir/first.bin")
+        .with_size(2000)
+        .with_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        .with_mode(0o100644);
+    let second_info = FileInfo::new("second.txt");
+
+    let files = vec![
+        (first_info.clone(), first.clone()),
+        (second_info.clone(), second.clone()),
+    ];
+    let config = ymodem.clone();
+    let send = tokio::spawn(async move {
+        let mut batch = config.sender(&mut sender);
+        for (info, data) in files {
+            batch.send(&info, &mut &data[..], |_| {}).await.unwrap();
+        }
+        batch.finish().await.unwrap();
+        // YMODEM-G doesn't wait for the end of the batch to be acknowledged.
+        sender
+    });
+
+    let mut batch = ymodem.receiver(&mut receiver);
+    let info = batch.next_file().await.unwrap().unwrap();
+    assert_eq!(info, first_info);
+    let mut received = Vec::new();
+    let mut totals = Vec::new();
+    let written = batch
+        .receive(&mut received, |p| totals.push((p.bytes, p.total)))
+        .await
+        .unwrap();
+    assert_eq!(written, 2000);
+    assert_eq!(received, first);
+    assert_eq!(totals.last(), Some(&(2000, Some(2000))));
+
+    let info = batch.next_file().await.unwrap().unwrap();
+    assert_eq!(info, second_info);
+    let mut received = Vec::new();
+    batch.receive(&mut received, |_| {}).await.unwrap();
+    // Without a size, the padding stays.
+    assert_eq!(received.len(), 128);
+    assert_eq!(&received[..second.len()], &second[..]);
+
+    assert_eq!(batch.next_file().await.unwrap(), None);
+    send.await.unwrap();
+}
+
+#[tokio::test]
+async fn transfers_a_batch() {
+    transfer_batch(fast()).await;
+}
+
+#[tokio::test]
+async fn transfers_a_batch_streaming() {
+    transfer_batch(fast().with_streaming(true)).await;
+}
+
+#[tokio::test]
+async fn sends_file_info_in_block_0() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let send = tokio::spawn(async move {
+        let mut batch = fast().sender(&mut sender);
+        let info = FileInfo::new("foo.c")
+            .with_size(5)
+            .with_modified(UNIX_EPOCH + Duration::from_secs(12))
+            .with_mode(0o644);
+        batch.send(&info, &mut &b"hello"[..], |_| {}).await.unwrap();
+        batch.finish().await.unwrap();
+    });
+
+    receiver.write_all(b"C").await.unwrap();
+    assert_eq!(
+        read_block(&mut receiver).await,
+        block(0, b"foo.c\x005 14 644", 0)
+    );
+    receiver.write_all(&[ACK, b'C']).await.unwrap();
+    assert_eq!(read_block(&mut receiver).await, block(1, b"hello", 0x1A));
+    receiver.write_all(&[ACK]).await.unwrap();
+    assert_eq!(read_byte(&mut receiver).await, EOT);
+    receiver.write_all(&[NAK]).await.unwrap();
+    assert_eq!(read_byte(&mut receiver).await, EOT);
+    receiver.write_all(&[ACK, b'C']).await.unwrap();
+    // An empty block 0 ends the batch.
+    assert_eq!(read_block(&mut receiver).await, block(0, &[], 0));
+    receiver.write_all(&[ACK]).await.unwrap();
+    send.await.unwrap();
+}
+
+#[tokio::test]
+async fn receives_from_a_plain_sender() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().receiver(&mut receiver);
+        let info = batch.next_file().await.unwrap().unwrap();
+        let mut received = Vec::new();
+        batch.receive(&mut received, |_| {}).await.unwrap();
+        assert_eq!(batch.next_file().await.unwrap(), None);
+        (info, received)
+    });
+
+    assert_eq!(read_byte(&mut sender).await, b'C');
+    sender
+        .write_all(&block(0, b"bar.txt\x003 0", 0))
+        .await
+        .unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    assert_eq!(read_byte(&mut sender).await, b'C');
+    let mut damaged = block(1, b"abc", 0x1A);
+    damaged[131] ^= 0xFF;
+    sender.write_all(&damaged).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, NAK);
+    sender.write_all(&block(1, b"abc", 0x1A)).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    // The first EOT is rejected, to make sure.
+    sender.write_all(&[EOT]).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, NAK);
+    sender.write_all(&[EOT]).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+    assert_eq!(read_byte(&mut sender).await, b'C');
+    sender.write_all(&block(0, &[], 0)).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, ACK);
+
+    let (info, received) = receive.await.unwrap();
+    assert_eq!(info.name(), "bar.txt");
+    assert_eq!(info.size(), Some(3));
+    assert_eq!(info.modified(), None);
+    assert_eq!(received, b"abc");
+}
+
+#[tokio::test]
+async fn streaming_gives_up_on_a_damaged_block() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().with_streaming(true).receiver(&mut receiver);
+        batch.next_file().await.unwrap().unwrap();
+        batch.receive(&mut Vec::new(), |_| {}).await
+    });
+
+    assert_eq!(read_byte(&mut sender).await, b'G');
+    sender.write_all(&block(0, b"x\x00", 0)).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, b'G');
+    let mut damaged = block(1, b"data", 0x1A);
+    damaged[10] ^= 0xFF;
+    sender.write_all(&damaged).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, CAN);
+    assert_eq!(read_byte(&mut sender).await, CAN);
+    assert!(matches!(
+        receive.await.unwrap(),
+        Err(XmodemError::TooManyErrors)
+    ));
+}
+
+#[tokio::test]
+async fn rejects_an_out_of_range_modification_time() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().with_retries(1).receiver(&mut receiver);
+        batch.next_file().await
+    });
+
+    let header = block(0, b"a\x001 1000000000000000000000", 0);
+    assert_eq!(read_byte(&mut sender).await, b'C');
+    sender.write_all(&header).await.unwrap();
+    assert_eq!(read_byte(&mut sender).await, NAK);
+    sender.write_all(&header).await.unwrap();
+    assert!(matches!(
+        receive.await.unwrap(),
+        Err(XmodemError::TooManyErrors)
+    ));
+}
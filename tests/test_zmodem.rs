@@ -0,0 +1,316 @@
+#![cfg(all(feature = "zmodem", unix))]
+
+use std::io::Cursor;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::ymodem::FileInfo;
+use tokio_serial::zmodem::{Detector, Start, Zmodem, ZmodemError};
+use tokio_serial::SerialStream;
+
+const ZRQINIT: &[u8] = b"**\x18B00000000000000\r\x8a\x11";
+const ZFIN: &[u8] = b"**\x18B0800000000022d\r\x8a";
+
+fn fast() -> Zmodem {
+    Zmodem::new().with_timeout(Duration::from_millis(500))
+}
+
+fn contents(len: usize) -> Vec<u8> {
+    // Every byte value, so everything that needs escaping is escaped.
+    (0..len).map(|i| (i * 7 % 256) as u8).collect()
+}
+
+fn crc16(data: &[u8]) -> [u8; 2] {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc.to_be_bytes()
+}
+
+/// Escapes what ZMODEM always escapes.
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::new();
+    for &byte in data {
+        match byte & 0x7F {
+            0x10 | 0x11 | 0x13 | 0x18 => escaped.extend(&[0x18, byte ^ 0x40]),
+            _ => escaped.push(byte),
+        }
+    }
+    escaped
+}
+
+/// A ZFILE header with CRC-16, followed by `info` as its subpacket.
+fn zfile(info: &[u8]) -> Vec<u8> {
+    let header = [0x04, 0, 0, 0, 0x01];
+    let mut frame = b"*\x18A".to_vec();
+    frame.extend(escape(&[&header[..], &crc16(&header)].concat()));
+    frame.extend(escape(info));
+    frame.extend(b"\x18k");
+    frame.extend(escape(&crc16(&[info, b"k"].concat())));
+    frame
+}
+
+/// Reads from `port` until what was read ends with `end`.
+async fn read_until(port: &mut SerialStream, end: &[u8]) -> Vec<u8> {
+    let mut read = Vec::new();
+    while !read.ends_with(end) {
+        let byte = tokio::time::timeout(Duration::from_secs(5), port.read_u8())
+            .await
+            .unwrap()
+            .unwrap();
+        read.push(byte);
+    }
+    read
+}
+
+#[tokio::test]
+async fn transfers_a_batch() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let first = contents(5000);
+    let first_info = FileInfo::new("first.bin")
+        .with_size(5000)
+        .with_modified(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+        .with_mode(0o100600);
+    let second_info = FileInfo::new("empty").with_size(0);
+
+    let files = vec![
+        (first_info.clone(), first.clone()),
+        (second_info.clone(), Vec::new()),
+    ];
+    let send = tokio::spawn(async move {
+        let mut batch = fast().sender(&mut sender);
+        for (info, data) in files {
+            let len = data.len() as u64;
+            let sent = batch.send(&info, &mut Cursor::new(data), |_| {}).await;
+            assert_eq!(sent.unwrap(), len);
+        }
+        batch.finish().await.unwrap();
+        sender
+    });
+
+    let mut batch = fast().receiver(&mut receiver);
+    assert_eq!(batch.next_file().await.unwrap(), Some(first_info));
+    let mut received = Vec::new();
+    let mut positions = Vec::new();
+    let written = batch
+        .receive(&mut received, |p| positions.push((p.bytes, p.total)))
+        .await
+        .unwrap();
+    assert_eq!(written, 5000);
+    assert_eq!(received, first);
+    assert_eq!(positions.first(), Some(&(1024, Some(5000))));
+    assert_eq!(positions.last(), Some(&(5000, Some(5000))));
+
+    assert_eq!(batch.next_file().await.unwrap(), Some(second_info));
+    let mut received = Vec::new();
+    assert_eq!(batch.receive(&mut received, |_| {}).await.unwrap(), 0);
+    assert_eq!(batch.next_file().await.unwrap(), None);
+    send.await.unwrap();
+}
+
+#[tokio::test]
+async fn resumes_after_damaged_data() {
+    let (mut sender, sender_end) = SerialStream::pair().unwrap();
+    let (receiver_end, mut receiver) = SerialStream::pair().unwrap();
+    let data = contents(20000);
+
+    // Pass everything along, damaging one byte of the data on its way.
+    let (mut from_sender, mut to_sender) = tokio::io::split(sender_end);
+    let (mut from_receiver, mut to_receiver) = tokio::io::split(receiver_end);
+    tokio::spawn(async move {
+        let mut buf = [0u8; 256];
+        let mut passed = 0;
+        while let Ok(n) = from_sender.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            if (passed..passed + n).contains(&6000) {
+                buf[6000 - passed] ^= 0x01;
+            }
+            passed += n;
+            to_receiver.write_all(&buf[..n]).await.unwrap();
+        }
+    });
+    tokio::spawn(async move {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = from_receiver.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            to_sender.write_all(&buf[..n]).await.unwrap();
+        }
+    });
+
+    let sending = data.clone();
+    let send = tokio::spawn(async move {
+        let mut batch = fast().sender(&mut sender);
+        let info = FileInfo::new("big.bin").with_size(20000);
+        let mut positions = Vec::new();
+        let sent = batch
+            .send(&info, &mut Cursor::new(sending), |p| {
+                positions.push(p.bytes)
+            })
+            .await
+            .unwrap();
+        batch.finish().await.unwrap();
+        (sent, positions, sender)
+    });
+
+    let mut batch = fast().receiver(&mut receiver);
+    batch.next_file().await.unwrap().unwrap();
+    let mut received = Vec::new();
+    batch.receive(&mut received, |_| {}).await.unwrap();
+    assert_eq!(batch.next_file().await.unwrap(), None);
+    assert_eq!(received, data);
+
+    let (sent, positions, _sender) = send.await.unwrap();
+    assert_eq!(sent, 20000);
+    // The damaged subpacket was sent again, along with any sent after it.
+    assert!(positions.len() > 20);
+    assert!(positions.windows(2).any(|pair| pair[1] <= pair[0]));
+}
+
+#[tokio::test]
+async fn resumes_from_an_offset_and_skips_files() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let data = contents(3000);
+
+    let sending = data.clone();
+    let send = tokio::spawn(async move {
+        let mut batch = fast().sender(&mut sender);
+        let info = FileInfo::new("unwanted");
+        let skipped = batch
+            .send(&info, &mut Cursor::new(vec![1; 10]), |_| {})
+            .await;
+        assert!(matches!(skipped, Err(ZmodemError::Skipped)));
+        let info = FileInfo::new("partial").with_size(3000);
+        let sent = batch.send(&info, &mut Cursor::new(sending), |_| {}).await;
+        assert_eq!(sent.unwrap(), 3000);
+        batch.finish().await.unwrap();
+        sender
+    });
+
+    let mut batch = fast().receiver(&mut receiver);
+    let info = batch.next_file().await.unwrap().unwrap();
+    assert_eq!(info.name(), "unwanted");
+    batch.skip().await.unwrap();
+
+    let info = batch.next_file().await.unwrap().unwrap();
+    assert_eq!(info.name(), "partial");
+    let mut received = data[..1000].to_vec();
+    let written = batch.resume(&mut received, 1000, |_| {}).await.unwrap();
+    assert_eq!(written, 2000);
+    assert_eq!(received, data);
+    assert_eq!(batch.next_file().await.unwrap(), None);
+    send.await.unwrap();
+}
+
+#[tokio::test]
+async fn speaks_like_lrzsz() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    // A receiver starts by offering full duplex, overlapped I/O and CRC-32.
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().with_retries(0).receiver(&mut receiver);
+        batch.next_file().await
+    });
+    assert_eq!(
+        read_until(&mut sender, b"\x11").await,
+        b"**\x18B0100000023be50\r\x8a\x11"
+    );
+    sender.write_all(&[0x18; 8]).await.unwrap();
+    assert!(matches!(
+        receive.await.unwrap(),
+        Err(ZmodemError::Cancelled)
+    ));
+
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let send = tokio::spawn(async move {
+        let mut batch = fast().sender(&mut sender);
+        let info = FileInfo::new("a");
+        let skipped = batch
+            .send(&info, &mut Cursor::new(vec![0; 4]), |_| {})
+            .await;
+        assert!(matches!(skipped, Err(ZmodemError::Skipped)));
+        batch.finish().await.unwrap();
+        sender
+    });
+    assert_eq!(
+        read_until(&mut receiver, ZRQINIT).await,
+        [b"rz\r", ZRQINIT].concat()
+    );
+    // Full duplex, but only CRC-16.
+    receiver
+        .write_all(b"**\x18B0100000001ba70\r\x8a\x11")
+        .await
+        .unwrap();
+    let zfile = read_until(&mut receiver, b"\x18k").await;
+    assert_eq!(&zfile[..8], b"*\x18A\x04\x00\x00\x00\x01");
+    assert!(zfile.ends_with(b"a\x00\x00\x18k"));
+    receiver
+        .write_all(b"**\x18B05000000002357\r\x8a\x11")
+        .await
+        .unwrap();
+    assert!(read_until(&mut receiver, ZFIN).await.ends_with(ZFIN));
+    receiver.write_all(ZFIN).await.unwrap();
+    assert_eq!(read_until(&mut receiver, b"OO").await, b"OO");
+    send.await.unwrap();
+}
+
+#[tokio::test]
+async fn rejects_an_out_of_range_modification_time() {
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().with_retries(0).receiver(&mut receiver);
+        batch.next_file().await
+    });
+    read_until(&mut sender, b"\x11").await;
+
+    // A well-formed ZFILE whose file would have been modified after the end of time.
+    let frame = zfile(b"a\x001 1000000000000000000000\x00");
+    sender.write_all(&frame).await.unwrap();
+    assert!(matches!(
+        receive.await.unwrap(),
+        Err(ZmodemError::TooManyErrors)
+    ));
+
+    // The same frame with a sensible time goes through.
+    let (mut sender, mut receiver) = SerialStream::pair().unwrap();
+    let receive = tokio::spawn(async move {
+        let mut batch = fast().with_retries(0).receiver(&mut receiver);
+        batch.next_file().await
+    });
+    read_until(&mut sender, b"\x11").await;
+    sender
+        .write_all(&zfile(b"a\x001 13727410000\x00"))
+        .await
+        .unwrap();
+    let info = receive.await.unwrap().unwrap().unwrap();
+    assert_eq!(
+        info.modified(),
+        Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000))
+    );
+}
+
+#[test]
+fn detects_sessions_starting() {
+    let mut detector = Detector::new();
+    assert_eq!(detector.detect(b"$ ls\r\n*** not yet **\x18"), None);
+    assert_eq!(detector.detect(b"B"), None);
+    assert_eq!(detector.detect(b"01000000"), Some((2, Start::Send)));
+
+    let mut detector = Detector::new();
+    assert_eq!(
+        detector.detect(b"rz\r***\x18B00000000000000"),
+        Some((10, Start::Receive))
+    );
+    assert_eq!(detector.detect(b"**\x18B02"), None);
+}